@@ -0,0 +1,838 @@
+use std::sync::Arc;
+
+use crossterm::event::{KeyCode, KeyEvent, MouseButton, MouseEvent, MouseEventKind};
+use tui::backend::Backend;
+use tui::layout::{Constraint, Direction, Layout, Margin, Rect};
+use tui::style::{Color as TermColor, Modifier, Style};
+use tui::text::{Span, Spans};
+use tui::widgets::{Block, Borders, List, ListItem, ListState, Paragraph};
+use tui::Frame;
+
+use crate::clock::Clock;
+use crate::eval::Evaluator;
+use crate::game::Game;
+use crate::search::Score;
+use crate::skill::Difficulty;
+use crate::theme::{GlyphSet, Theme};
+use crate::view::{BoardView, Orientation};
+use crate::widget::BoardWidget;
+use crate::{Board, Color, Coordinate, Move, Piece, Pieces};
+
+mod analysis;
+mod coach;
+mod command;
+mod editor;
+mod engine;
+mod promotion;
+mod replay;
+mod report;
+mod timing;
+
+pub use analysis::san_line;
+use analysis::Analysis;
+use coach::Coach;
+use editor::Editor;
+use engine::Thinking;
+use replay::{GamePicker, Replay};
+use report::GameReport;
+
+/// State of the terminal app, kept between frames
+pub struct App {
+  game:           Game,
+  // Moves of the game in algebraic notation, kept along with it for the
+  // move list drawn every frame
+  san:            Vec<String>,
+  cursor:         Coordinate,
+  selected:       Option<Coordinate>,
+  // Legal destinations of the selected piece
+  targets:        Vec<Coordinate>,
+  // Feedback about the last action, e.g. an illegal move
+  message:        Option<String>,
+  quit:           bool,
+  // Where the board was drawn last, to map mouse positions to squares
+  board_area:     Rect,
+  hover:          Option<Coordinate>,
+  // Square a press started on, and whether its piece was selected before
+  drag:           Option<(Coordinate, bool)>,
+  ascii_art:      bool,
+  orientation:    Orientation,
+  theme:          Theme,
+  glyphs:         GlyphSet,
+  // Earlier position being looked at, with the number of moves played to reach it
+  browsing:       Option<(usize, Board)>,
+  move_list:      ListState,
+  // Text to put on the clipboard, taken by the terminal loop
+  clipboard:      Option<String>,
+  // Text typed after `:`, while the command line is open
+  command:        Option<String>,
+  history:        Vec<String>,
+  // Entry of the history being edited, counted from the most recent
+  recalled:       Option<usize>,
+  // Candidates of the last tab completion and the one shown
+  completions:    Option<(Vec<String>, usize)>,
+  // Side the computer plays
+  engine:         Option<Color>,
+  evaluator:      Arc<Evaluator>,
+  difficulty:     Difficulty,
+  thinking:       Option<Thinking>,
+  // Score of the computer's last search, from its point of view
+  engine_score:   Option<Score>,
+  analysis:       Option<Analysis>,
+  analysis_lines: usize,
+  clock:          Option<Clock>,
+  // Pawn move waiting for a piece, with the one highlighted in the picker
+  promotion:      Option<(Move, usize)>,
+  editor:         Option<Editor>,
+  replay:         Option<Replay>,
+  // Games of a PGN file to choose one to replay from
+  picker:         Option<GamePicker>,
+  // Moves are announced in words, for screen readers
+  accessible:     bool,
+  announcements:  Vec<String>,
+  coach:          Coach,
+  report:         Option<GameReport>
+}
+
+impl Default for App {
+  fn default() -> Self { App::new(Game::default()) }
+}
+
+impl App {
+  pub fn new(game: Game) -> Self {
+    App {
+      san: game.san_moves(),
+      game,
+      cursor: coord!(6, 4),
+      selected: None,
+      targets: Vec::new(),
+      message: None,
+      quit: false,
+      board_area: Rect::default(),
+      hover: None,
+      drag: None,
+      ascii_art: false,
+      orientation: Orientation::default(),
+      theme: Theme::default(),
+      glyphs: GlyphSet::default(),
+      browsing: None,
+      move_list: ListState::default(),
+      clipboard: None,
+      command: None,
+      history: Vec::new(),
+      recalled: None,
+      completions: None,
+      engine: None,
+      evaluator: Arc::new(Evaluator::default()),
+      difficulty: Difficulty::default(),
+      thinking: None,
+      engine_score: None,
+      analysis: None,
+      analysis_lines: 3,
+      clock: None,
+      promotion: None,
+      editor: None,
+      replay: None,
+      picker: None,
+      accessible: false,
+      announcements: Vec::new(),
+      coach: Coach::default(),
+      report: None
+    }
+  }
+
+  pub fn game(&self) -> &Game { &self.game }
+
+  pub fn cursor(&self) -> Coordinate { self.cursor }
+
+  pub fn selected(&self) -> Option<Coordinate> { self.selected }
+
+  pub fn message(&self) -> Option<&str> { self.message.as_deref() }
+
+  pub fn should_quit(&self) -> bool { self.quit }
+
+  /// Where the board was drawn last
+  pub fn board_area(&self) -> Rect { self.board_area }
+
+  pub fn orientation(&self) -> Orientation { self.orientation }
+
+  pub fn set_orientation(
+    &mut self,
+    orientation: Orientation
+  ) {
+    self.orientation = orientation;
+  }
+
+  pub fn set_theme(
+    &mut self,
+    theme: Theme
+  ) {
+    self.theme = theme;
+  }
+
+  pub fn set_glyphs(
+    &mut self,
+    glyphs: GlyphSet
+  ) {
+    self.glyphs = glyphs;
+  }
+
+  /// Number of moves played to reach the position on screen
+  pub fn ply(&self) -> usize {
+    match &self.browsing {
+      Some((ply, _)) => *ply,
+      None => self.game.moves().len()
+    }
+  }
+
+  /// Shows the position after `ply` moves, the last one goes back to the game
+  pub fn go_to_ply(
+    &mut self,
+    ply: usize
+  ) {
+    let ply = ply.min(self.game.moves().len());
+
+    self.deselect();
+    self.browsing = if ply == self.game.moves().len() {
+      None
+    } else {
+      Some((ply, self.game.board_at(ply)))
+    };
+  }
+
+  /// Checks on the background searches, called regularly by the terminal loop
+  pub fn tick(&mut self) {
+    self.update_clock();
+    self.update_engine();
+    self.update_analysis();
+    self.update_replay();
+    self.update_coach();
+    self.update_report();
+  }
+
+  /// Text the user asked to copy, e.g. the FEN
+  pub fn take_clipboard(&mut self) -> Option<String> { self.clipboard.take() }
+
+  // Board on screen, which differs from the game's one while browsing
+  fn board(&self) -> &Board {
+    match (&self.editor, &self.browsing) {
+      (Some(editor), _) => &editor.board,
+      (None, Some((_, board))) => board,
+      (None, None) => self.game.board()
+    }
+  }
+
+  /// The board as it is shown on screen
+  pub fn view(&self) -> BoardView<'_> {
+    BoardView::new(self.board())
+      .orientation(self.orientation)
+      .first_to_move(self.game.start().turn)
+      .glyphs(self.glyphs)
+  }
+
+  // Shows the board from the other side, keeping it there
+  fn flip(&mut self) {
+    self.orientation = match self.view().bottom() {
+      Color::White => Orientation::Black,
+      Color::Black => Orientation::White
+    };
+  }
+
+  pub fn handle_key(
+    &mut self,
+    key: KeyEvent
+  ) {
+    if self.command.is_some() {
+      return self.handle_command_key(key)
+    }
+    if self.promotion.is_some() {
+      return self.handle_promotion_key(key)
+    }
+    if self.picker.is_some() {
+      return self.handle_picker_key(key)
+    }
+    if self.editor.is_some() {
+      return self.handle_editor_key(key)
+    }
+    if self.handle_replay_key(key) || self.handle_warning_key(key) {
+      return
+    }
+
+    match key.code {
+      KeyCode::Char(':') => self.command = Some(String::new()),
+      KeyCode::Char('e') => self.set_analysis(!self.is_analysing()),
+      KeyCode::Char('?') => {
+        self.message = Some(match self.ask_hint() {
+          Ok(message) | Err(message) => message
+        })
+      },
+      KeyCode::Char('u') => {
+        self.message = Some(match self.take_back() {
+          Ok(message) | Err(message) => message
+        })
+      },
+      KeyCode::Char('q') => self.quit = true,
+      KeyCode::Up | KeyCode::Char('k') => self.move_cursor(-1, 0),
+      KeyCode::Down | KeyCode::Char('j') => self.move_cursor(1, 0),
+      KeyCode::Left | KeyCode::Char('h') => self.move_cursor(0, -1),
+      KeyCode::Right | KeyCode::Char('l') => self.move_cursor(0, 1),
+      KeyCode::Enter | KeyCode::Char(' ') => self.activate(self.cursor),
+      KeyCode::Esc => self.deselect(),
+      KeyCode::Char('a') => self.ascii_art = !self.ascii_art,
+      KeyCode::Char('f') => self.flip(),
+      KeyCode::Char('[') => self.go_to_ply(self.ply().saturating_sub(1)),
+      KeyCode::Char(']') => self.go_to_ply(self.ply() + 1),
+      KeyCode::Home => self.go_to_ply(0),
+      KeyCode::End => self.go_to_ply(usize::MAX),
+      KeyCode::Char('y') => {
+        self.clipboard = Some(self.board().get_fen());
+        self.message = Some(String::from("FEN copied"));
+      },
+      KeyCode::Char('o') => {
+        self.orientation = self.orientation.next();
+        self.message = Some(format!("Orientation: {}", self.orientation));
+      },
+      _ => {}
+    }
+  }
+
+  /// Clicks select and move, dragging a piece drops it on the square under
+  /// the pointer
+  pub fn handle_mouse(
+    &mut self,
+    event: MouseEvent
+  ) {
+    if self.promotion.is_some() {
+      if let MouseEventKind::Down(MouseButton::Left) = event.kind {
+        self.handle_promotion_click(event.column, event.row);
+      }
+      return
+    }
+    if self.editor.is_some() {
+      return self.handle_editor_mouse(event)
+    }
+
+    let square = self
+      .board_widget()
+      .square_at(self.board_area, event.column, event.row);
+    self.hover = square;
+
+    match event.kind {
+      MouseEventKind::Down(MouseButton::Left) => {
+        let coord = match square {
+          Some(coord) => coord,
+          None => return self.deselect()
+        };
+        self.cursor = coord;
+
+        // Pressing the selected piece again may start a drag, so it's only
+        // deselected when released without moving
+        let was_selected = self.selected == Some(coord);
+        if !was_selected {
+          self.activate(coord);
+        }
+        if self.selected == Some(coord) {
+          self.drag = Some((coord, was_selected));
+        }
+      },
+      MouseEventKind::Up(MouseButton::Left) => match (self.drag.take(), square) {
+        (Some((start, true)), Some(coord)) if coord == start => self.deselect(),
+        (Some((start, _)), Some(coord)) if coord != start && self.selected == Some(start) => {
+          self.cursor = coord;
+          self.activate(coord);
+        },
+        _ => {}
+      },
+      _ => {}
+    }
+  }
+
+  fn board_widget(&self) -> BoardWidget<'_> {
+    self
+      .view()
+      .widget()
+      .theme(self.theme)
+      .last_move(
+        self
+          .ply()
+          .checked_sub(1)
+          .filter(|_| self.editor.is_none())
+          .map(|ply| self.game.moves()[ply])
+      )
+      .cursor(Some(self.cursor))
+      .selected(self.selected)
+      .hover(self.hover)
+      .targets(&self.targets)
+      .hanging(self.hanging())
+      .threat(self.threat())
+      .hint(self.hint())
+      .ascii_art(self.ascii_art)
+  }
+
+  // Moves the cursor in screen directions, whichever side is at the bottom
+  fn move_cursor(
+    &mut self,
+    d_row: i8,
+    d_col: i8
+  ) {
+    let (d_row, d_col) = if self.view().is_flipped() {
+      (-d_row, -d_col)
+    } else {
+      (d_row, d_col)
+    };
+    let row = (self.cursor.row as i8 + d_row).clamp(0, 7) as u8;
+    let col = (self.cursor.col as i8 + d_col).clamp(0, 7) as u8;
+    self.cursor = coord!(row, col);
+  }
+
+  // Replaces the game, e.g. with a loaded one
+  fn set_game(
+    &mut self,
+    game: Game
+  ) {
+    self.stop_thinking();
+    self.san = game.san_moves();
+    self.game = game;
+    self.browsing = None;
+    self.replay = None;
+    self.engine_score = None;
+    self.deselect();
+    self.reset_clock();
+    self.engine_move();
+  }
+
+  // Plays a move of the user, then lets the computer answer
+  fn play(
+    &mut self,
+    mv: Move
+  ) -> Result<(), String> {
+    if self.replay.is_some() {
+      return Err(String::from("Press esc to play on from here"))
+    }
+    if self.browsing.is_some() {
+      return Err(String::from("Press End to go back to the game"))
+    }
+    if self.engine == Some(self.game.board().turn) {
+      return Err(String::from("It's the computer's turn"))
+    }
+    // The flag may have fallen since the last tick
+    self.update_clock();
+    if let Some(outcome) = self.game.outcome() {
+      return Err(outcome.to_string())
+    }
+    if self.check_blunder(mv) {
+      return Ok(())
+    }
+
+    let san = self.game.board().to_san(mv);
+    let words = self.game.board().describe_move(mv);
+    self.game.play(mv)?;
+    self.san.push(san);
+    self.announce(words);
+    self.clock_move();
+    self.engine_move();
+    Ok(())
+  }
+
+  pub fn is_accessible(&self) -> bool { self.accessible }
+
+  /// Announces moves in words, in the message line and to
+  /// `take_announcements`
+  pub fn set_accessible(
+    &mut self,
+    on: bool
+  ) {
+    self.accessible = on;
+    self.announcements.clear();
+  }
+
+  /// Moves played since the last call, in words, when announcing them
+  pub fn take_announcements(&mut self) -> Vec<String> { std::mem::take(&mut self.announcements) }
+
+  // Reads out a move that was just played
+  fn announce(
+    &mut self,
+    words: String
+  ) {
+    if self.accessible {
+      self.message = Some(words.clone());
+      self.announcements.push(words);
+    }
+  }
+
+  fn deselect(&mut self) {
+    self.selected = None;
+    self.targets.clear();
+  }
+
+  /// Selects the piece on the square, or moves the selected piece there
+  pub fn activate(
+    &mut self,
+    coord: Coordinate
+  ) {
+    self.message = None;
+    let board = self.game.board();
+
+    if self.replay.is_some() {
+      self.message = Some(String::from("Press esc to play on from here"));
+      return
+    }
+    if self.browsing.is_some() {
+      self.message = Some(String::from("Press End to go back to the game"));
+      return
+    }
+
+    if self.thinking.is_some() {
+      self.message = Some(String::from("The computer is thinking"));
+      return
+    }
+
+    if self.game.outcome().is_some() {
+      self.message = Some(String::from("The game is over"));
+      return
+    }
+
+    // Picking another piece of the side to move
+    if let Some(piece) = board.get_piece(&coord) {
+      if piece.color == board.turn && self.selected != Some(coord) {
+        self.selected = Some(coord);
+        self.targets = board.generate_moves(coord).unwrap_or_default();
+
+        if self.targets.is_empty() {
+          self.message = Some(String::from("That piece can't move"));
+        }
+        return
+      }
+    }
+
+    match self.selected {
+      Some(start) if start == coord => self.deselect(),
+      Some(start) if self.targets.contains(&coord) => {
+        let mv = Move::new(start, coord);
+        let promoting = (coord.row == 0 || coord.row == 7)
+          && board
+            .get_piece(&start)
+            .is_some_and(|piece| piece.breed == Pieces::Pawn);
+        self.deselect();
+        if promoting {
+          return self.choose_promotion(mv)
+        }
+        if let Err(err) = self.play(mv) {
+          self.message = Some(err);
+        }
+      },
+      Some(start) => {
+        self.message = Some(format!(
+          "Illegal move {}{}",
+          start.to_notation(),
+          coord.to_notation()
+        ));
+      },
+      None => {
+        self.message = Some(match board.get_piece(&coord) {
+          Some(_) => format!("It's {} to move", board.turn.name().to_lowercase()),
+          None => String::from("Select a piece first")
+        })
+      },
+    }
+  }
+
+  /// Side to move and the state of the game
+  pub fn status(&self) -> String {
+    if let Some(status) = self.editor_status() {
+      return status
+    }
+
+    let board = self.board();
+    let turn = board.turn;
+
+    let status = match self.game.outcome() {
+      Some(outcome) if self.browsing.is_none() => format!("{} ({})", outcome, outcome.result()),
+      _ if board.is_in_checkmate(turn) => format!("{} mates", turn.opposite().name()),
+      _ if board.is_in_stalemate(turn) => String::from("Draw by stalemate"),
+      _ if board.is_in_check(turn) => format!("{} to move, check", turn.name()),
+      _ => format!("{} to move", turn.name())
+    };
+
+    if let Some(time) = self.thinking_time() {
+      const SPINNER: [char; 10] = ['⠋', '⠙', '⠹', '⠸', '⠼', '⠴', '⠦', '⠧', '⠇', '⠏'];
+      let frame = SPINNER[(time.as_millis() / 100) as usize % SPINNER.len()];
+
+      return format!(
+        "{}, computer is thinking {} {:.1}s",
+        status,
+        frame,
+        time.as_secs_f64()
+      )
+    }
+
+    let status = match self.threat_status() {
+      Some(threat) => format!("{}, {}", status, threat),
+      None => status
+    };
+
+    match self.browsing {
+      Some((ply, _)) => format!("{} (move {} of {})", status, ply, self.game.moves().len()),
+      None => status
+    }
+  }
+
+  pub fn draw<B: Backend>(
+    &mut self,
+    f: &mut Frame<B>
+  ) {
+    let size = f.size();
+    let block = Block::default().title("Chyes").borders(Borders::ALL);
+    f.render_widget(block, size);
+
+    let inner = size.inner(&Margin {
+      horizontal: 2,
+      vertical:   1
+    });
+    let analysis_height = if self.is_analysing() {
+      self.analysis_lines as u16 + 3
+    } else {
+      0
+    };
+    let replay_height = if self.replay.is_some() { 6 } else { 0 };
+    let report_height = if self.report.is_some() { 10 } else { 0 };
+    let chunks = Layout::default()
+      .direction(Direction::Vertical)
+      .constraints([
+        Constraint::Min(9),
+        Constraint::Length(analysis_height),
+        Constraint::Length(replay_height),
+        Constraint::Length(report_height),
+        Constraint::Length(1),
+        Constraint::Length(1),
+        Constraint::Length(1),
+        Constraint::Length(1)
+      ])
+      .split(inner);
+    let columns = Layout::default()
+      .direction(Direction::Horizontal)
+      .constraints([Constraint::Min(10), Constraint::Length(24)])
+      .split(chunks[0]);
+    let clock_height = if self.clock.is_some() { 4 } else { 0 };
+    let sidebar = Layout::default()
+      .direction(Direction::Vertical)
+      .constraints([
+        Constraint::Length(clock_height),
+        Constraint::Min(3),
+        Constraint::Length(4)
+      ])
+      .split(columns[1]);
+
+    self.board_area = columns[0];
+
+    // Hovering over a piece without a selection previews its moves
+    let board = self.board();
+    let preview = match (self.selected, self.hover) {
+      (None, Some(hover))
+        if self.browsing.is_none()
+          && self.editor.is_none()
+          && board
+            .get_piece(&hover)
+            .is_some_and(|piece| piece.color == board.turn) =>
+      {
+        board.generate_moves(hover).unwrap_or_default()
+      },
+      _ => Vec::new()
+    };
+
+    let widget = if preview.is_empty() {
+      self.board_widget()
+    } else {
+      self.board_widget().targets(&preview)
+    };
+    f.render_widget(widget, self.board_area);
+    self.draw_promotion(f);
+
+    self.draw_clock(f, sidebar[0]);
+    if self.editor.is_some() {
+      self.draw_editor(f, sidebar[1]);
+    } else {
+      self.draw_moves(f, sidebar[1]);
+    }
+    self.draw_material(f, sidebar[2]);
+    self.draw_analysis(f, chunks[1]);
+    self.draw_replay(f, chunks[2]);
+    self.draw_report(f, chunks[3]);
+
+    f.render_widget(
+      Paragraph::new(Span::styled(
+        self.status(),
+        Style::default().add_modifier(Modifier::BOLD)
+      )),
+      chunks[4]
+    );
+
+    // Plain text, so it can be selected in the terminal as well as copied with `y`
+    f.render_widget(
+      Paragraph::new(format!("FEN {}", self.board().get_fen())),
+      chunks[5]
+    );
+
+    if let Some(command) = &self.command {
+      f.render_widget(Paragraph::new(format!(":{}", command)), chunks[6]);
+      f.set_cursor(
+        (chunks[6].x + 1 + command.chars().count() as u16).min(chunks[6].right()),
+        chunks[6].y
+      );
+    } else if let Some(message) = &self.message {
+      f.render_widget(
+        Paragraph::new(Span::styled(
+          message.as_str(),
+          Style::default().fg(TermColor::Red)
+        )),
+        chunks[6]
+      );
+    }
+
+    f.render_widget(
+      Paragraph::new(Spans::from(
+        "arrows enter: move  esc: cancel  :: command  u: take back  e: analysis  ?: hint  [ ] \
+         home end: browse  f: flip  o: orientation  a: art  y: copy FEN  q: quit"
+      )),
+      chunks[7]
+    );
+    self.draw_picker(f, chunks[0]);
+  }
+
+  // Moves in pairs, one full move per line, with the ply on screen highlighted
+  fn draw_moves<B: Backend>(
+    &mut self,
+    f: &mut Frame<B>,
+    area: Rect
+  ) {
+    let start = self.game.start();
+    // Games starting with black to move leave the first white slot empty
+    let offset = if start.turn == Color::Black { 1 } else { 0 };
+    let current = self.ply().checked_sub(1).map(|ply| ply + offset);
+
+    let mut rows: Vec<Vec<Span>> = Vec::new();
+    // Moves found wanting by the report get their `?`
+    let moves = self
+      .san
+      .iter()
+      .enumerate()
+      .map(|(ply, san)| match self.judgement(ply) {
+        Some(judgement) => format!("{}{}", san, judgement.symbol()),
+        None => san.clone()
+      });
+    let slots = (0..offset).map(|_| String::from("...")).chain(moves);
+
+    for (slot, san) in slots.enumerate() {
+      if slot % 2 == 0 {
+        let number = start.fullmove_number as usize + slot / 2;
+        rows.push(vec![Span::raw(format!("{:>3}. ", number))]);
+      }
+
+      let style = if Some(slot) == current {
+        Style::default().add_modifier(Modifier::REVERSED)
+      } else {
+        Style::default()
+      };
+      let row = rows.last_mut().expect("Rows start on even slots");
+      row.push(Span::styled(format!("{:<7}", san), style));
+      row.push(Span::raw(" "));
+    }
+
+    self.move_list.select(current.map(|slot| slot / 2));
+    let list = List::new(
+      rows
+        .into_iter()
+        .map(|row| ListItem::new(Spans::from(row)))
+        .collect::<Vec<_>>()
+    )
+    .block(Block::default().title("Moves").borders(Borders::ALL));
+
+    f.render_stateful_widget(list, area, &mut self.move_list);
+  }
+
+  // Pieces each side has taken and who is ahead in material
+  fn draw_material<B: Backend>(
+    &self,
+    f: &mut Frame<B>,
+    area: Rect
+  ) {
+    let board = self.board();
+    let balance = material(board, Color::White) - material(board, Color::Black);
+
+    let lines: Vec<Spans> = [Color::White, Color::Black]
+      .into_iter()
+      .map(|color| {
+        // Taken pieces are the opponent's missing ones
+        let taken: String = captured(board, color.opposite())
+          .into_iter()
+          .map(|breed| {
+            self.glyphs.glyph(Piece {
+              breed,
+              color: color.opposite()
+            })
+          })
+          .collect();
+        // Less than a pawn, a bishop for a knight, doesn't count as a lead
+        let lead = match color {
+          Color::White if balance >= 100 => format!(" +{}", balance / 100),
+          Color::Black if balance <= -100 => format!(" +{}", -balance / 100),
+          _ => String::new()
+        };
+
+        Spans::from(format!("{} {}{}", &color.name()[..1], taken, lead))
+      })
+      .collect();
+
+    f.render_widget(
+      Paragraph::new(lines).block(Block::default().title("Captured").borders(Borders::ALL)),
+      area
+    );
+  }
+}
+
+// Value of the pieces of one side, kings aside
+fn material(
+  board: &Board,
+  color: Color
+) -> i32 {
+  board
+    .pieces
+    .values()
+    .filter(|piece| piece.color == color && piece.breed != Pieces::King)
+    .map(|piece| piece.breed.value())
+    .sum()
+}
+
+/// Pieces of `color` missing from a full set, the most valuable first
+pub fn captured(
+  board: &Board,
+  color: Color
+) -> Vec<Pieces> {
+  let count = |breed| {
+    board
+      .pieces
+      .values()
+      .filter(|piece| piece.color == color && piece.breed == breed)
+      .count()
+  };
+
+  // Promoted pieces make up for the pawns they came from
+  let mut pawns = 8usize.saturating_sub(count(Pieces::Pawn));
+  let mut missing = Vec::new();
+
+  for (breed, full) in [
+    (Pieces::Queen, 1),
+    (Pieces::Rook, 2),
+    (Pieces::Bishop, 2),
+    (Pieces::Knight, 2)
+  ] {
+    let extra = count(breed).saturating_sub(full);
+    pawns = pawns.saturating_sub(extra);
+    missing.extend(std::iter::repeat_n(
+      breed,
+      full.saturating_sub(count(breed))
+    ));
+  }
+  missing.extend(std::iter::repeat_n(Pieces::Pawn, pawns));
+
+  missing
+}
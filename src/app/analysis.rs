@@ -0,0 +1,226 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver};
+use std::sync::Arc;
+use std::thread;
+
+use tui::backend::Backend;
+use tui::layout::{Constraint, Direction, Layout, Rect};
+use tui::style::{Color as TermColor, Style};
+use tui::text::Spans;
+use tui::widgets::{Block, Borders, Gauge, Paragraph};
+use tui::Frame;
+
+use super::App;
+use crate::search::{self, Info, Limits, Score};
+use crate::{Board, Color, Move};
+
+// Background search of the position on screen, stopped when dropped
+pub(super) struct Analysis {
+  stop:     Arc<AtomicBool>,
+  receiver: Receiver<Info>,
+  board:    Board,
+  // Latest report of every line, the best one first
+  lines:    Vec<Info>
+}
+
+impl Drop for Analysis {
+  fn drop(&mut self) { self.stop.store(true, Ordering::Relaxed); }
+}
+
+impl Analysis {
+  fn start(
+    app: &App,
+    board: &Board
+  ) -> Self {
+    let (sender, receiver) = mpsc::channel();
+    let stop = Arc::new(AtomicBool::new(false));
+    let limits = Limits {
+      multi_pv: app.analysis_lines,
+      ..Limits::default()
+    };
+    // Earlier positions only count for repetitions in the game itself
+    let history = if app.browsing.is_none() {
+      app.game.history().to_vec()
+    } else {
+      Vec::new()
+    };
+    let evaluator = Arc::clone(&app.evaluator);
+    let thread_board = board.clone();
+    let thread_stop = Arc::clone(&stop);
+
+    thread::spawn(move || {
+      search::search(
+        &thread_board,
+        &history,
+        &evaluator,
+        &limits,
+        &thread_stop,
+        |info| {
+          let _ = sender.send(info.clone());
+        }
+      );
+    });
+
+    Analysis {
+      stop,
+      receiver,
+      board: board.clone(),
+      lines: Vec::new()
+    }
+  }
+}
+
+/// Moves in standard algebraic notation with move numbers, `12... Nf6 13. Bg5`
+pub fn san_line(
+  board: &Board,
+  moves: &[Move]
+) -> String {
+  let mut board = board.clone();
+  let mut text = Vec::new();
+
+  for (index, &mv) in moves.iter().enumerate() {
+    match board.turn {
+      Color::White => text.push(format!("{}.", board.fullmove_number)),
+      Color::Black if index == 0 => text.push(format!("{}...", board.fullmove_number)),
+      Color::Black => {}
+    }
+    text.push(board.to_san(mv));
+
+    if board.make_move(mv).is_err() {
+      break
+    }
+  }
+
+  text.join(" ")
+}
+
+impl App {
+  pub fn is_analysing(&self) -> bool { self.analysis.is_some() }
+
+  /// Turns the analysis of the position on screen on or off
+  pub fn set_analysis(
+    &mut self,
+    on: bool
+  ) {
+    self.analysis = None;
+    if on {
+      self.analysis = Some(Analysis::start(self, self.board()));
+    }
+  }
+
+  /// How many lines the analysis shows
+  pub fn set_analysis_lines(
+    &mut self,
+    lines: usize
+  ) {
+    self.analysis_lines = lines.clamp(1, 10);
+    if self.is_analysing() {
+      self.set_analysis(true);
+    }
+  }
+
+  /// Latest report of every line of the analysis, the best one first
+  pub fn analysis_lines(&self) -> &[Info] {
+    self
+      .analysis
+      .as_ref()
+      .map_or(&[], |analysis| analysis.lines.as_slice())
+  }
+
+  // Collects the reports of the search, and starts over when the position
+  // on screen changed
+  pub(super) fn update_analysis(&mut self) {
+    let restart = match &self.analysis {
+      Some(analysis) => analysis.board.key() != self.board().key(),
+      None => return
+    };
+    if restart {
+      return self.set_analysis(true)
+    }
+
+    if let Some(analysis) = &mut self.analysis {
+      for info in analysis.receiver.try_iter() {
+        let index = info.multipv.saturating_sub(1);
+
+        // A new iteration starts with the best line
+        if index == 0 {
+          analysis.lines.truncate(1);
+        }
+        if index < analysis.lines.len() {
+          analysis.lines[index] = info;
+        } else {
+          analysis.lines.push(info);
+        }
+      }
+    }
+  }
+
+  pub(super) fn draw_analysis<B: Backend>(
+    &self,
+    f: &mut Frame<B>,
+    area: Rect
+  ) {
+    let analysis = match &self.analysis {
+      Some(analysis) => analysis,
+      None => return
+    };
+
+    let block = Block::default().title("Analysis").borders(Borders::ALL);
+    let inner = block.inner(area);
+    f.render_widget(block, area);
+
+    let chunks = Layout::default()
+      .direction(Direction::Vertical)
+      .constraints([Constraint::Length(1), Constraint::Min(0)])
+      .split(inner);
+
+    let best = analysis.lines.first();
+    let score = best.map(|info| info.score.for_white(&analysis.board));
+
+    // Share of the bar that is white, a logistic curve of the score
+    let ratio = match score {
+      Some(Score::Mate(moves)) => {
+        if moves > 0 {
+          1.0
+        } else {
+          0.0
+        }
+      },
+      Some(Score::Cp(cp)) => 1.0 / (1.0 + 10f64.powf(-cp as f64 / 400.0)),
+      None => 0.5
+    };
+    let label = match best {
+      Some(info) => format!(
+        "{}  depth {}  {} knps",
+        score.expect("There is a best line"),
+        info.depth,
+        info.nps() / 1000
+      ),
+      None => String::from("thinking")
+    };
+    f.render_widget(
+      Gauge::default()
+        .gauge_style(
+          Style::default()
+            .fg(TermColor::White)
+            .bg(TermColor::DarkGray)
+        )
+        .ratio(ratio)
+        .label(label),
+      chunks[0]
+    );
+
+    let lines: Vec<Spans> = analysis
+      .lines
+      .iter()
+      .map(|info| {
+        Spans::from(format!(
+          "{:>6}  {}",
+          info.score.for_white(&analysis.board).to_string(),
+          san_line(&analysis.board, &info.pv)
+        ))
+      })
+      .collect();
+    f.render_widget(Paragraph::new(lines), chunks[1]);
+  }
+}
@@ -0,0 +1,336 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, TryRecvError};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+use crossterm::event::{KeyCode, KeyEvent};
+
+use super::App;
+use crate::coach;
+use crate::search::Limits;
+use crate::{Color, Coordinate, Move};
+
+// Quick searches, the overlays point at the obvious and mustn't hold up play
+fn limits() -> Limits {
+  Limits {
+    depth: Some(4),
+    movetime: Some(Duration::from_millis(250)),
+    ..Limits::default()
+  }
+}
+
+// Search done on its own thread for the overlays or a move warning,
+// stopped when dropped
+pub(super) struct Lookup<T> {
+  stop:     Arc<AtomicBool>,
+  receiver: Receiver<T>,
+  // Position it's for, in case the game changed meanwhile
+  key:      u64
+}
+
+impl<T> Drop for Lookup<T> {
+  fn drop(&mut self) { self.stop.store(true, Ordering::Relaxed); }
+}
+
+// Centipawns the move gives away and the best move, or why it can't be played
+type Loss = Result<(i32, Option<Move>), String>;
+
+/// Training help shown on the board and when moving
+#[derive(Default)]
+pub(super) struct Coach {
+  hanging:  bool,
+  threats:  bool,
+  // Centipawns a move may give away before it has to be confirmed
+  warn_at:  Option<i32>,
+  // Position the overlays below were worked out for
+  key:      Option<u64>,
+  squares:  Vec<Coordinate>,
+  threat:   Option<Move>,
+  hint:     Option<Move>,
+  searches: Vec<(Lookup<Option<Move>>, bool)>,
+  // Move held back while it's checked for giving away too much
+  checking: Option<(Move, Lookup<Loss>)>,
+  // Move that gives away too much, played again or with enter to confirm it
+  pending:  Option<Move>
+}
+
+// Runs the search on a background thread for the position with the key,
+// with the flag that stops it once the lookup is dropped
+fn spawn<T: Send + 'static>(
+  key: u64,
+  search: impl FnOnce(&AtomicBool) -> T + Send + 'static
+) -> Lookup<T> {
+  let (sender, receiver) = mpsc::channel();
+  let stop = Arc::new(AtomicBool::new(false));
+  let flag = Arc::clone(&stop);
+
+  thread::spawn(move || {
+    // The app may have moved on, nobody is listening then
+    let _ = sender.send(search(&flag));
+  });
+
+  Lookup {
+    stop,
+    receiver,
+    key
+  }
+}
+
+// Searches the board on a background thread, for the hint or the threat
+fn look_up(
+  app: &App,
+  hint: bool
+) -> Lookup<Option<Move>> {
+  let board = app.board().clone();
+  let history = if app.browsing.is_none() {
+    app.game.history().to_vec()
+  } else {
+    Vec::new()
+  };
+  let evaluator = Arc::clone(&app.evaluator);
+
+  spawn(board.key(), move |stop| {
+    if hint {
+      coach::hint(&board, &history, &evaluator, &limits(), stop)
+    } else {
+      coach::threat(&board, &evaluator, &limits(), stop)
+    }
+  })
+}
+
+impl App {
+  /// Pieces of either side that can be taken for free, when shown
+  pub fn hanging(&self) -> &[Coordinate] { &self.coach.squares }
+
+  /// Move the opponent would play if it were their turn, when shown and
+  /// found
+  pub fn threat(&self) -> Option<Move> { self.coach.threat }
+
+  /// Move suggested by the last hint, until the position changes
+  pub fn hint(&self) -> Option<Move> { self.coach.hint }
+
+  /// Highlights the pieces left hanging
+  pub fn set_hanging(
+    &mut self,
+    on: bool
+  ) {
+    self.coach.hanging = on;
+    self.coach.key = None;
+  }
+
+  /// Shows what the opponent threatens
+  pub fn set_threats(
+    &mut self,
+    on: bool
+  ) {
+    self.coach.threats = on;
+    self.coach.key = None;
+  }
+
+  /// Asks to confirm moves that give away more centipawns than the
+  /// threshold, `None` turns the warnings off
+  pub fn set_warnings(
+    &mut self,
+    threshold: Option<i32>
+  ) {
+    self.coach.warn_at = threshold;
+    self.coach.checking = None;
+    self.coach.pending = None;
+  }
+
+  /// Move waiting to be confirmed after a warning
+  pub fn pending_move(&self) -> Option<Move> { self.coach.pending }
+
+  /// Asks the engine for a move, shown once found
+  pub fn ask_hint(&mut self) -> Result<String, String> {
+    if self.editor.is_some() {
+      return Err(String::from("No hints while editing"))
+    }
+    if self.board().legal_moves().is_empty() {
+      return Err(String::from("There is no move to play"))
+    }
+
+    let lookup = look_up(self, true);
+    self.coach.searches.push((lookup, true));
+    Ok(String::from("Looking for a hint"))
+  }
+
+  // Works the overlays out again when the position on screen changed, and
+  // collects the searches that are done
+  pub(super) fn update_coach(&mut self) {
+    let key = self.board().key();
+
+    if self.coach.key != Some(key) {
+      self.coach.key = Some(key);
+      self.coach.squares.clear();
+      self.coach.threat = None;
+      self.coach.hint = None;
+      self.coach.searches.clear();
+      self.coach.pending = None;
+
+      // The board may be missing a king while editing
+      if self.editor.is_none() {
+        if self.coach.hanging {
+          let board = self.board();
+          let mut squares = board.hanging_pieces(Color::White);
+          squares.extend(board.hanging_pieces(Color::Black));
+          self.coach.squares = squares;
+        }
+        if self.coach.threats {
+          let lookup = look_up(self, false);
+          self.coach.searches.push((lookup, false));
+        }
+      }
+    }
+
+    let mut found = Vec::new();
+    self
+      .coach
+      .searches
+      .retain(|(lookup, hint)| match lookup.receiver.try_recv() {
+        Ok(mv) => {
+          if lookup.key == key {
+            found.push((mv, *hint));
+          }
+          false
+        },
+        Err(TryRecvError::Disconnected) => false,
+        Err(TryRecvError::Empty) => true
+      });
+
+    for (mv, hint) in found {
+      if hint {
+        self.coach.hint = mv;
+        self.message = Some(match mv {
+          Some(mv) => format!("Hint: {}", self.board().to_san(mv)),
+          None => String::from("No hint found")
+        });
+      } else {
+        self.coach.threat = mv;
+      }
+    }
+
+    self.update_check();
+  }
+
+  // Plays the move that was held back once it's found to be fine, or warns
+  // about it
+  fn update_check(&mut self) {
+    let (mv, loss) = match &self.coach.checking {
+      Some((mv, lookup)) => match lookup.receiver.try_recv() {
+        Ok(loss) if lookup.key == self.game.board().key() => (*mv, loss),
+        Err(TryRecvError::Empty) => return,
+        _ => {
+          self.coach.checking = None;
+          return
+        }
+      },
+      None => return
+    };
+    self.coach.checking = None;
+
+    let threshold = match self.coach.warn_at {
+      Some(threshold) => threshold,
+      None => return
+    };
+    let (loss, best) = match loss {
+      Ok(loss) => loss,
+      Err(err) => {
+        self.message = Some(err);
+        return
+      }
+    };
+
+    self.coach.pending = Some(mv);
+    if loss < threshold {
+      self.message = None;
+      if let Err(err) = self.play(mv) {
+        self.coach.pending = None;
+        self.message = Some(err);
+      }
+      return
+    }
+
+    let board = self.game.board();
+    let best = best.map_or(String::new(), |best| {
+      format!(", best is {}", board.to_san(best))
+    });
+    self.message = Some(format!(
+      "{} loses about {:.1} pawns{}. Play it again or press enter to confirm, esc cancels",
+      board.to_san(mv),
+      loss as f64 / 100.0,
+      best
+    ));
+  }
+
+  /// The threat in the status line, `black threatens Qxf7`
+  pub(super) fn threat_status(&self) -> Option<String> {
+    let threat = self.coach.threat.filter(|_| self.coach.threats)?;
+
+    let mut passed = self.board().clone();
+    passed.set_turn(passed.turn.opposite());
+    passed.set_en_passant(None);
+    Some(format!(
+      "{} threatens {}",
+      passed.turn.name().to_lowercase(),
+      passed.to_san(threat)
+    ))
+  }
+
+  // Holds back a move while a background search checks it doesn't give
+  // away too much, unless it's being confirmed. Returns whether it was held
+  pub(super) fn check_blunder(
+    &mut self,
+    mv: Move
+  ) -> bool {
+    if self.coach.warn_at.is_none() || self.coach.pending.take() == Some(mv) {
+      return false
+    }
+
+    let board = self.game.board().clone();
+    let history = self.game.history().to_vec();
+    let evaluator = Arc::clone(&self.evaluator);
+    self.message = Some(format!("Checking {}", board.to_san(mv)));
+    self.coach.checking = Some((
+      mv,
+      spawn(board.key(), move |stop| {
+        coach::move_loss(&board, &history, &evaluator, &limits(), stop, mv)
+      })
+    ));
+    true
+  }
+
+  // Enter plays the move warned about or being checked, esc drops it. Other
+  // keys go on as usual, returns whether the key was used
+  pub(super) fn handle_warning_key(
+    &mut self,
+    key: KeyEvent
+  ) -> bool {
+    let checking = self.coach.checking.as_ref().map(|(mv, _)| *mv);
+    let mv = match self.coach.pending.or(checking) {
+      Some(mv) => mv,
+      None => return false
+    };
+
+    match key.code {
+      KeyCode::Enter => {
+        self.coach.checking = None;
+        self.coach.pending = Some(mv);
+        self.deselect();
+        self.message = None;
+        if let Err(err) = self.play(mv) {
+          self.message = Some(err);
+        }
+      },
+      KeyCode::Esc => {
+        self.coach.checking = None;
+        self.coach.pending = None;
+        self.deselect();
+        self.message = Some(String::from("Move cancelled"));
+      },
+      _ => return false
+    }
+    true
+  }
+}
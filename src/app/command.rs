@@ -0,0 +1,349 @@
+use std::fs;
+
+use crossterm::event::{KeyCode, KeyEvent};
+
+use super::App;
+use crate::game::Game;
+use crate::pgn;
+use crate::{Board, Color};
+
+// Words offered by tab completion besides the legal moves
+const COMMANDS: [&str; 39] = [
+  "fen ",
+  "new",
+  "edit",
+  "undo",
+  "flip",
+  "save ",
+  "load ",
+  "open ",
+  "engine on",
+  "engine off",
+  "play white",
+  "play black",
+  "play both",
+  "level ",
+  "analysis on",
+  "analysis off",
+  "clock ",
+  "clock off",
+  "resign",
+  "draw",
+  "square ",
+  "rank ",
+  "file ",
+  "pieces ",
+  "describe",
+  "accessible on",
+  "accessible off",
+  "hint",
+  "hanging on",
+  "hanging off",
+  "threats on",
+  "threats off",
+  "warnings on",
+  "warnings off",
+  "report",
+  "report off",
+  "report save ",
+  "quit",
+  "help"
+];
+
+impl App {
+  /// Whether `:` was typed and the command line is open
+  pub fn command(&self) -> Option<&str> { self.command.as_deref() }
+
+  pub(super) fn handle_command_key(
+    &mut self,
+    key: KeyEvent
+  ) {
+    let input = self.command.get_or_insert_with(String::new);
+
+    if key.code != KeyCode::Tab {
+      self.completions = None;
+    }
+
+    match key.code {
+      KeyCode::Esc => self.command = None,
+      KeyCode::Backspace if input.is_empty() => self.command = None,
+      KeyCode::Backspace => {
+        input.pop();
+      },
+      KeyCode::Char(c) => input.push(c),
+      KeyCode::Enter => {
+        let line = self.command.take().unwrap_or_default();
+        let line = line.trim();
+        if line.is_empty() {
+          return
+        }
+
+        if self.history.last().map(String::as_str) != Some(line) {
+          self.history.push(String::from(line));
+        }
+        self.recalled = None;
+
+        self.message = match self.run_command(line) {
+          Ok(message) => message,
+          Err(err) => Some(err)
+        };
+      },
+      KeyCode::Up | KeyCode::Down if !self.history.is_empty() => {
+        let newest = self.history.len() - 1;
+        self.recalled = match (key.code, self.recalled) {
+          (KeyCode::Up, None) => Some(0),
+          (KeyCode::Up, Some(back)) => Some((back + 1).min(newest)),
+          (_, Some(0)) | (_, None) => None,
+          (_, Some(back)) => Some(back - 1)
+        };
+
+        self.command = Some(match self.recalled {
+          Some(back) => self.history[newest - back].clone(),
+          None => String::new()
+        });
+      },
+      KeyCode::Tab => self.complete(),
+      _ => {}
+    }
+  }
+
+  // Completes the legal move or command being typed, pressing tab again
+  // cycles through the candidates
+  fn complete(&mut self) {
+    if let Some((candidates, index)) = &mut self.completions {
+      *index = (*index + 1) % candidates.len();
+      self.command = Some(candidates[*index].clone());
+      return
+    }
+
+    let input = self.command.clone().unwrap_or_default();
+    let board = self.game.board();
+    let mut candidates: Vec<String> = board
+      .legal_moves()
+      .into_iter()
+      .map(|mv| board.to_san(mv))
+      .chain(COMMANDS.iter().map(|command| String::from(*command)))
+      .filter(|candidate| candidate.starts_with(&input))
+      .collect();
+    candidates.sort();
+    candidates.dedup();
+
+    match candidates.len() {
+      0 => self.message = Some(format!("Nothing matches {}", input)),
+      1 => self.command = Some(candidates.remove(0)),
+      _ => {
+        self.message = Some(candidates.join(" "));
+        self.command = Some(candidates[0].clone());
+        self.completions = Some((candidates, 0));
+      }
+    }
+  }
+
+  /// Runs a line of the command line, a move in SAN or UCI notation or one of
+  /// the commands. Returns the message to show
+  pub fn run_command(
+    &mut self,
+    line: &str
+  ) -> Result<Option<String>, String> {
+    let (name, argument) = match line.split_once(char::is_whitespace) {
+      Some((name, argument)) => (name, argument.trim()),
+      None => (line, "")
+    };
+
+    match (name, argument) {
+      ("new", "") => {
+        self.set_game(Game::default());
+        Ok(Some(String::from("New game")))
+      },
+      ("edit", "") => {
+        self.edit();
+        Ok(self.message.take())
+      },
+      ("fen", fen) => {
+        let mut board = Board::new();
+        board.load_fen(fen).map_err(String::from)?;
+        self.set_game(Game::new(board));
+        Ok(None)
+      },
+      ("undo", "") => self.take_back().map(Some),
+      ("flip", "") => {
+        self.flip();
+        Ok(None)
+      },
+      ("save", "") | ("load", "") | ("open", "") => Err(format!("Usage: :{} <file.pgn>", name)),
+      ("save", path) => {
+        let tags: Vec<(&str, String)> = self
+          .clock
+          .iter()
+          .map(|clock| ("TimeControl", clock.control().to_pgn_tag()))
+          .collect();
+        fs::write(path, self.game.to_pgn(&tags))
+          .map_err(|err| format!("Couldn't write {}: {}", path, err))?;
+        Ok(Some(format!("Saved {}", path)))
+      },
+      ("load", path) => {
+        let game = pgn::load(path)?
+          .into_iter()
+          .next()
+          .ok_or_else(|| format!("No game in {}", path))?;
+        self.set_game(game.game);
+        Ok(Some(format!("Loaded {}", path)))
+      },
+      ("open", path) => {
+        self.open_pgn(pgn::load(path)?)?;
+        Ok(self.message.take())
+      },
+      ("engine", "on") => {
+        // The computer takes the side that just moved, the user keeps playing
+        self.set_engine(Some(self.game.board().turn.opposite()));
+        Ok(Some(String::from("Computer is on")))
+      },
+      ("engine", "off") => {
+        self.set_engine(None);
+        Ok(Some(String::from("Computer is off")))
+      },
+      ("play", side) => {
+        let engine = match side {
+          "white" | "w" => Some(Color::Black),
+          "black" | "b" => Some(Color::White),
+          "both" => None,
+          _ => return Err(String::from("Usage: :play <white|black|both>"))
+        };
+        self.set_engine(engine);
+        if engine.is_some_and(|engine| engine == self.view().bottom()) {
+          self.flip();
+        }
+        Ok(Some(match engine {
+          Some(engine) => format!("Computer plays {}", engine.name().to_lowercase()),
+          None => String::from("Computer is off")
+        }))
+      },
+      ("level", "") => Ok(Some(format!("{:?}", self.difficulty))),
+      ("level", level) => {
+        self.set_difficulty(level.parse()?);
+        Ok(Some(format!("Difficulty {}", level)))
+      },
+      ("analysis", "on") | ("analysis", "") => {
+        self.set_analysis(true);
+        Ok(None)
+      },
+      ("analysis", "off") => {
+        self.set_analysis(false);
+        Ok(None)
+      },
+      ("analysis", lines) => {
+        let lines = lines
+          .parse()
+          .map_err(|_| String::from("Usage: :analysis <on|off|lines>"))?;
+        self.set_analysis_lines(lines);
+        self.set_analysis(true);
+        Ok(None)
+      },
+      ("clock", "off") => {
+        self.set_clock(None);
+        Ok(None)
+      },
+      ("clock", control) if !control.is_empty() => {
+        self.set_clock(Some(control.parse()?));
+        Ok(Some(format!("Clock set to {}", control)))
+      },
+      ("square", square) => {
+        let coord = square
+          .parse()
+          .map_err(|_| String::from("Usage: :square <e4>"))?;
+        Ok(Some(self.board().describe_square(coord)))
+      },
+      ("rank", rank) => {
+        let rank = rank
+          .parse()
+          .map_err(|_| String::from("Usage: :rank <1-8>"))?;
+        self.board().describe_rank(rank).map(Some)
+      },
+      ("file", file) => {
+        let mut chars = file.chars();
+        match (chars.next(), chars.next()) {
+          (Some(file), None) => self.board().describe_file(file).map(Some),
+          _ => Err(String::from("Usage: :file <a-h>"))
+        }
+      },
+      ("pieces", "") => Ok(Some(format!(
+        "{}. {}",
+        self.board().describe_pieces(Color::White),
+        self.board().describe_pieces(Color::Black)
+      ))),
+      ("pieces", side) => Ok(Some(self.board().describe_pieces(side.parse()?))),
+      ("describe", "") => Ok(Some(self.board().describe_position())),
+      ("accessible", "on") => {
+        self.set_accessible(true);
+        Ok(Some(String::from("Moves are announced in words")))
+      },
+      ("accessible", "off") => {
+        self.set_accessible(false);
+        Ok(None)
+      },
+      ("hint", "") => self.ask_hint().map(Some),
+      ("hanging", "on") | ("threats", "on") | ("hanging", "off") | ("threats", "off") => {
+        let on = argument == "on";
+        if name == "hanging" {
+          self.set_hanging(on);
+        } else {
+          self.set_threats(on);
+        }
+        Ok(None)
+      },
+      ("warnings", "on") => {
+        self.set_warnings(Some(150));
+        Ok(Some(String::from(
+          "Moves losing 1.5 pawns or more need confirming"
+        )))
+      },
+      ("warnings", "off") => {
+        self.set_warnings(None);
+        Ok(None)
+      },
+      ("warnings", threshold) => {
+        let threshold: i32 = threshold
+          .parse()
+          .map_err(|_| String::from("Usage: :warnings <on|off|centipawns>"))?;
+        self.set_warnings(Some(threshold.max(1)));
+        Ok(Some(format!(
+          "Moves losing {:.1} pawns or more need confirming",
+          threshold.max(1) as f64 / 100.0
+        )))
+      },
+      ("report", "") => self.start_report().map(Some),
+      ("report", "off") => {
+        self.close_report();
+        Ok(None)
+      },
+      ("report", "save") => Err(String::from("Usage: :report save <file.pgn>")),
+      ("report", argument) => match argument.split_once(char::is_whitespace) {
+        Some(("save", path)) => self.save_report(path.trim()).map(Some),
+        _ => Err(String::from("Usage: :report [off|save <file.pgn>]"))
+      },
+      ("resign", "") => self.resign().map(Some),
+      ("draw", "") => self.offer_draw().map(Some),
+      ("help", "") => Ok(Some(String::from(
+        "Commands: <move> fen new edit undo flip save load open engine play level analysis clock \
+         square rank file pieces describe accessible hint hanging threats warnings report resign \
+         draw quit"
+      ))),
+      ("quit", "") | ("q", "") => {
+        self.quit = true;
+        Ok(None)
+      },
+      _ => {
+        let board = self.game.board();
+        let mv = board
+          .parse_san(line)
+          .or_else(|err| board.parse_uci(line).map_err(|_| err))?;
+
+        self.deselect();
+        self.message = None;
+        self.play(mv)?;
+        // The computer's answer, if any
+        Ok(self.message.take())
+      }
+    }
+  }
+}
@@ -0,0 +1,266 @@
+use crossterm::event::{KeyCode, KeyEvent, MouseButton, MouseEvent, MouseEventKind};
+use tui::backend::Backend;
+use tui::layout::Rect;
+use tui::style::{Modifier, Style};
+use tui::text::{Span, Spans};
+use tui::widgets::{Block, Borders, Paragraph};
+use tui::Frame;
+
+use super::App;
+use crate::game::Game;
+use crate::{Board, Color, Coordinate, Piece, Pieces};
+
+/// Position being set up, shown instead of the game until it's played or
+/// dropped
+pub(super) struct Editor {
+  pub(super) board: Board,
+  // Piece put down by space and clicks, none erases
+  brush:            Option<Piece>
+}
+
+fn piece_from_letter(c: char) -> Option<Piece> {
+  let breed = match c.to_ascii_lowercase() {
+    'k' => Pieces::King,
+    'q' => Pieces::Queen,
+    'r' => Pieces::Rook,
+    'b' => Pieces::Bishop,
+    'n' => Pieces::Knight,
+    'p' => Pieces::Pawn,
+    _ => return None
+  };
+  let color = if c.is_ascii_uppercase() {
+    Color::White
+  } else {
+    Color::Black
+  };
+
+  Some(Piece::new(color, breed))
+}
+
+impl Editor {
+  // Changes made by the keys without a meaning outside the editor
+  fn edit(
+    &mut self,
+    code: KeyCode,
+    cursor: Coordinate
+  ) {
+    let board = &mut self.board;
+
+    match code {
+      KeyCode::Char('x') | KeyCode::Delete | KeyCode::Backspace => {
+        self.brush = None;
+        board.remove_piece(cursor);
+      },
+      KeyCode::Char('c') => board.clear(),
+      KeyCode::Char('s') => *board = Board::default(),
+      KeyCode::Char('t') => board.set_turn(board.turn.opposite()),
+      // White and black castling rights, king side first
+      KeyCode::Char(c @ '1'..='4') => {
+        let color = if c <= '2' { Color::White } else { Color::Black };
+        let king_side = c == '1' || c == '3';
+        let allowed = board.castling_right(color, king_side);
+        board.set_castling_right(color, king_side, !allowed);
+      },
+      KeyCode::Char('e') => {
+        let square = (board.en_passant() != Some(cursor)).then_some(cursor);
+        board.set_en_passant(square);
+      },
+      KeyCode::Char(c) => {
+        if let Some(piece) = piece_from_letter(c) {
+          self.brush = Some(piece);
+          board.place_piece(piece, cursor);
+        }
+      },
+      _ => {}
+    }
+  }
+}
+
+impl App {
+  pub fn is_editing(&self) -> bool { self.editor.is_some() }
+
+  /// Opens the editor on the position on screen
+  pub fn edit(&mut self) {
+    self.stop_thinking();
+    self.set_analysis(false);
+    self.deselect();
+    self.editor = Some(Editor {
+      board: self.board().clone(),
+      brush: None
+    });
+    self.message = Some(String::from(
+      "Editing: KQRBNP kqrbnp place, x removes, enter plays, esc cancels"
+    ));
+  }
+
+  /// Starts a game from the edited position, if it's legal
+  pub fn finish_editing(&mut self) -> Result<(), String> {
+    let board = match &self.editor {
+      Some(editor) => editor.board.clone(),
+      None => return Err(String::from("Not editing"))
+    };
+    board.validate()?;
+
+    self.editor = None;
+    self.message = None;
+    self.set_game(Game::new(board));
+    Ok(())
+  }
+
+  fn cancel_editing(&mut self) {
+    self.editor = None;
+    self.message = None;
+  }
+
+  // Whether the edited position can be played, shown in the status line
+  pub(super) fn editor_status(&self) -> Option<String> {
+    let editor = self.editor.as_ref()?;
+
+    Some(match editor.board.validate() {
+      Ok(()) => format!(
+        "Setting up, {} to move, the position is legal",
+        editor.board.turn.name().to_lowercase()
+      ),
+      Err(err) => format!("Setting up: {}", err)
+    })
+  }
+
+  // Puts the brush down on the square, or takes off the same piece
+  fn paint(
+    &mut self,
+    coord: Coordinate
+  ) {
+    let editor = match &mut self.editor {
+      Some(editor) => editor,
+      None => return
+    };
+
+    match editor.brush {
+      Some(piece) if editor.board.get_piece(&coord) != Some(piece) => {
+        editor.board.place_piece(piece, coord);
+      },
+      _ => {
+        editor.board.remove_piece(coord);
+      }
+    }
+  }
+
+  pub(super) fn handle_editor_key(
+    &mut self,
+    key: KeyEvent
+  ) {
+    let cursor = self.cursor;
+
+    match key.code {
+      KeyCode::Esc => self.cancel_editing(),
+      KeyCode::Enter => {
+        if let Err(err) = self.finish_editing() {
+          self.message = Some(err);
+        }
+      },
+      KeyCode::Up => self.move_cursor(-1, 0),
+      KeyCode::Down => self.move_cursor(1, 0),
+      KeyCode::Left => self.move_cursor(0, -1),
+      KeyCode::Right => self.move_cursor(0, 1),
+      KeyCode::Char(' ') => self.paint(cursor),
+      KeyCode::Char('y') => {
+        self.clipboard = Some(self.board().get_fen());
+        self.message = Some(String::from("FEN copied"));
+      },
+      code => {
+        if let Some(editor) = &mut self.editor {
+          editor.edit(code, cursor);
+        }
+      },
+    }
+  }
+
+  // Left clicks paint with the brush, right clicks remove
+  pub(super) fn handle_editor_mouse(
+    &mut self,
+    event: MouseEvent
+  ) {
+    let square = self
+      .board_widget()
+      .square_at(self.board_area, event.column, event.row);
+    self.hover = square;
+    let coord = match square {
+      Some(coord) => coord,
+      None => return
+    };
+
+    match event.kind {
+      MouseEventKind::Down(MouseButton::Left) => {
+        self.cursor = coord;
+        self.paint(coord);
+      },
+      MouseEventKind::Down(MouseButton::Right) => {
+        self.cursor = coord;
+        if let Some(editor) = &mut self.editor {
+          editor.board.remove_piece(coord);
+        }
+      },
+      _ => {}
+    }
+  }
+
+  // Brush, side to move, castling rights and en passant square, with the keys
+  pub(super) fn draw_editor<B: Backend>(
+    &self,
+    f: &mut Frame<B>,
+    area: Rect
+  ) {
+    let editor = match &self.editor {
+      Some(editor) => editor,
+      None => return
+    };
+    let board = &editor.board;
+
+    let flag = |key: &'static str, name: &'static str, on: bool| {
+      let style = if on {
+        Style::default().add_modifier(Modifier::BOLD | Modifier::REVERSED)
+      } else {
+        Style::default()
+      };
+      vec![Span::raw(format!("{} ", key)), Span::styled(name, style)]
+    };
+    let castling = |color: Color| {
+      let keys = if color == Color::White {
+        ["1", "2"]
+      } else {
+        ["3", "4"]
+      };
+      let mut spans = vec![Span::raw(format!("{:<6}", color.name()))];
+      spans.extend(flag(keys[0], "O-O", board.castling_right(color, true)));
+      spans.push(Span::raw(" "));
+      spans.extend(flag(keys[1], "O-O-O", board.castling_right(color, false)));
+      Spans::from(spans)
+    };
+
+    let lines = vec![
+      Spans::from(format!(
+        "Brush  {}",
+        match editor.brush {
+          Some(piece) => self.glyphs.glyph(piece).to_string(),
+          None => String::from("eraser")
+        }
+      )),
+      Spans::from(format!("t turn {}", board.turn.name())),
+      castling(Color::White),
+      castling(Color::Black),
+      Spans::from(format!(
+        "e en passant {}",
+        board
+          .en_passant()
+          .map_or_else(|| String::from("-"), |coord| coord.to_string())
+      )),
+      Spans::from("x remove  c clear"),
+      Spans::from("s start  y copy FEN"),
+    ];
+
+    f.render_widget(
+      Paragraph::new(lines).block(Block::default().title("Editor").borders(Borders::ALL)),
+      area
+    );
+  }
+}
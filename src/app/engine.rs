@@ -0,0 +1,241 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, TryRecvError};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use super::App;
+use crate::eval::Evaluator;
+use crate::game::GameResult;
+use crate::search::SearchResult;
+use crate::skill::{self, Difficulty};
+use crate::Color;
+
+// Search running on its own thread, stopped when dropped
+pub(super) struct Thinking {
+  stop:     Arc<AtomicBool>,
+  receiver: Receiver<SearchResult>,
+  started:  Instant,
+  // Position being searched, in case the game changed meanwhile
+  key:      u64
+}
+
+impl Drop for Thinking {
+  fn drop(&mut self) { self.stop.store(true, Ordering::Relaxed); }
+}
+
+impl App {
+  /// Side the computer plays, if any
+  pub fn engine(&self) -> Option<Color> { self.engine }
+
+  /// Lets the computer play a side, it starts thinking right away when it's
+  /// that side's turn
+  pub fn set_engine(
+    &mut self,
+    side: Option<Color>
+  ) {
+    self.stop_thinking();
+    self.engine = side;
+    self.engine_move();
+  }
+
+  pub fn difficulty(&self) -> Difficulty { self.difficulty }
+
+  pub fn set_difficulty(
+    &mut self,
+    difficulty: Difficulty
+  ) {
+    self.difficulty = difficulty;
+  }
+
+  pub fn set_evaluator(
+    &mut self,
+    evaluator: Arc<Evaluator>
+  ) {
+    self.evaluator = evaluator;
+  }
+
+  /// How long the computer has been thinking, `None` when it isn't
+  pub fn thinking_time(&self) -> Option<Duration> {
+    self
+      .thinking
+      .as_ref()
+      .map(|thinking| thinking.started.elapsed())
+  }
+
+  pub(super) fn stop_thinking(&mut self) { self.thinking = None; }
+
+  // Starts a search on a background thread when it's the computer's turn
+  pub(super) fn engine_move(&mut self) {
+    if self.engine != Some(self.game.board().turn)
+      || self.game.outcome().is_some()
+      || self.thinking.is_some()
+    {
+      return
+    }
+
+    let (sender, receiver) = mpsc::channel();
+    let stop = Arc::new(AtomicBool::new(false));
+    let board = self.game.board().clone();
+    let history = self.game.history().to_vec();
+    let evaluator = Arc::clone(&self.evaluator);
+    let mut difficulty = self.difficulty;
+    // Playing on the clock, a share of the time left at most
+    if let Some(clock) = &self.clock {
+      let budget = clock.remaining(self.game.board().turn, Instant::now()) / 30;
+      difficulty.movetime = Some(difficulty.movetime.map_or(budget, |time| time.min(budget)));
+    }
+    let seed = SystemTime::now()
+      .duration_since(UNIX_EPOCH)
+      .map_or(1, |time| time.as_nanos() as u64);
+
+    let thread_stop = Arc::clone(&stop);
+    thread::spawn(move || {
+      let result = skill::pick_move(
+        &board,
+        &history,
+        &evaluator,
+        &difficulty,
+        &thread_stop,
+        seed
+      );
+      // The app may have moved on, nobody is listening then
+      let _ = sender.send(result);
+    });
+
+    self.thinking = Some(Thinking {
+      stop,
+      receiver,
+      started: Instant::now(),
+      key: self.game.board().key()
+    });
+  }
+
+  // Plays the move the computer found
+  fn engine_done(
+    &mut self,
+    result: SearchResult
+  ) {
+    let key = self.thinking.take().map(|thinking| thinking.key);
+    if key != Some(self.game.board().key()) {
+      return
+    }
+
+    // The computer may have run out of time while thinking
+    self.update_clock();
+    if let Some(mv) = result.best_move {
+      let san = self.game.board().to_san(mv);
+      let words = self.game.board().describe_move(mv);
+      if self.game.play(mv).is_ok() {
+        self.san.push(san.clone());
+        self.clock_move();
+        self.engine_score = Some(result.score);
+        self.message = Some(format!("Computer played {}", san));
+        self.announce(format!("Computer: {}", words));
+      }
+    }
+  }
+
+  // Plays the computer's move once its search is done
+  pub(super) fn update_engine(&mut self) {
+    let received = match &self.thinking {
+      Some(thinking) => thinking.receiver.try_recv(),
+      None => return
+    };
+
+    match received {
+      Ok(result) => self.engine_done(result),
+      Err(TryRecvError::Disconnected) => self.thinking = None,
+      Err(TryRecvError::Empty) => {}
+    }
+  }
+
+  /// Blocks until the computer has moved
+  pub fn wait_for_engine(&mut self) {
+    let received = match &self.thinking {
+      Some(thinking) => thinking.receiver.recv(),
+      None => return
+    };
+
+    match received {
+      Ok(result) => self.engine_done(result),
+      Err(_) => self.thinking = None
+    }
+  }
+
+  // The side at the keyboard, the one to move when nobody plays the computer
+  fn user_side(&self) -> Color {
+    match self.engine {
+      Some(engine) => engine.opposite(),
+      None => self.game.board().turn
+    }
+  }
+
+  /// Takes back the last move of the user, with the computer's answer
+  pub fn take_back(&mut self) -> Result<String, String> {
+    self.stop_thinking();
+    let mv = self.game.undo().ok_or("Nothing to take back")?;
+
+    // Back to a position where it's the user's turn, unless the computer
+    // made the first move
+    let replay_first = self.engine == Some(self.game.board().turn) && self.game.undo().is_none();
+    self.san.truncate(self.game.moves().len());
+    self.restart_clock();
+    if replay_first {
+      self.engine_move();
+    }
+
+    self.browsing = None;
+    self.deselect();
+    Ok(format!("Took back {}", mv))
+  }
+
+  pub fn resign(&mut self) -> Result<String, String> {
+    if self.game.outcome().is_some() {
+      return Err(String::from("The game is over"))
+    }
+
+    let side = self.user_side();
+    self.stop_thinking();
+    self.stop_clock();
+    self.game.decide(
+      GameResult::win(side.opposite()),
+      format!("{} resigns", side.name())
+    );
+    Ok(format!("{} resigns", side.name()))
+  }
+
+  /// Claims a draw by repetition or the fifty move rule when there is one,
+  /// otherwise offers one. The computer takes it when it doesn't think it's
+  /// better, a draw offered over the board is agreed right away
+  pub fn offer_draw(&mut self) -> Result<String, String> {
+    if self.game.outcome().is_some() {
+      return Err(String::from("The game is over"))
+    }
+
+    if let Ok(outcome) = self.game.claim_draw() {
+      self.stop_thinking();
+      self.stop_clock();
+      return Ok(outcome.to_string())
+    }
+
+    if self.engine.is_some() {
+      let late = self.game.moves().len() >= 60;
+      let accepts = match self.engine_score {
+        Some(score) => score.centipawns() < -50 || (late && score.centipawns().abs() <= 25),
+        None => false
+      };
+
+      if !accepts {
+        return Err(String::from("The computer declines the draw"))
+      }
+    }
+
+    self.stop_thinking();
+    self.stop_clock();
+    self
+      .game
+      .decide(GameResult::Draw, String::from("Draw agreed"));
+    Ok(String::from("Draw agreed"))
+  }
+}
@@ -0,0 +1,157 @@
+use crossterm::event::{KeyCode, KeyEvent};
+use tui::backend::Backend;
+use tui::layout::Rect;
+use tui::style::{Modifier, Style};
+use tui::text::{Span, Spans};
+use tui::widgets::{Block, Borders, Clear, Paragraph};
+use tui::Frame;
+
+use super::App;
+use crate::{Move, Piece, Pieces, PROMOTIONS};
+
+// Columns taken by each piece in the picker, ` ♛ q `
+const CHOICE_WIDTH: u16 = 5;
+// Keys picking the pieces, in the order of `PROMOTIONS`
+const KEYS: [char; 4] = ['q', 'r', 'b', 'n'];
+
+impl App {
+  /// Pawn move waiting for the piece the pawn becomes
+  pub fn promotion(&self) -> Option<Move> { self.promotion.map(|(mv, _)| mv) }
+
+  // Opens the picker with the queen highlighted
+  pub(super) fn choose_promotion(
+    &mut self,
+    mv: Move
+  ) {
+    self.promotion = Some((mv, 0));
+    self.message = Some(String::from(
+      "Promote to: q r b n, or arrows and enter, esc cancels"
+    ));
+  }
+
+  // Plays the pending pawn move
+  fn promote(
+    &mut self,
+    breed: Pieces
+  ) {
+    let mv = match self.promotion.take() {
+      Some((mv, _)) => mv,
+      None => return
+    };
+
+    self.message = None;
+    if let Err(err) = self.play(Move::promoting(mv.start, mv.target, breed)) {
+      self.message = Some(err);
+    }
+  }
+
+  fn cancel_promotion(&mut self) {
+    self.promotion = None;
+    self.message = None;
+  }
+
+  pub(super) fn handle_promotion_key(
+    &mut self,
+    key: KeyEvent
+  ) {
+    let (mv, index) = match self.promotion {
+      Some(promotion) => promotion,
+      None => return
+    };
+
+    match key.code {
+      KeyCode::Esc => self.cancel_promotion(),
+      KeyCode::Left | KeyCode::Up | KeyCode::Char('h') | KeyCode::Char('k') => {
+        self.promotion = Some((mv, (index + PROMOTIONS.len() - 1) % PROMOTIONS.len()))
+      },
+      KeyCode::Right | KeyCode::Down | KeyCode::Char('l') | KeyCode::Char('j') => {
+        self.promotion = Some((mv, (index + 1) % PROMOTIONS.len()))
+      },
+      KeyCode::Enter | KeyCode::Char(' ') => self.promote(PROMOTIONS[index]),
+      KeyCode::Char(c) => {
+        if let Some(i) = KEYS.iter().position(|&key| key == c.to_ascii_lowercase()) {
+          self.promote(PROMOTIONS[i]);
+        }
+      },
+      _ => {}
+    }
+  }
+
+  // Clicking a piece picks it, clicking elsewhere cancels
+  pub(super) fn handle_promotion_click(
+    &mut self,
+    column: u16,
+    row: u16
+  ) {
+    let inner = self.promotion_area(self.board_area);
+    let inner = Rect {
+      x:      inner.x + 1,
+      y:      inner.y + 1,
+      width:  inner.width.saturating_sub(2),
+      height: inner.height.saturating_sub(2)
+    };
+
+    let index = ((column.wrapping_sub(inner.x)) / CHOICE_WIDTH) as usize;
+    if row == inner.y && column >= inner.x && index < PROMOTIONS.len() {
+      self.promote(PROMOTIONS[index]);
+    } else {
+      self.cancel_promotion();
+    }
+  }
+
+  // Popup in the middle of the board
+  fn promotion_area(
+    &self,
+    board_area: Rect
+  ) -> Rect {
+    let width = (CHOICE_WIDTH * PROMOTIONS.len() as u16 + 2).min(board_area.width);
+    let height = 3.min(board_area.height);
+
+    Rect {
+      x: board_area.x + (board_area.width - width) / 2,
+      y: board_area.y + (board_area.height - height) / 2,
+      width,
+      height
+    }
+  }
+
+  pub(super) fn draw_promotion<B: Backend>(
+    &self,
+    f: &mut Frame<B>
+  ) {
+    let index = match self.promotion {
+      Some((_, index)) => index,
+      None => return
+    };
+    let color = self.game.board().turn;
+
+    let choices: Vec<Span> = PROMOTIONS
+      .iter()
+      .enumerate()
+      .map(|(i, &breed)| {
+        let text = format!(
+          " {} {} ",
+          self.glyphs.glyph(Piece {
+            color,
+            breed
+          }),
+          KEYS[i]
+        );
+        let style = if i == index {
+          Style::default().add_modifier(Modifier::BOLD | Modifier::REVERSED)
+        } else {
+          Style::default()
+        };
+        Span::styled(text, style)
+      })
+      .collect();
+
+    let area = self.promotion_area(self.board_area);
+    f.render_widget(Clear, area);
+    f.render_widget(
+      Paragraph::new(Spans::from(choices))
+        .block(Block::default().title("Promote to").borders(Borders::ALL)),
+      area
+    );
+  }
+}
@@ -0,0 +1,431 @@
+use std::time::{Duration, Instant};
+
+use crossterm::event::{KeyCode, KeyEvent};
+use tui::backend::Backend;
+use tui::layout::Rect;
+use tui::style::{Modifier, Style};
+use tui::text::Spans;
+use tui::widgets::{Block, Borders, Clear, List, ListItem, ListState, Paragraph, Wrap};
+use tui::Frame;
+
+use super::App;
+use crate::pgn::PgnGame;
+
+// Autoplay delays between moves, `+` and `-` step through them
+const SPEEDS: [Duration; 7] = [
+  Duration::from_millis(250),
+  Duration::from_millis(500),
+  Duration::from_secs(1),
+  Duration::from_secs(2),
+  Duration::from_secs(3),
+  Duration::from_secs(5),
+  Duration::from_secs(10)
+];
+
+/// Games of a PGN file to choose from, narrowed down by typing
+pub(super) struct GamePicker {
+  games: Vec<PgnGame>,
+  query: String,
+  // Selection among the games matching the query
+  list:  ListState
+}
+
+impl GamePicker {
+  // Indices of the games whose header contains every word of the query
+  fn matches(&self) -> Vec<usize> {
+    let query = self.query.to_lowercase();
+
+    self
+      .games
+      .iter()
+      .enumerate()
+      .filter(|(_, game)| {
+        let header = game.header().to_lowercase();
+        query.split_whitespace().all(|word| header.contains(word))
+      })
+      .map(|(index, _)| index)
+      .collect()
+  }
+
+  fn select(
+    &mut self,
+    delta: isize
+  ) {
+    let count = self.matches().len();
+    if count == 0 {
+      return self.list.select(None)
+    }
+
+    let index = self.list.selected().unwrap_or(0) as isize + delta;
+    self
+      .list
+      .select(Some(index.clamp(0, count as isize - 1) as usize));
+  }
+}
+
+/// Game being stepped through, with what the PGN had besides the moves
+pub(super) struct Replay {
+  pgn:       PgnGame,
+  autoplay:  bool,
+  speed:     Duration,
+  last_step: Instant,
+  // Ply typed after `g`, while the prompt is open
+  jump:      Option<String>
+}
+
+impl App {
+  pub fn is_replaying(&self) -> bool { self.replay.is_some() }
+
+  pub fn is_picking_game(&self) -> bool { self.picker.is_some() }
+
+  /// Games matching the search of the game picker, in file order
+  pub fn picker_games(&self) -> Vec<&PgnGame> {
+    match &self.picker {
+      Some(picker) => picker
+        .matches()
+        .into_iter()
+        .map(|index| &picker.games[index])
+        .collect(),
+      None => Vec::new()
+    }
+  }
+
+  /// Replays the only game of a file, or lets the user pick one
+  pub fn open_pgn(
+    &mut self,
+    games: Vec<PgnGame>
+  ) -> Result<(), String> {
+    match games.len() {
+      0 => Err(String::from("No game to replay")),
+      1 => {
+        self.replay(games.into_iter().next().expect("There is one game"));
+        Ok(())
+      },
+      _ => {
+        self.picker = Some(GamePicker {
+          games,
+          query: String::new(),
+          list: ListState::default()
+        });
+        if let Some(picker) = &mut self.picker {
+          picker.select(0);
+        }
+        self.message = Some(String::from(
+          "Pick a game: type to search, arrows and enter, esc cancels"
+        ));
+        Ok(())
+      }
+    }
+  }
+
+  /// Shows a game from its first move. The computer and the clock are
+  /// turned off, they'd play on in the game being watched
+  pub fn replay(
+    &mut self,
+    game: PgnGame
+  ) {
+    self.engine = None;
+    self.clock = None;
+    self.set_game(game.game.clone());
+    self.go_to_ply(0);
+    self.replay = Some(Replay {
+      pgn:       game,
+      autoplay:  false,
+      speed:     Duration::from_secs(1),
+      last_step: Instant::now(),
+      jump:      None
+    });
+    self.message = Some(String::from(
+      "Replaying: arrows step, g jumps, p autoplays, esc plays on from here"
+    ));
+  }
+
+  /// Delay between moves when autoplaying
+  pub fn set_replay_speed(
+    &mut self,
+    speed: Duration
+  ) {
+    if let Some(replay) = &mut self.replay {
+      replay.speed = speed;
+    }
+  }
+
+  pub fn set_autoplay(
+    &mut self,
+    on: bool
+  ) {
+    if let Some(replay) = &mut self.replay {
+      replay.autoplay = on;
+      replay.last_step = Instant::now();
+    }
+  }
+
+  pub fn is_autoplaying(&self) -> bool {
+    self.replay.as_ref().is_some_and(|replay| replay.autoplay)
+  }
+
+  // Steps forward when the delay is up, stopping at the last move
+  pub(super) fn update_replay(&mut self) {
+    let due = match &self.replay {
+      Some(replay) => replay.autoplay && replay.last_step.elapsed() >= replay.speed,
+      None => false
+    };
+    if !due {
+      return
+    }
+
+    self.go_to_ply(self.ply() + 1);
+    let finished = self.ply() == self.game.moves().len();
+    if let Some(replay) = &mut self.replay {
+      replay.last_step = Instant::now();
+      replay.autoplay = !finished;
+    }
+  }
+
+  // Leaves the replay, the game goes on from the position on screen
+  fn stop_replay(&mut self) {
+    let ply = self.ply();
+    while self.game.moves().len() > ply {
+      self.game.undo();
+    }
+    self.san.truncate(ply);
+    self.replay = None;
+    self.browsing = None;
+    self.deselect();
+    self.message = Some(String::from("Playing on from here"));
+  }
+
+  // Picks a speed from `SPEEDS`, a step slower or faster than the current one
+  fn change_speed(
+    &mut self,
+    slower: bool
+  ) {
+    let replay = match &mut self.replay {
+      Some(replay) => replay,
+      None => return
+    };
+
+    let index = SPEEDS
+      .iter()
+      .position(|&speed| speed >= replay.speed)
+      .unwrap_or(SPEEDS.len() - 1);
+    let index = if slower {
+      (index + 1).min(SPEEDS.len() - 1)
+    } else {
+      index.saturating_sub(1)
+    };
+    replay.speed = SPEEDS[index];
+    self.message = Some(format!("{:.2}s a move", replay.speed.as_secs_f64()));
+  }
+
+  // Keys of the replay, the others are left to the usual handling. Returns
+  // whether the key was used
+  pub(super) fn handle_replay_key(
+    &mut self,
+    key: KeyEvent
+  ) -> bool {
+    let replay = match &mut self.replay {
+      Some(replay) => replay,
+      None => return false
+    };
+
+    if let Some(jump) = &mut replay.jump {
+      match key.code {
+        KeyCode::Char(c) if c.is_ascii_digit() => jump.push(c),
+        KeyCode::Backspace => {
+          jump.pop();
+        },
+        KeyCode::Enter => {
+          let ply = jump.parse();
+          replay.jump = None;
+          match ply {
+            Ok(ply) => self.go_to_ply(ply),
+            Err(_) => self.message = Some(String::from("Type the number of a ply"))
+          }
+        },
+        KeyCode::Esc => replay.jump = None,
+        _ => {}
+      }
+      return true
+    }
+
+    // Stepping by hand takes over from autoplay
+    let manual = !matches!(
+      key.code,
+      KeyCode::Char('p') | KeyCode::Char('+') | KeyCode::Char('-')
+    );
+    match key.code {
+      KeyCode::Right | KeyCode::Char('l') | KeyCode::Char(' ') | KeyCode::Char(']') => {
+        self.go_to_ply(self.ply() + 1)
+      },
+      KeyCode::Left | KeyCode::Char('h') | KeyCode::Char('[') => {
+        self.go_to_ply(self.ply().saturating_sub(1))
+      },
+      KeyCode::Home => self.go_to_ply(0),
+      KeyCode::End => self.go_to_ply(usize::MAX),
+      KeyCode::Char('g') => replay.jump = Some(String::new()),
+      KeyCode::Char('p') => {
+        let on = !replay.autoplay;
+        self.set_autoplay(on);
+      },
+      KeyCode::Char('+') => self.change_speed(false),
+      KeyCode::Char('-') => self.change_speed(true),
+      KeyCode::Esc => {
+        self.stop_replay();
+        return true
+      },
+      KeyCode::Enter | KeyCode::Up | KeyCode::Down | KeyCode::Char('u') => {
+        self.message = Some(String::from("Press esc to play on from here"))
+      },
+      _ => return false
+    }
+
+    if manual {
+      self.set_autoplay(false);
+    }
+    true
+  }
+
+  pub(super) fn handle_picker_key(
+    &mut self,
+    key: KeyEvent
+  ) {
+    let picker = match &mut self.picker {
+      Some(picker) => picker,
+      None => return
+    };
+
+    match key.code {
+      KeyCode::Esc => {
+        self.picker = None;
+        self.message = None;
+      },
+      KeyCode::Up => picker.select(-1),
+      KeyCode::Down => picker.select(1),
+      KeyCode::PageUp => picker.select(-10),
+      KeyCode::PageDown => picker.select(10),
+      KeyCode::Backspace => {
+        picker.query.pop();
+        picker.list.select(Some(0));
+        picker.select(0);
+      },
+      KeyCode::Char(c) => {
+        picker.query.push(c);
+        picker.list.select(Some(0));
+        picker.select(0);
+      },
+      KeyCode::Enter => {
+        let index = picker
+          .list
+          .selected()
+          .and_then(|selected| picker.matches().get(selected).copied());
+        match index {
+          Some(index) => {
+            let picker = self.picker.take().expect("The picker is open");
+            let game = picker
+              .games
+              .into_iter()
+              .nth(index)
+              .expect("Matches are games");
+            self.replay(game);
+          },
+          None => self.message = Some(String::from("No game matches"))
+        }
+      },
+      _ => {}
+    }
+  }
+
+  // Header, the comment on the move shown, the moves given instead of it
+  // and the autoplay state
+  pub(super) fn draw_replay<B: Backend>(
+    &self,
+    f: &mut Frame<B>,
+    area: Rect
+  ) {
+    let replay = match &self.replay {
+      Some(replay) => replay,
+      None => return
+    };
+
+    let mut lines = vec![Spans::from(replay.pgn.header())];
+    if let Some(ply) = self.ply().checked_sub(1) {
+      if let Some(comment) = &self.game.comments()[ply] {
+        lines.push(Spans::from(format!("{{{}}}", comment)));
+      }
+
+      let start = self.game.board_at(ply);
+      lines.extend(
+        replay
+          .pgn
+          .variations
+          .iter()
+          .filter(|variation| variation.ply == ply)
+          .map(|variation| Spans::from(format!("({})", variation.to_pgn(&start))))
+      );
+    }
+    lines.push(Spans::from(match &replay.jump {
+      Some(jump) => format!("Jump to ply: {}_", jump),
+      None => format!(
+        "p autoplay {} {:.2}s a move  +/- speed  g jump  esc play on",
+        if replay.autoplay { "on" } else { "off" },
+        replay.speed.as_secs_f64()
+      )
+    }));
+
+    f.render_widget(
+      Paragraph::new(lines)
+        .wrap(Wrap {
+          trim: true
+        })
+        .block(Block::default().title("Replay").borders(Borders::ALL)),
+      area
+    );
+  }
+
+  // Popup over the board and the sidebar with the search and the games
+  pub(super) fn draw_picker<B: Backend>(
+    &mut self,
+    f: &mut Frame<B>,
+    area: Rect
+  ) {
+    let picker = match &mut self.picker {
+      Some(picker) => picker,
+      None => return
+    };
+
+    let games: Vec<ListItem> = picker
+      .matches()
+      .into_iter()
+      .map(|index| ListItem::new(picker.games[index].header()))
+      .collect();
+    let count = games.len();
+
+    f.render_widget(Clear, area);
+    let block = Block::default()
+      .title(format!("Games {}/{}", count, picker.games.len()))
+      .borders(Borders::ALL);
+    let inner = block.inner(area);
+    f.render_widget(block, area);
+    if inner.height < 2 {
+      return
+    }
+
+    f.render_widget(
+      Paragraph::new(format!("Search: {}", picker.query)),
+      Rect {
+        height: 1,
+        ..inner
+      }
+    );
+    f.render_stateful_widget(
+      List::new(games).highlight_style(Style::default().add_modifier(Modifier::REVERSED)),
+      Rect {
+        y: inner.y + 1,
+        height: inner.height - 1,
+        ..inner
+      },
+      &mut picker.list
+    );
+  }
+}
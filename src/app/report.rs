@@ -0,0 +1,259 @@
+use std::fs;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver};
+use std::sync::Arc;
+use std::thread;
+
+use tui::backend::Backend;
+use tui::layout::{Constraint, Direction, Layout, Rect};
+use tui::style::{Color as TermColor, Style};
+use tui::symbols::Marker;
+use tui::text::Spans;
+use tui::widgets::{Axis, Block, Borders, Chart, Dataset, Gauge, GraphType, Paragraph};
+use tui::Frame;
+
+use super::App;
+use crate::report::{self, Judgement, Report};
+use crate::{Color, Move};
+
+enum Progress {
+  Searched(usize, usize),
+  Done(Report)
+}
+
+// Report of the game being worked out on its own thread, stopped when dropped
+pub(super) struct GameReport {
+  stop:     Arc<AtomicBool>,
+  receiver: Receiver<Progress>,
+  // Moves of the game it's for, in case the game changed meanwhile
+  moves:    Vec<Move>,
+  searched: (usize, usize),
+  report:   Option<Report>
+}
+
+impl Drop for GameReport {
+  fn drop(&mut self) { self.stop.store(true, Ordering::Relaxed); }
+}
+
+impl App {
+  /// Report of the game, once the engine went over all of it
+  pub fn report(&self) -> Option<&Report> {
+    self
+      .report
+      .as_ref()
+      .and_then(|report| report.report.as_ref())
+  }
+
+  /// Positions searched so far out of all of them, while the report is
+  /// being worked out
+  pub fn report_progress(&self) -> Option<(usize, usize)> {
+    self
+      .report
+      .as_ref()
+      .filter(|report| report.report.is_none())
+      .map(|report| report.searched)
+  }
+
+  /// Has the engine go over every move of the game in the background
+  pub fn start_report(&mut self) -> Result<String, String> {
+    if self.game.moves().is_empty() {
+      return Err(String::from("No moves to report on"))
+    }
+
+    let (sender, receiver) = mpsc::channel();
+    let stop = Arc::new(AtomicBool::new(false));
+    let game = self.game.clone();
+    let evaluator = Arc::clone(&self.evaluator);
+
+    let thread_stop = Arc::clone(&stop);
+    thread::spawn(move || {
+      let report = report::analyse(
+        &game,
+        &evaluator,
+        &report::limits(),
+        &thread_stop,
+        |searched, total| {
+          let _ = sender.send(Progress::Searched(searched, total));
+        }
+      );
+      // The app may have moved on, nobody is listening then
+      let _ = sender.send(Progress::Done(report));
+    });
+
+    self.report = Some(GameReport {
+      stop,
+      receiver,
+      moves: self.game.moves().to_vec(),
+      searched: (0, self.game.moves().len() + 1),
+      report: None
+    });
+    Ok(String::from("Going over the game"))
+  }
+
+  pub fn close_report(&mut self) { self.report = None; }
+
+  /// Writes the game annotated with the report
+  pub fn save_report(
+    &self,
+    path: &str
+  ) -> Result<String, String> {
+    let report = self.report().ok_or("No report yet, run :report first")?;
+
+    let mut tags = vec![("Annotator", String::from("chyes"))];
+    tags.extend(
+      self
+        .clock
+        .iter()
+        .map(|clock| ("TimeControl", clock.control().to_pgn_tag()))
+    );
+    fs::write(path, report.to_pgn(&self.game, &tags))
+      .map_err(|err| format!("Couldn't write {}: {}", path, err))?;
+    Ok(format!("Saved the annotated game to {}", path))
+  }
+
+  // How the move of the ply was judged, in the move list
+  pub(super) fn judgement(
+    &self,
+    ply: usize
+  ) -> Option<Judgement> {
+    self.report()?.moves.get(ply)?.judgement
+  }
+
+  // Collects the progress of the report, and drops it when the game changed
+  pub(super) fn update_report(&mut self) {
+    let report = match &mut self.report {
+      Some(report) => report,
+      None => return
+    };
+    if report.moves != self.game.moves() {
+      self.report = None;
+      return
+    }
+
+    let mut done = None;
+    for progress in report.receiver.try_iter() {
+      match progress {
+        Progress::Searched(searched, total) => report.searched = (searched, total),
+        Progress::Done(result) => done = Some(result)
+      }
+    }
+
+    if let Some(result) = done {
+      self.message = Some(format!(
+        "Report of {} moves done, :report save <file> writes it",
+        result.moves.len()
+      ));
+      report.report = Some(result);
+    }
+  }
+
+  pub(super) fn draw_report<B: Backend>(
+    &self,
+    f: &mut Frame<B>,
+    area: Rect
+  ) {
+    let game_report = match &self.report {
+      Some(report) => report,
+      None => return
+    };
+
+    let block = Block::default().title("Report").borders(Borders::ALL);
+    let inner = block.inner(area);
+    f.render_widget(block, area);
+
+    let report = match &game_report.report {
+      Some(report) => report,
+      None => {
+        let (searched, total) = game_report.searched;
+        return f.render_widget(
+          Gauge::default()
+            .gauge_style(
+              Style::default()
+                .fg(TermColor::White)
+                .bg(TermColor::DarkGray)
+            )
+            .ratio(searched as f64 / total.max(1) as f64)
+            .label(format!("Searched {} of {} positions", searched, total)),
+          Rect {
+            height: 1,
+            ..inner
+          }
+        )
+      }
+    };
+
+    let chunks = Layout::default()
+      .direction(Direction::Vertical)
+      .constraints([Constraint::Length(3), Constraint::Min(0)])
+      .split(inner);
+
+    // The move on screen with what the engine thinks of it
+    let ply = self.ply();
+    let current = match ply.checked_sub(1).and_then(|ply| report.moves.get(ply)) {
+      Some(played) => {
+        let number = self.game.board_at(ply - 1).fullmove_number;
+        let dots = match played.color {
+          Color::White => ".",
+          Color::Black => "..."
+        };
+        let eval = played
+          .eval
+          .map_or(String::from("game over"), |eval| eval.to_string());
+        let verdict = played
+          .verdict()
+          .map_or(String::new(), |verdict| format!(", {}", verdict));
+        format!(
+          "{}{} {}{} {}{}",
+          number,
+          dots,
+          played.san,
+          played.judgement.map_or("", Judgement::symbol),
+          eval,
+          verdict
+        )
+      },
+      None => String::from("Starting position")
+    };
+    f.render_widget(
+      Paragraph::new(vec![
+        Spans::from(report.summary(Color::White)),
+        Spans::from(report.summary(Color::Black)),
+        Spans::from(current),
+      ]),
+      chunks[0]
+    );
+
+    // Scores in pawns from white's point of view, with the ply on screen
+    let points: Vec<(f64, f64)> = report
+      .curve()
+      .iter()
+      .enumerate()
+      .map(|(ply, &cp)| (ply as f64, cp as f64 / 100.0))
+      .collect();
+    let zero = [(0.0, 0.0), (report.moves.len() as f64, 0.0)];
+    let marker = [(ply as f64, -10.0), (ply as f64, 10.0)];
+    let datasets = vec![
+      Dataset::default()
+        .marker(Marker::Braille)
+        .graph_type(GraphType::Line)
+        .style(Style::default().fg(TermColor::DarkGray))
+        .data(&zero),
+      Dataset::default()
+        .marker(Marker::Braille)
+        .graph_type(GraphType::Line)
+        .style(Style::default().fg(TermColor::Yellow))
+        .data(&marker),
+      Dataset::default()
+        .marker(Marker::Braille)
+        .graph_type(GraphType::Line)
+        .style(Style::default().fg(TermColor::White))
+        .data(&points),
+    ];
+    f.render_widget(
+      Chart::new(datasets)
+        .x_axis(Axis::default().bounds([0.0, report.moves.len().max(1) as f64]))
+        .y_axis(Axis::default().bounds([-10.0, 10.0])),
+      chunks[1]
+    );
+  }
+}
@@ -0,0 +1,135 @@
+use std::time::Instant;
+
+use tui::backend::Backend;
+use tui::layout::Rect;
+use tui::style::{Modifier, Style};
+use tui::text::{Span, Spans};
+use tui::widgets::{Block, Borders, Paragraph};
+use tui::Frame;
+
+use super::App;
+use crate::clock::{self, Clock, TimeControl};
+use crate::Color;
+
+impl App {
+  pub fn clock(&self) -> Option<&Clock> { self.clock.as_ref() }
+
+  /// Plays with a clock, which starts for the side to move right away
+  pub fn set_clock(
+    &mut self,
+    control: Option<TimeControl>
+  ) {
+    self.clock = control.map(|control| {
+      let mut clock = Clock::new(control);
+      if self.game.outcome().is_none() {
+        clock.start(self.game.board().turn, Instant::now());
+      }
+      clock
+    });
+  }
+
+  // Same control from the start, for a new game
+  pub(super) fn reset_clock(&mut self) {
+    let control = self.clock.as_ref().map(|clock| clock.control().clone());
+    self.set_clock(control);
+  }
+
+  // Presses the clock after a move and notes the mover's time in the game
+  pub(super) fn clock_move(&mut self) {
+    let now = Instant::now();
+    let clock = match &mut self.clock {
+      Some(clock) => clock,
+      None => return
+    };
+
+    if let Some(remaining) = clock.press(now) {
+      self.game.comment(clock::clk_comment(remaining));
+    }
+    if self.game.outcome().is_some() {
+      clock.stop(now);
+    }
+  }
+
+  // Stops the clock for good, once the game is decided
+  pub(super) fn stop_clock(&mut self) {
+    if let Some(clock) = &mut self.clock {
+      clock.stop(Instant::now());
+    }
+  }
+
+  // Runs the clock of the side to move again after moves were taken back
+  pub(super) fn restart_clock(&mut self) {
+    let now = Instant::now();
+    let outcome = self.game.outcome();
+    let turn = self.game.board().turn;
+
+    if let Some(clock) = &mut self.clock {
+      clock.stop(now);
+      if outcome.is_none() {
+        clock.start(turn, now);
+      }
+    }
+  }
+
+  // Ends the game when a flag falls
+  pub(super) fn update_clock(&mut self) {
+    // A game decided otherwise can't be lost on time anymore
+    if self.game.outcome().is_some() {
+      return self.stop_clock()
+    }
+
+    let now = Instant::now();
+    let flagged = match &mut self.clock {
+      Some(clock) if clock.running().is_some() => clock.flagged(now),
+      _ => return
+    };
+
+    if let Some(flagged) = flagged {
+      let (result, reason) = clock::flag_result(self.game.board(), flagged);
+      self.stop_thinking();
+      self.game.decide(result, reason.clone());
+      self.message = Some(reason);
+      if let Some(clock) = &mut self.clock {
+        clock.stop(now);
+      }
+    }
+  }
+
+  pub(super) fn draw_clock<B: Backend>(
+    &self,
+    f: &mut Frame<B>,
+    area: Rect
+  ) {
+    let clock = match &self.clock {
+      Some(clock) => clock,
+      None => return
+    };
+    let now = Instant::now();
+
+    let lines: Vec<Spans> = [Color::White, Color::Black]
+      .into_iter()
+      .map(|color| {
+        let text = format!(
+          "{:<6} {:>9}",
+          color.name(),
+          clock::format_time(clock.remaining(color, now))
+        );
+        let style = if clock.running() == Some(color) {
+          Style::default().add_modifier(Modifier::BOLD | Modifier::REVERSED)
+        } else {
+          Style::default()
+        };
+        Spans::from(Span::styled(text, style))
+      })
+      .collect();
+
+    f.render_widget(
+      Paragraph::new(lines).block(
+        Block::default()
+          .title(format!("Clock {}", clock.control()))
+          .borders(Borders::ALL)
+      ),
+      area
+    );
+  }
+}
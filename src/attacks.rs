@@ -0,0 +1,125 @@
+use std::collections::HashMap;
+
+use crate::{Board, Color, Coordinate, Piece, Pieces};
+
+pub(crate) const DIRECTIONS: [(i8, i8); 8] = [
+  (-1, -1),
+  (-1, 0),
+  (-1, 1),
+  (0, -1),
+  (0, 1),
+  (1, -1),
+  (1, 0),
+  (1, 1)
+];
+
+const KNIGHT_JUMPS: [(i8, i8); 8] = [
+  (-2, -1),
+  (-2, 1),
+  (-1, -2),
+  (-1, 2),
+  (1, -2),
+  (1, 2),
+  (2, -1),
+  (2, 1)
+];
+
+pub(crate) fn step(
+  coord: Coordinate,
+  (d_row, d_col): (i8, i8)
+) -> Option<Coordinate> {
+  let new_coord = coord!(
+    (coord.row as i8 + d_row) as u8,
+    (coord.col as i8 + d_col) as u8
+  );
+
+  new_coord.is_valid().then_some(new_coord)
+}
+
+pub(crate) fn is_diagonal((d_row, d_col): (i8, i8)) -> bool { d_row != 0 && d_col != 0 }
+
+// Whether a piece slides along the direction
+pub(crate) fn slides_along(
+  breed: Pieces,
+  delta: (i8, i8)
+) -> bool {
+  match breed {
+    Pieces::Queen => true,
+    Pieces::Bishop => is_diagonal(delta),
+    Pieces::Rook => !is_diagonal(delta),
+    _ => false
+  }
+}
+
+// Pieces of the color attacking the target, on the given occupancy
+pub(crate) fn attackers(
+  occupancy: &HashMap<Coordinate, Piece>,
+  target: Coordinate,
+  color: Color
+) -> Vec<(Coordinate, Piece)> {
+  let mut candidates: Vec<(Coordinate, Pieces)> = Vec::new();
+
+  // Pawns attack the target from behind, relative to their direction
+  let behind = if color == Color::White { 1 } else { -1 };
+  for side in [-1, 1] {
+    if let Some(coord) = step(target, (behind, side)) {
+      candidates.push((coord, Pieces::Pawn));
+    }
+  }
+
+  for delta in KNIGHT_JUMPS {
+    if let Some(coord) = step(target, delta) {
+      candidates.push((coord, Pieces::Knight));
+    }
+  }
+
+  for delta in DIRECTIONS {
+    if let Some(coord) = step(target, delta) {
+      candidates.push((coord, Pieces::King));
+    }
+
+    let mut current = step(target, delta);
+    while let Some(coord) = current {
+      if let Some(piece) = occupancy.get(&coord) {
+        if slides_along(piece.breed, delta) {
+          candidates.push((coord, piece.breed));
+        }
+        break
+      }
+
+      current = step(coord, delta);
+    }
+  }
+
+  candidates
+    .into_iter()
+    .filter_map(|(coord, breed)| {
+      occupancy
+        .get(&coord)
+        .filter(|piece| piece.color == color && piece.breed == breed)
+        .map(|piece| (coord, *piece))
+    })
+    .collect()
+}
+
+impl Board {
+  pub fn is_attacked(
+    &self,
+    target: Coordinate,
+    by: Color
+  ) -> bool {
+    !attackers(&self.pieces, target, by).is_empty()
+  }
+
+  // Squares of the pieces of the color attacking the target
+  pub fn attackers(
+    &self,
+    target: Coordinate,
+    by: Color
+  ) -> Vec<Coordinate> {
+    attackers(&self.pieces, target, by)
+      .into_iter()
+      .map(|(coord, _)| coord)
+      .collect()
+  }
+}
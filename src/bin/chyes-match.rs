@@ -0,0 +1,180 @@
+use std::process;
+
+use chyes::selfplay::{self, load_openings, EngineConfig, MatchConfig, SprtResult};
+use cli::Usage;
+
+#[path = "../cli.rs"]
+mod cli;
+
+const USAGE: Usage = Usage(
+  "Usage: chyes-match --engine <spec> --engine <spec> [options]
+
+Plays two engine configurations against each other, every opening once with each color.
+
+Engine spec, comma separated:
+  name=<name>             Name in the PGN and the report
+  params=<file>           Handcrafted evaluation weights for our own search
+  network=<file>          Neural network for our own search
+  cmd=<path>              Run a UCI engine instead, with arg=<arg> and option.<name>=<value>
+  depth=<n>, nodes=<n>    Search limits besides the clock
+
+Options:
+  --openings <file>       One FEN per line (default: the starting position)
+  --games <n>             Number of games (default: 100)
+  --tc <base+inc>         Time control in seconds, or `none` (default: 10+0.1)
+  --concurrency <n>       Games played at the same time (default: 1)
+  --pgn <file>            Append the games to a PGN file
+  --sprt <spec>           Stop once decided: elo0=0,elo1=5,alpha=0.05,beta=0.05
+  --draw <spec>           Adjudicate draws: movenumber=40,movecount=8,score=10
+  --resign <spec>         Adjudicate losses: movecount=3,score=600
+  --maxmoves <n>          Adjudicate a draw after this many moves"
+);
+
+fn main() {
+  let mut args = USAGE.args();
+  let mut config = MatchConfig::default();
+  let mut engines = Vec::new();
+
+  while let Some(arg) = args.next() {
+    match arg.as_str() {
+      "-h" | "--help" => {
+        USAGE.print();
+        return
+      },
+      "--engine" => engines.push(
+        args
+          .value(&arg)
+          .parse::<EngineConfig>()
+          .unwrap_or_else(|err| USAGE.fail(&err))
+      ),
+      "--openings" => {
+        config.openings = load_openings(args.value(&arg)).unwrap_or_else(|err| USAGE.fail(&err))
+      },
+      "--games" => {
+        config.games = args
+          .value(&arg)
+          .parse()
+          .unwrap_or_else(|_| USAGE.fail("Invalid number of games"))
+      },
+      "--tc" => {
+        config.time_control = match args.value(&arg).as_str() {
+          "none" => None,
+          tc => Some(tc.parse().unwrap_or_else(|err: String| USAGE.fail(&err)))
+        }
+      },
+      "--concurrency" => {
+        config.concurrency = args
+          .value(&arg)
+          .parse()
+          .unwrap_or_else(|_| USAGE.fail("Invalid concurrency"))
+      },
+      "--pgn" => config.pgn = Some(args.value(&arg).into()),
+      "--sprt" => {
+        config.sprt = Some(
+          args
+            .value(&arg)
+            .parse()
+            .unwrap_or_else(|err: String| USAGE.fail(&err))
+        )
+      },
+      "--draw" => {
+        config.adjudication.draw = Some(
+          args
+            .value(&arg)
+            .parse()
+            .unwrap_or_else(|err: String| USAGE.fail(&err))
+        )
+      },
+      "--resign" => {
+        config.adjudication.resign = Some(
+          args
+            .value(&arg)
+            .parse()
+            .unwrap_or_else(|err: String| USAGE.fail(&err))
+        )
+      },
+      "--maxmoves" => {
+        config.adjudication.max_moves = Some(
+          args
+            .value(&arg)
+            .parse()
+            .unwrap_or_else(|_| USAGE.fail("Invalid number of moves"))
+        )
+      },
+      _ => USAGE.fail(&format!("Unexpected argument {}", arg))
+    }
+  }
+
+  config.engines = match <[EngineConfig; 2]>::try_from(engines) {
+    Ok(engines) => engines,
+    Err(_) => USAGE.fail("Exactly two engines are needed")
+  };
+
+  if config.time_control.is_none()
+    && config
+      .engines
+      .iter()
+      .any(|engine| engine.depth.is_none() && engine.nodes.is_none())
+  {
+    USAGE.fail("Without a time control every engine needs a depth or nodes limit");
+  }
+
+  let sprt = config.sprt;
+  let result = selfplay::run(&config, |record, stats| {
+    let (white, black) = if record.white == 0 { (0, 1) } else { (1, 0) };
+    println!(
+      "Game {} ({} vs {}): {} {{{}}}",
+      record.number,
+      config.engines[white].name,
+      config.engines[black].name,
+      record.outcome.result(),
+      record.outcome
+    );
+
+    let elo = match stats.elo() {
+      Some((elo, margin)) => format!("{:.1} +/- {:.1}", elo, margin),
+      None => String::from("-")
+    };
+    print!(
+      "Score of {} vs {}: {} - {} - {} [{:.3}] {}, Elo {}",
+      config.engines[0].name,
+      config.engines[1].name,
+      stats.wins,
+      stats.losses,
+      stats.draws,
+      stats.score(),
+      stats.games(),
+      elo
+    );
+
+    match sprt {
+      Some(sprt) => {
+        let (lower, upper) = sprt.bounds();
+        println!(", LLR {:.2} ({:.2}, {:.2})", sprt.llr(stats), lower, upper);
+      },
+      None => println!()
+    }
+  });
+
+  let stats = result.unwrap_or_else(|err| {
+    eprintln!("{}", err);
+    process::exit(1)
+  });
+
+  if let Some(sprt) = sprt {
+    println!(
+      "{}",
+      match sprt.result(&stats) {
+        Some(SprtResult::AcceptH1) => format!(
+          "H1 accepted: {} is stronger by at least {} Elo",
+          config.engines[0].name, sprt.elo1
+        ),
+        Some(SprtResult::AcceptH0) => format!(
+          "H0 accepted: {} isn't stronger by more than {} Elo",
+          config.engines[0].name, sprt.elo0
+        ),
+        None => String::from("SPRT inconclusive")
+      }
+    );
+  }
+}
@@ -0,0 +1,106 @@
+use std::process;
+
+use chyes::eval::EvalParams;
+use chyes::tuning::{Dataset, Method, Tuner};
+use cli::Usage;
+
+#[path = "../cli.rs"]
+mod cli;
+
+const USAGE: Usage = Usage(
+  "Usage: chyes-tune <dataset> [options]
+
+Tunes the handcrafted evaluation on a file of quiet positions, one `<fen> <result>` per line.
+
+Options:
+  --params <file>         Starting weights (default: built-in weights)
+  --output <file>         Where to write the tuned weights (default: params.cfg)
+  --method <local|gradient>
+  --iterations <n>        Maximum number of passes (default: 100)
+  --step <n>              Local search step (default: 1)
+  --rate <x>              Gradient descent learning rate (default: 10000)"
+);
+
+fn main() {
+  let mut args = USAGE.args();
+  let mut dataset = None;
+  let mut params = EvalParams::default();
+  let mut output = String::from("params.cfg");
+  let mut gradient = false;
+  let mut iterations = 100;
+  let mut step = 1;
+  let mut rate = 10_000.0;
+
+  while let Some(arg) = args.next() {
+    match arg.as_str() {
+      "-h" | "--help" => {
+        USAGE.print();
+        return
+      },
+      "--params" => {
+        params =
+          EvalParams::load(args.value(&arg)).unwrap_or_else(|err| USAGE.fail(&err.to_string()))
+      },
+      "--output" => output = args.value(&arg),
+      "--method" => {
+        gradient = match args.value(&arg).as_str() {
+          "local" => false,
+          "gradient" => true,
+          other => USAGE.fail(&format!("Unknown method {}", other))
+        }
+      },
+      "--iterations" => {
+        iterations = args
+          .value(&arg)
+          .parse()
+          .unwrap_or_else(|_| USAGE.fail("Invalid number of iterations"))
+      },
+      "--step" => {
+        step = args
+          .value(&arg)
+          .parse()
+          .unwrap_or_else(|_| USAGE.fail("Invalid step"))
+      },
+      "--rate" => {
+        rate = args
+          .value(&arg)
+          .parse()
+          .unwrap_or_else(|_| USAGE.fail("Invalid learning rate"))
+      },
+      _ if dataset.is_none() && !arg.starts_with('-') => dataset = Some(arg),
+      _ => USAGE.fail(&format!("Unexpected argument {}", arg))
+    }
+  }
+
+  let dataset = Dataset::load(dataset.unwrap_or_else(|| USAGE.fail("Missing dataset")))
+    .unwrap_or_else(|err| USAGE.fail(&err.to_string()));
+
+  let tuner = Tuner::new(&dataset, &params);
+  println!(
+    "{} positions, K = {:.4}, error = {:.6}",
+    dataset.len(),
+    tuner.k(),
+    tuner.mean_squared_error(&params)
+  );
+
+  let method = if gradient {
+    Method::GradientDescent {
+      learning_rate: rate
+    }
+  } else {
+    Method::LocalSearch {
+      step
+    }
+  };
+
+  let tuned = tuner.tune(&params, method, iterations, |iteration, error| {
+    println!("Iteration {}: error = {:.6}", iteration, error);
+  });
+
+  if let Err(err) = tuned.save(&output) {
+    eprintln!("Couldn't write {}: {}", output, err);
+    process::exit(1);
+  }
+
+  println!("Tuned weights written to {}", output);
+}
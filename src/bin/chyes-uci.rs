@@ -0,0 +1,44 @@
+use std::io;
+
+use chyes::eval::{EvalParams, Evaluator};
+use chyes::uci;
+use cli::Usage;
+
+#[path = "../cli.rs"]
+mod cli;
+
+const USAGE: Usage = Usage(
+  "Usage: chyes-uci [options]
+
+Runs the engine with the universal chess interface on stdin and stdout.
+
+Options:
+  --network <file>        Evaluate with a neural network
+  --params <file>         Handcrafted evaluation weights (default: built-in weights)"
+);
+
+fn main() {
+  let mut args = USAGE.args();
+  let mut evaluator = Evaluator::default();
+
+  while let Some(arg) = args.next() {
+    match arg.as_str() {
+      "-h" | "--help" => {
+        USAGE.print();
+        return
+      },
+      "--network" => {
+        evaluator = Evaluator::load(Some(args.value(&arg).as_ref()))
+          .unwrap_or_else(|err| USAGE.fail(&err.to_string()))
+      },
+      "--params" => {
+        evaluator = Evaluator::Handcrafted(
+          EvalParams::load(args.value(&arg)).unwrap_or_else(|err| USAGE.fail(&err.to_string()))
+        )
+      },
+      _ => USAGE.fail(&format!("Unexpected argument {}", arg))
+    }
+  }
+
+  uci::serve(evaluator, io::stdin().lock(), io::stdout());
+}
@@ -0,0 +1,57 @@
+// Command line handling shared by the binaries: options with a value and
+// bare arguments, errors printed along with the usage. It exits the process,
+// so each binary includes it as a module and the library stays out of it
+
+use std::{env, iter, process};
+
+/// Usage text of a binary, shown for `--help` and after argument errors
+#[derive(Clone, Copy, Debug)]
+pub struct Usage(pub &'static str);
+
+impl Usage {
+  pub fn print(self) {
+    println!("{}", self.0);
+  }
+
+  /// Prints the message with the usage and exits with status 2
+  pub fn fail(
+    self,
+    message: &str
+  ) -> ! {
+    eprintln!("{}\n\n{}", message, self.0);
+    process::exit(2)
+  }
+
+  /// Arguments after the program name
+  pub fn args(self) -> Args {
+    Args {
+      usage: self,
+      args:  env::args().skip(1)
+    }
+  }
+}
+
+/// Arguments of the command line, read one at a time
+pub struct Args {
+  usage: Usage,
+  args:  iter::Skip<env::Args>
+}
+
+impl Iterator for Args {
+  type Item = String;
+
+  fn next(&mut self) -> Option<String> { self.args.next() }
+}
+
+impl Args {
+  /// Argument following the option, failing when there's none
+  pub fn value(
+    &mut self,
+    option: &str
+  ) -> String {
+    let usage = self.usage;
+    self
+      .next()
+      .unwrap_or_else(|| usage.fail(&format!("Missing value for {}", option)))
+  }
+}
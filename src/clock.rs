@@ -0,0 +1,399 @@
+use core::fmt;
+use std::str::FromStr;
+use std::time::{Duration, Instant};
+
+use crate::game::GameResult;
+use crate::{Board, Color, Pieces};
+
+/// What a player gets back for a move
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Bonus {
+  // Added after every move
+  Fischer(Duration),
+  // The clock waits this long before running
+  SimpleDelay(Duration),
+  // The time used is given back after the move, up to this much
+  Bronstein(Duration)
+}
+
+/// Part of a time control, `moves` in `time` or the rest of the game
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Stage {
+  pub moves: Option<u32>,
+  pub time:  Duration,
+  pub bonus: Option<Bonus>
+}
+
+/// Stages played one after the other, the last one repeats when it has a
+/// number of moves
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct TimeControl {
+  pub stages: Vec<Stage>
+}
+
+impl TimeControl {
+  pub fn sudden_death(time: Duration) -> Self {
+    TimeControl {
+      stages: vec![Stage {
+        moves: None,
+        time,
+        bonus: None
+      }]
+    }
+  }
+
+  /// `TimeControl` tag of PGN files, e.g. `40/5400+30:1800+30`. Delays have
+  /// no notation there and are left out
+  pub fn to_pgn_tag(&self) -> String {
+    self
+      .stages
+      .iter()
+      .map(|stage| {
+        let mut tag = match stage.moves {
+          Some(moves) => format!("{}/{}", moves, stage.time.as_secs()),
+          None => stage.time.as_secs().to_string()
+        };
+        if let Some(Bonus::Fischer(increment)) = stage.bonus {
+          tag.push_str(&format!("+{}", increment.as_secs()));
+        }
+        tag
+      })
+      .collect::<Vec<_>>()
+      .join(":")
+  }
+
+  fn stage(
+    &self,
+    index: usize
+  ) -> &Stage {
+    &self.stages[index.min(self.stages.len() - 1)]
+  }
+}
+
+// Minutes, or seconds with an `s` suffix
+fn parse_time(text: &str) -> Option<Duration> {
+  let (number, scale) = match text.strip_suffix('s') {
+    Some(seconds) => (seconds, 1.0),
+    None => (text.strip_suffix('m').unwrap_or(text), 60.0)
+  };
+
+  number
+    .parse::<f64>()
+    .ok()
+    .filter(|number| *number >= 0.0)
+    .map(|number| Duration::from_secs_f64(number * scale))
+}
+
+fn parse_seconds(text: &str) -> Option<Duration> {
+  text
+    .strip_suffix('s')
+    .unwrap_or(text)
+    .parse::<f64>()
+    .ok()
+    .filter(|seconds| *seconds >= 0.0)
+    .map(Duration::from_secs_f64)
+}
+
+impl FromStr for TimeControl {
+  type Err = String;
+
+  /// Stages separated by commas, each `[moves/]minutes` followed by `+seconds`
+  /// for an increment, `d` seconds for a simple delay or `b` seconds for a
+  /// Bronstein delay: `5+3`, `15d10`, `40/90+30,30+30`. Times in seconds take
+  /// an `s` suffix, `30s+1`
+  fn from_str(text: &str) -> Result<Self, Self::Err> {
+    let invalid = || format!("Invalid time control `{}`", text);
+    let mut stages = Vec::new();
+
+    for part in text.split(',') {
+      let part = part.trim();
+      let (moves, rest) = match part.split_once('/') {
+        Some((moves, rest)) => (Some(moves.parse::<u32>().map_err(|_| invalid())?), rest),
+        None => (None, part)
+      };
+      if moves == Some(0) {
+        return Err(invalid())
+      }
+
+      let (time, bonus) = match rest.find(['+', 'd', 'b']) {
+        Some(index) => {
+          let seconds = parse_seconds(&rest[index + 1..]).ok_or_else(invalid)?;
+          let bonus = match &rest[index..index + 1] {
+            "+" => Bonus::Fischer(seconds),
+            "d" => Bonus::SimpleDelay(seconds),
+            _ => Bonus::Bronstein(seconds)
+          };
+          (&rest[..index], Some(bonus))
+        },
+        None => (rest, None)
+      };
+
+      stages.push(Stage {
+        moves,
+        time: parse_time(time).ok_or_else(invalid)?,
+        bonus
+      });
+    }
+
+    Ok(TimeControl {
+      stages
+    })
+  }
+}
+
+impl fmt::Display for TimeControl {
+  fn fmt(
+    &self,
+    f: &mut fmt::Formatter<'_>
+  ) -> fmt::Result {
+    for (index, stage) in self.stages.iter().enumerate() {
+      if index > 0 {
+        write!(f, ",")?;
+      }
+      if let Some(moves) = stage.moves {
+        write!(f, "{}/", moves)?;
+      }
+
+      let seconds = stage.time.as_secs_f64();
+      if seconds % 60.0 == 0.0 {
+        write!(f, "{}", seconds / 60.0)?;
+      } else {
+        write!(f, "{}s", seconds)?;
+      }
+
+      match stage.bonus {
+        Some(Bonus::Fischer(time)) => write!(f, "+{}", time.as_secs_f64())?,
+        Some(Bonus::SimpleDelay(time)) => write!(f, "d{}", time.as_secs_f64())?,
+        Some(Bonus::Bronstein(time)) => write!(f, "b{}", time.as_secs_f64())?,
+        None => {}
+      }
+    }
+
+    Ok(())
+  }
+}
+
+/// `1:29:58`, or `4:59` under an hour and `9.8` under ten seconds
+pub fn format_time(time: Duration) -> String {
+  let tenths = time.as_millis() / 100;
+  let seconds = tenths / 10;
+
+  if seconds >= 3600 {
+    format!(
+      "{}:{:02}:{:02}",
+      seconds / 3600,
+      seconds / 60 % 60,
+      seconds % 60
+    )
+  } else if seconds >= 10 {
+    format!("{}:{:02}", seconds / 60, seconds % 60)
+  } else {
+    format!("{}.{}", seconds, tenths % 10)
+  }
+}
+
+/// Clock time of `[%clk]` comments, `h:mm:ss`
+pub fn clk_comment(time: Duration) -> String {
+  let seconds = time.as_secs();
+  format!(
+    "[%clk {}:{:02}:{:02}]",
+    seconds / 3600,
+    seconds / 60 % 60,
+    seconds % 60
+  )
+}
+
+#[derive(Clone, Copy, Debug)]
+struct Side {
+  remaining: Duration,
+  stage:     usize,
+  // Moves made in the current stage
+  moves:     u32
+}
+
+/// Chess clock for both players. Times are passed in so the clock can be
+/// driven by tests as well as by the wall clock
+#[derive(Clone, Debug)]
+pub struct Clock {
+  control: TimeControl,
+  sides:   [Side; 2],
+  // Side whose clock runs, and since when
+  running: Option<(Color, Instant)>
+}
+
+impl Clock {
+  pub fn new(control: TimeControl) -> Self {
+    let side = Side {
+      remaining: control.stage(0).time,
+      stage:     0,
+      moves:     0
+    };
+
+    Clock {
+      control,
+      sides: [side; 2],
+      running: None
+    }
+  }
+
+  pub fn control(&self) -> &TimeControl { &self.control }
+
+  /// Side whose clock is running
+  pub fn running(&self) -> Option<Color> { self.running.map(|(color, _)| color) }
+
+  /// Starts the clock of the side to move
+  pub fn start(
+    &mut self,
+    color: Color,
+    now: Instant
+  ) {
+    self.running = Some((color, now));
+  }
+
+  pub fn stop(
+    &mut self,
+    now: Instant
+  ) {
+    if let Some((color, since)) = self.running.take() {
+      let used = self.used(color, since, now);
+      let side = &mut self.sides[color as usize];
+      side.remaining = side.remaining.saturating_sub(used);
+    }
+  }
+
+  // Time taken from the clock since the move started, a simple delay is free
+  fn used(
+    &self,
+    color: Color,
+    since: Instant,
+    now: Instant
+  ) -> Duration {
+    let elapsed = now.saturating_duration_since(since);
+
+    match self.control.stage(self.sides[color as usize].stage).bonus {
+      Some(Bonus::SimpleDelay(delay)) => elapsed.saturating_sub(delay),
+      _ => elapsed
+    }
+  }
+
+  /// Ends the move of the running side and starts the other clock. Returns
+  /// the time left to the side that moved
+  pub fn press(
+    &mut self,
+    now: Instant
+  ) -> Option<Duration> {
+    let (color, since) = self.running?;
+    let elapsed = now.saturating_duration_since(since);
+    self.stop(now);
+
+    let control = &self.control;
+    let side = &mut self.sides[color as usize];
+    if side.remaining.is_zero() {
+      // Flagged, the time stays at zero
+      return Some(Duration::ZERO)
+    }
+
+    let stage = control.stage(side.stage);
+    match stage.bonus {
+      Some(Bonus::Fischer(increment)) => side.remaining += increment,
+      Some(Bonus::Bronstein(delay)) => side.remaining += elapsed.min(delay),
+      _ => {}
+    }
+
+    side.moves += 1;
+    if stage.moves == Some(side.moves) {
+      side.stage += 1;
+      side.moves = 0;
+      side.remaining += control.stage(side.stage).time;
+    }
+
+    let remaining = side.remaining;
+    self.running = Some((color.opposite(), now));
+    Some(remaining)
+  }
+
+  /// Time left, the running side's counting down
+  pub fn remaining(
+    &self,
+    color: Color,
+    now: Instant
+  ) -> Duration {
+    let remaining = self.sides[color as usize].remaining;
+
+    match self.running {
+      Some((running, since)) if running == color => {
+        remaining.saturating_sub(self.used(color, since, now))
+      },
+      _ => remaining
+    }
+  }
+
+  /// Side whose time ran out
+  pub fn flagged(
+    &self,
+    now: Instant
+  ) -> Option<Color> {
+    [Color::White, Color::Black]
+      .into_iter()
+      .find(|&color| self.remaining(color, now).is_zero())
+  }
+}
+
+impl Board {
+  /// Whether the side has the material to mate by any series of legal moves,
+  /// a lone king, a lone knight against a bare king and bishops all on squares
+  /// of the same color against bishops of that color don't
+  pub fn has_mating_material(
+    &self,
+    color: Color
+  ) -> bool {
+    let own: Vec<_> = self
+      .pieces
+      .iter()
+      .filter(|(_, piece)| piece.color == color && piece.breed != Pieces::King)
+      .collect();
+    let others: Vec<_> = self
+      .pieces
+      .iter()
+      .filter(|(_, piece)| piece.color != color && piece.breed != Pieces::King)
+      .collect();
+
+    match own.as_slice() {
+      [] => false,
+      [(_, piece)] if piece.breed == Pieces::Knight => !others.is_empty(),
+      _ => {
+        // Bishops only, on both sides, all on squares of one color
+        let square_color = |coord: &crate::Coordinate| (coord.row + coord.col) % 2;
+        let first = square_color(own[0].0);
+
+        !own
+          .iter()
+          .chain(others.iter())
+          .all(|(coord, piece)| piece.breed == Pieces::Bishop && square_color(coord) == first)
+      }
+    }
+  }
+}
+
+/// Result when `flagged` runs out of time, a draw when the opponent couldn't
+/// mate anyway
+pub fn flag_result(
+  board: &Board,
+  flagged: Color
+) -> (GameResult, String) {
+  if board.has_mating_material(flagged.opposite()) {
+    (
+      GameResult::win(flagged.opposite()),
+      format!("{} wins on time", flagged.opposite().name())
+    )
+  } else {
+    (
+      GameResult::Draw,
+      format!(
+        "Draw, {} ran out of time but {} can't mate",
+        flagged.name().to_lowercase(),
+        flagged.opposite().name().to_lowercase()
+      )
+    )
+  }
+}
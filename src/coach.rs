@@ -0,0 +1,104 @@
+// Training help: pieces left hanging, what the opponent threatens, hints and
+// how much a move gives away. The searches are short, they're meant to point
+// at the obvious, not to analyse
+
+use std::sync::atomic::AtomicBool;
+
+use crate::eval::Evaluator;
+use crate::search::{self, Limits};
+use crate::{Board, Color, Coordinate, Move, Pieces};
+
+impl Board {
+  /// Pieces of the color the opponent wins material by taking, attacked and
+  /// not defended well enough. Kings are never hanging
+  pub fn hanging_pieces(
+    &self,
+    color: Color
+  ) -> Vec<Coordinate> {
+    let mut hanging: Vec<Coordinate> = self
+      .pieces
+      .iter()
+      .filter(|(_, piece)| piece.color == color && piece.breed != Pieces::King)
+      .map(|(coord, _)| *coord)
+      .filter(|&coord| {
+        self
+          .attackers(coord, color.opposite())
+          .into_iter()
+          .any(|attacker| self.see_with(Move::new(attacker, coord), true) > 0)
+      })
+      .collect();
+
+    hanging.sort_by_key(|coord| (coord.row, coord.col));
+    hanging
+  }
+}
+
+/// Best move of the opponent if the side to move passed. There is no threat
+/// to speak of when the side to move is in check, passing isn't possible
+pub fn threat(
+  board: &Board,
+  evaluator: &Evaluator,
+  limits: &Limits,
+  stop: &AtomicBool
+) -> Option<Move> {
+  if board.is_in_check(board.turn) {
+    return None
+  }
+
+  let mut passed = board.clone();
+  passed.set_turn(board.turn.opposite());
+  passed.set_en_passant(None);
+
+  search::search(&passed, &[], evaluator, limits, stop, |_| {}).best_move
+}
+
+/// Move the engine suggests for the side to move
+pub fn hint(
+  board: &Board,
+  history: &[u64],
+  evaluator: &Evaluator,
+  limits: &Limits,
+  stop: &AtomicBool
+) -> Option<Move> {
+  search::search(board, history, evaluator, limits, stop, |_| {}).best_move
+}
+
+/// Centipawns the move gives away compared to the best one, which comes
+/// with it. The move is searched a ply less deep than the position, the
+/// same depth the best move got
+pub fn move_loss(
+  board: &Board,
+  history: &[u64],
+  evaluator: &Evaluator,
+  limits: &Limits,
+  stop: &AtomicBool,
+  mv: Move
+) -> Result<(i32, Option<Move>), String> {
+  let best = search::search(board, history, evaluator, limits, stop, |_| {});
+  if best.best_move == Some(mv) {
+    return Ok((0, best.best_move))
+  }
+
+  let mut after = board.clone();
+  after.apply_move(mv)?;
+  let mut history = history.to_vec();
+  history.push(board.key());
+  let shallower = Limits {
+    depth: limits.depth.map(|depth| depth.saturating_sub(1).max(1)),
+    ..limits.clone()
+  };
+  // Searches don't score positions without moves, mates and stalemates
+  let reply = if after.legal_moves().is_empty() {
+    if after.is_in_check(after.turn) {
+      return Ok((0, best.best_move))
+    }
+    0
+  } else {
+    search::search(&after, &history, evaluator, &shallower, stop, |_| {})
+      .score
+      .centipawns()
+  };
+
+  let loss = best.score.centipawns() + reply;
+  Ok((loss.max(0), best.best_move))
+}
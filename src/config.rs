@@ -0,0 +1,162 @@
+use core::fmt;
+use std::env;
+use std::fs;
+use std::io::ErrorKind;
+use std::path::PathBuf;
+use std::str::FromStr;
+
+use crate::clock::TimeControl;
+use crate::skill::Difficulty;
+use crate::theme::{value_of, ColorDepth, GlyphSet};
+use crate::view::Orientation;
+use crate::Color;
+
+/// What the app starts up doing
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum Mode {
+  #[default]
+  HumanVsHuman,
+  // The computer plays the side the user doesn't
+  Engine,
+  Analysis,
+  // Stepping through a loaded game
+  Replay
+}
+
+impl fmt::Display for Mode {
+  fn fmt(
+    &self,
+    f: &mut fmt::Formatter<'_>
+  ) -> fmt::Result {
+    write!(
+      f,
+      "{}",
+      match self {
+        Mode::HumanVsHuman => "hvh",
+        Mode::Engine => "engine",
+        Mode::Analysis => "analysis",
+        Mode::Replay => "replay"
+      }
+    )
+  }
+}
+
+impl FromStr for Mode {
+  type Err = String;
+
+  fn from_str(text: &str) -> Result<Self, Self::Err> {
+    match text.trim().to_lowercase().as_str() {
+      "hvh" | "human" => Ok(Mode::HumanVsHuman),
+      "engine" | "computer" => Ok(Mode::Engine),
+      "analysis" => Ok(Mode::Analysis),
+      "replay" => Ok(Mode::Replay),
+      _ => Err(format!(
+        "Unknown mode `{}`, expected hvh, engine, analysis or replay",
+        text
+      ))
+    }
+  }
+}
+
+/// Settings from the user's config file, all of them optional and kept as
+/// written until they're used. Command line flags take precedence
+#[derive(Clone, Default, PartialEq, Eq, Debug)]
+pub struct Config {
+  pub theme:       Option<String>,
+  pub pieces:      Option<String>,
+  pub colors:      Option<String>,
+  pub mode:        Option<String>,
+  pub side:        Option<String>,
+  pub orientation: Option<String>,
+  pub time:        Option<String>,
+  pub level:       Option<String>,
+  pub lines:       Option<String>,
+  pub network:     Option<String>,
+  pub params:      Option<String>,
+  pub accessible:  Option<String>
+}
+
+/// `chyes/config.toml` in the platform's config directory
+pub fn path() -> Option<PathBuf> {
+  let dir = if cfg!(windows) {
+    env::var_os("APPDATA").map(PathBuf::from)
+  } else if cfg!(target_os = "macos") {
+    env::var_os("HOME").map(|home| PathBuf::from(home).join("Library/Application Support"))
+  } else {
+    env::var_os("XDG_CONFIG_HOME")
+      .filter(|dir| !dir.is_empty())
+      .map(PathBuf::from)
+      .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))
+  };
+
+  dir.map(|dir| dir.join("chyes").join("config.toml"))
+}
+
+impl Config {
+  /// `key = value` lines in TOML style, `#` starts a comment
+  pub fn parse(text: &str) -> Result<Config, String> {
+    let mut config = Config::default();
+
+    for (number, line) in text.lines().enumerate() {
+      let line = line.trim();
+      if line.is_empty() || line.starts_with('#') || line.starts_with('[') {
+        continue
+      }
+
+      let (key, value) = line
+        .split_once('=')
+        .ok_or_else(|| format!("Line {}: expected `key = value`", number + 1))?;
+      let value = value_of(value);
+      // Values are checked here, so mistakes are reported with their line
+      let checked = |valid: Result<(), String>| {
+        valid
+          .map(|_| Some(String::from(value)))
+          .map_err(|err| format!("Line {}: {}", number + 1, err))
+      };
+
+      match key.trim() {
+        "theme" => config.theme = checked(Ok(()))?,
+        "pieces" => config.pieces = checked(value.parse::<GlyphSet>().map(drop))?,
+        "colors" => config.colors = checked(value.parse::<ColorDepth>().map(drop))?,
+        "mode" => config.mode = checked(value.parse::<Mode>().map(drop))?,
+        "side" => config.side = checked(value.parse::<Color>().map(drop))?,
+        "orientation" => config.orientation = checked(value.parse::<Orientation>().map(drop))?,
+        "time" => config.time = checked(value.parse::<TimeControl>().map(drop))?,
+        "level" => config.level = checked(value.parse::<Difficulty>().map(drop))?,
+        "lines" => {
+          config.lines = checked(match value.parse::<usize>() {
+            Ok(1..=10) => Ok(()),
+            _ => Err(String::from("Analysis lines go from 1 to 10"))
+          })?
+        },
+        "network" => config.network = checked(Ok(()))?,
+        "params" => config.params = checked(Ok(()))?,
+        "accessible" => {
+          config.accessible = checked(
+            value
+              .parse::<bool>()
+              .map(drop)
+              .map_err(|_| String::from("Expected true or false"))
+          )?
+        },
+        key => return Err(format!("Line {}: unknown setting `{}`", number + 1, key))
+      }
+    }
+
+    Ok(config)
+  }
+
+  /// Reads the config file, a missing one is the same as an empty one
+  pub fn load() -> Result<Config, String> {
+    let path = match path() {
+      Some(path) => path,
+      None => return Ok(Config::default())
+    };
+
+    match fs::read_to_string(&path) {
+      Ok(text) => Config::parse(&text).map_err(|err| format!("{}: {}", path.display(), err)),
+      Err(err) if err.kind() == ErrorKind::NotFound => Ok(Config::default()),
+      Err(err) => Err(format!("Couldn't read {}: {}", path.display(), err))
+    }
+  }
+}
@@ -0,0 +1,233 @@
+// The board in words, for screen readers and anyone who can't make out the
+// glyphs. Every sentence stands on its own, since it may be read out alone
+
+use crate::{Board, Color, Coordinate, Move, Piece, Pieces};
+
+pub fn piece_name(breed: Pieces) -> &'static str {
+  match breed {
+    Pieces::King => "king",
+    Pieces::Queen => "queen",
+    Pieces::Rook => "rook",
+    Pieces::Bishop => "bishop",
+    Pieces::Knight => "knight",
+    Pieces::Pawn => "pawn"
+  }
+}
+
+fn plural(breed: Pieces) -> String { format!("{}s", piece_name(breed)) }
+
+// `a`, `a and b`, `a, b and c`
+fn list(items: &[String]) -> String {
+  match items {
+    [] => String::new(),
+    [item] => item.clone(),
+    [rest @ .., last] => format!("{} and {}", rest.join(", "), last)
+  }
+}
+
+// `white knight`
+fn piece_words(piece: Piece) -> String {
+  format!(
+    "{} {}",
+    piece.color.name().to_lowercase(),
+    piece_name(piece.breed)
+  )
+}
+
+impl Board {
+  /// The move as it would be read out, before it's played: `White knight
+  /// from g1 to f3, check`
+  pub fn describe_move(
+    &self,
+    mv: Move
+  ) -> String {
+    let piece = match self.get_piece(&mv.start) {
+      Some(piece) => piece,
+      None => return format!("No piece on {}", mv.start)
+    };
+    let color = piece.color.name();
+
+    let mut words = if piece.breed == Pieces::King && mv.start.col.abs_diff(mv.target.col) == 2 {
+      let side = if mv.target.col > mv.start.col {
+        "king"
+      } else {
+        "queen"
+      };
+      format!("{} castles {} side", color, side)
+    } else {
+      let action = match self.get_piece(&mv.target) {
+        Some(taken) => format!("takes {} on {}", piece_name(taken.breed), mv.target),
+        // A pawn changing file onto an empty square
+        None if piece.breed == Pieces::Pawn && mv.start.col != mv.target.col => {
+          format!("takes pawn en passant on {}", mv.target)
+        },
+        None => format!("to {}", mv.target)
+      };
+      format!(
+        "{} {} from {} {}",
+        color,
+        piece_name(piece.breed),
+        mv.start,
+        action
+      )
+    };
+
+    if piece.breed == Pieces::Pawn && (mv.target.row == 0 || mv.target.row == 7) {
+      words.push_str(&format!(
+        ", promotes to {}",
+        piece_name(mv.promotion.unwrap_or(Pieces::Queen))
+      ));
+    }
+
+    let mut after = self.clone();
+    if after.apply_move(mv).is_ok() {
+      let opponent = piece.color.opposite();
+      if after.is_in_checkmate(opponent) {
+        words.push_str(", checkmate");
+      } else if after.is_in_check(opponent) {
+        words.push_str(", check");
+      } else if after.is_in_stalemate(opponent) {
+        words.push_str(", stalemate");
+      }
+    }
+
+    words
+  }
+
+  /// `e4, white pawn` or `e5, empty`
+  pub fn describe_square(
+    &self,
+    coord: Coordinate
+  ) -> String {
+    match self.get_piece(&coord) {
+      Some(piece) => format!("{}, {}", coord, piece_words(piece)),
+      None => format!("{}, empty", coord)
+    }
+  }
+
+  // The pieces on the squares, in the order given
+  fn describe_line(
+    &self,
+    name: String,
+    squares: impl Iterator<Item = Coordinate>
+  ) -> String {
+    let pieces: Vec<String> = squares
+      .filter_map(|coord| {
+        self
+          .get_piece(&coord)
+          .map(|piece| format!("{} {}", coord, piece_words(piece)))
+      })
+      .collect();
+
+    if pieces.is_empty() {
+      format!("{} is empty", name)
+    } else {
+      format!("{}: {}", name, list(&pieces))
+    }
+  }
+
+  /// Pieces on a rank from 1 to 8, from the a file to the h file
+  pub fn describe_rank(
+    &self,
+    rank: u8
+  ) -> Result<String, String> {
+    if !(1..=8).contains(&rank) {
+      return Err(format!("No rank {}", rank))
+    }
+
+    let row = 8 - rank;
+    Ok(self.describe_line(format!("Rank {}", rank), (0..8).map(|col| coord!(row, col))))
+  }
+
+  /// Pieces on a file from `a` to `h`, from the first rank up
+  pub fn describe_file(
+    &self,
+    file: char
+  ) -> Result<String, String> {
+    if !('a'..='h').contains(&file) {
+      return Err(format!("No file {}", file))
+    }
+
+    let col = file as u8 - b'a';
+    Ok(self.describe_line(
+      format!("File {}", file),
+      (0..8).rev().map(|row| coord!(row, col))
+    ))
+  }
+
+  /// Every piece of a side, the king first and the pawns last: `White: king
+  /// on g1, rooks on a1 and f1, pawns on f2, g2 and h2`
+  pub fn describe_pieces(
+    &self,
+    color: Color
+  ) -> String {
+    let groups: Vec<String> = [
+      Pieces::King,
+      Pieces::Queen,
+      Pieces::Rook,
+      Pieces::Bishop,
+      Pieces::Knight,
+      Pieces::Pawn
+    ]
+    .into_iter()
+    .filter_map(|breed| {
+      let mut squares: Vec<Coordinate> = self
+        .pieces
+        .iter()
+        .filter(|(_, piece)| piece.color == color && piece.breed == breed)
+        .map(|(coord, _)| *coord)
+        .collect();
+      // Along the files, from white's side of the board
+      squares.sort_by_key(|coord| (coord.col, 7 - coord.row));
+
+      let names: Vec<String> = squares.iter().map(Coordinate::to_notation).collect();
+      match names.len() {
+        0 => None,
+        1 => Some(format!("{} on {}", piece_name(breed), names[0])),
+        _ => Some(format!("{} on {}", plural(breed), list(&names)))
+      }
+    })
+    .collect();
+
+    if groups.is_empty() {
+      format!("{}: no pieces", color.name())
+    } else {
+      format!("{}: {}", color.name(), groups.join(", "))
+    }
+  }
+
+  /// The side to move and whether it's in check, both sides' pieces, the
+  /// castling rights and the en passant square, one sentence each
+  pub fn describe_position(&self) -> String {
+    let turn = self.turn;
+    let mut sentences = vec![if self.is_in_checkmate(turn) {
+      format!("{} is checkmated", turn.name())
+    } else if self.is_in_stalemate(turn) {
+      format!("{} is stalemated", turn.name())
+    } else if self.is_in_check(turn) {
+      format!("{} to move, in check", turn.name())
+    } else {
+      format!("{} to move", turn.name())
+    }];
+
+    sentences.push(self.describe_pieces(Color::White));
+    sentences.push(self.describe_pieces(Color::Black));
+
+    for color in [Color::White, Color::Black] {
+      let sides: Vec<String> = [(true, "king side"), (false, "queen side")]
+        .into_iter()
+        .filter(|(king_side, _)| self.castling_right(color, *king_side))
+        .map(|(_, side)| String::from(side))
+        .collect();
+      if !sides.is_empty() {
+        sentences.push(format!("{} can castle {}", color.name(), list(&sides)));
+      }
+    }
+
+    if let Some(square) = self.en_passant() {
+      sentences.push(format!("En passant square {}", square));
+    }
+
+    sentences.join(". ")
+  }
+}
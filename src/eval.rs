@@ -0,0 +1,376 @@
+use std::path::Path;
+use std::{fs, io};
+
+use crate::nnue::{Accumulator, Network};
+use crate::{Board, Color, Coordinate, Move, Pieces, Undo};
+
+// The handcrafted evaluation is a weighted sum of features, each counted for
+// white minus black. Keeping it linear makes the weights easy to tune.
+pub const TERMS: [&str; 18] = [
+  "pawn",
+  "knight",
+  "bishop",
+  "rook",
+  "queen",
+  "pawn_advance",
+  "passed_pawn",
+  "doubled_pawn",
+  "isolated_pawn",
+  "knight_center",
+  "bishop_center",
+  "king_shelter",
+  "knight_mobility",
+  "bishop_mobility",
+  "rook_mobility",
+  "queen_mobility",
+  "bishop_pair",
+  "rook_open_file"
+];
+
+pub type Features = [i32; TERMS.len()];
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct EvalParams {
+  pub weights: [i32; TERMS.len()]
+}
+
+impl Default for EvalParams {
+  fn default() -> Self {
+    EvalParams {
+      weights: [
+        100, 320, 330, 500, 900, // Material
+        5, 20, -15, -10, // Pawn structure
+        10, 5, 10, // Placement
+        4, 4, 2, 1, // Mobility
+        30, 15 // Bishop pair, rooks on open files
+      ]
+    }
+  }
+}
+
+impl EvalParams {
+  pub fn get(
+    &self,
+    term: &str
+  ) -> Option<i32> {
+    TERMS
+      .iter()
+      .position(|&name| name == term)
+      .map(|i| self.weights[i])
+  }
+
+  /// Parses `term = value` lines, terms that aren't mentioned keep their default
+  pub fn from_config(config: &str) -> Result<Self, String> {
+    let mut params = EvalParams::default();
+
+    for (number, line) in config.lines().enumerate() {
+      let line = line.split('#').next().unwrap_or("").trim();
+
+      if line.is_empty() {
+        continue
+      }
+
+      let (name, value) = line
+        .split_once('=')
+        .ok_or_else(|| format!("Line {}: expected `term = value`", number + 1))?;
+      let index = TERMS
+        .iter()
+        .position(|&term| term == name.trim())
+        .ok_or_else(|| format!("Line {}: unknown term `{}`", number + 1, name.trim()))?;
+
+      params.weights[index] = value
+        .trim()
+        .parse()
+        .map_err(|_| format!("Line {}: invalid value `{}`", number + 1, value.trim()))?;
+    }
+
+    Ok(params)
+  }
+
+  pub fn to_config(&self) -> String {
+    TERMS
+      .iter()
+      .zip(self.weights.iter())
+      .map(|(term, weight)| format!("{} = {}\n", term, weight))
+      .collect()
+  }
+
+  pub fn load(path: impl AsRef<Path>) -> io::Result<Self> {
+    Self::from_config(&fs::read_to_string(path)?)
+      .map_err(|message| io::Error::new(io::ErrorKind::InvalidData, message))
+  }
+
+  pub fn save(
+    &self,
+    path: impl AsRef<Path>
+  ) -> io::Result<()> {
+    fs::write(path, self.to_config())
+  }
+
+  /// Evaluation in centipawns from the side to move's point of view
+  pub fn evaluate(
+    &self,
+    board: &Board
+  ) -> i32 {
+    let score = self.score(&features(board));
+
+    match board.turn {
+      Color::White => score,
+      Color::Black => -score
+    }
+  }
+
+  /// Evaluation of the features from white's point of view
+  pub fn score(
+    &self,
+    features: &Features
+  ) -> i32 {
+    self
+      .weights
+      .iter()
+      .zip(features.iter())
+      .map(|(weight, feature)| weight * feature)
+      .sum()
+  }
+}
+
+fn breed_term(breed: Pieces) -> Option<usize> {
+  match breed {
+    Pieces::Pawn => Some(0),
+    Pieces::Knight => Some(1),
+    Pieces::Bishop => Some(2),
+    Pieces::Rook => Some(3),
+    Pieces::Queen => Some(4),
+    Pieces::King => None
+  }
+}
+
+fn term(name: &str) -> usize {
+  TERMS
+    .iter()
+    .position(|&term| term == name)
+    .expect("Unknown evaluation term")
+}
+
+// 3 on the four central squares, 0 on the edge
+fn centrality(coord: Coordinate) -> i32 {
+  let distance = |x: u8| if x < 4 { 3 - x } else { x - 4 };
+
+  3 - distance(coord.row).max(distance(coord.col)) as i32
+}
+
+/// Feature counts of the position, white minus black
+pub fn features(board: &Board) -> Features {
+  let mut features = [0; TERMS.len()];
+
+  let pawns = |color: Color| -> Vec<Coordinate> {
+    board
+      .pieces
+      .iter()
+      .filter(|(_, piece)| piece.breed == Pieces::Pawn && piece.color == color)
+      .map(|(coord, _)| *coord)
+      .collect()
+  };
+  let white_pawns = pawns(Color::White);
+  let black_pawns = pawns(Color::Black);
+
+  for (coord, piece) in board.pieces.iter() {
+    let sign = if piece.color == Color::White { 1 } else { -1 };
+    let (own_pawns, enemy_pawns) = if piece.color == Color::White {
+      (&white_pawns, &black_pawns)
+    } else {
+      (&black_pawns, &white_pawns)
+    };
+
+    // Rows count towards the enemy: 0 is the own back rank
+    let rank = |coord: &Coordinate| {
+      if piece.color == Color::White {
+        7 - coord.row as i32
+      } else {
+        coord.row as i32
+      }
+    };
+
+    if let Some(index) = breed_term(piece.breed) {
+      features[index] += sign;
+    }
+
+    let mobility = || {
+      board
+        .generate_pseudo_legal_moves(*coord)
+        .map_or(0, |moves| moves.len() as i32)
+    };
+
+    match piece.breed {
+      Pieces::Pawn => {
+        features[term("pawn_advance")] += sign * (rank(coord) - 1);
+
+        if !enemy_pawns
+          .iter()
+          .any(|other| (other.col as i8 - coord.col as i8).abs() <= 1 && rank(other) > rank(coord))
+        {
+          features[term("passed_pawn")] += sign;
+        }
+
+        if own_pawns
+          .iter()
+          .any(|other| other.col == coord.col && rank(other) > rank(coord))
+        {
+          features[term("doubled_pawn")] += sign;
+        }
+
+        if !own_pawns
+          .iter()
+          .any(|other| (other.col as i8 - coord.col as i8).abs() == 1)
+        {
+          features[term("isolated_pawn")] += sign;
+        }
+      },
+      Pieces::Knight => {
+        features[term("knight_center")] += sign * centrality(*coord);
+        features[term("knight_mobility")] += sign * mobility();
+      },
+      Pieces::Bishop => {
+        features[term("bishop_center")] += sign * centrality(*coord);
+        features[term("bishop_mobility")] += sign * mobility();
+      },
+      Pieces::Rook => {
+        features[term("rook_mobility")] += sign * mobility();
+
+        if !own_pawns.iter().any(|other| other.col == coord.col) {
+          features[term("rook_open_file")] += sign;
+        }
+      },
+      Pieces::Queen => {
+        features[term("queen_mobility")] += sign * mobility();
+      },
+      Pieces::King => {
+        let shelter = own_pawns
+          .iter()
+          .filter(|other| {
+            (other.col as i8 - coord.col as i8).abs() <= 1 && rank(other) == rank(coord) + 1
+          })
+          .count() as i32;
+
+        features[term("king_shelter")] += sign * shelter;
+      }
+    }
+  }
+
+  for (color, sign) in [(Color::White, 1), (Color::Black, -1)] {
+    let bishops = board
+      .pieces
+      .values()
+      .filter(|piece| piece.breed == Pieces::Bishop && piece.color == color)
+      .count();
+
+    if bishops >= 2 {
+      features[term("bishop_pair")] += sign;
+    }
+  }
+
+  features
+}
+
+/// Static evaluation, either by a neural network or by the handcrafted terms
+pub enum Evaluator {
+  Handcrafted(EvalParams),
+  Nnue(Network)
+}
+
+impl Default for Evaluator {
+  fn default() -> Self { Evaluator::Handcrafted(EvalParams::default()) }
+}
+
+impl Evaluator {
+  /// Loads a network when a path is given, otherwise uses the handcrafted evaluation
+  pub fn load(network: Option<&Path>) -> io::Result<Self> {
+    match network {
+      Some(path) => Ok(Evaluator::Nnue(Network::load(path)?)),
+      None => Ok(Evaluator::default())
+    }
+  }
+
+  /// Evaluation in centipawns from the side to move's point of view
+  pub fn evaluate(
+    &self,
+    board: &Board
+  ) -> i32 {
+    match self {
+      Evaluator::Handcrafted(params) => params.evaluate(board),
+      Evaluator::Nnue(network) => network.evaluate(&Accumulator::new(network, board), board.turn)
+    }
+  }
+}
+
+/// Evaluator bound to a board that follows it through make and unmake,
+/// so the network accumulators are updated incrementally
+pub struct EvalState<'a> {
+  evaluator:    &'a Evaluator,
+  accumulators: Vec<Accumulator>
+}
+
+impl<'a> EvalState<'a> {
+  pub fn new(
+    evaluator: &'a Evaluator,
+    board: &Board
+  ) -> Self {
+    let accumulators = match evaluator {
+      Evaluator::Nnue(network) => vec![Accumulator::new(network, board)],
+      Evaluator::Handcrafted(_) => Vec::new()
+    };
+
+    EvalState {
+      evaluator,
+      accumulators
+    }
+  }
+
+  pub fn make_move(
+    &mut self,
+    board: &mut Board,
+    mv: Move
+  ) -> Result<Undo, String> {
+    let undo = board.make_move(mv)?;
+
+    if let Evaluator::Nnue(network) = self.evaluator {
+      let mut accumulator = self
+        .accumulators
+        .last()
+        .expect("Accumulator stack is never empty")
+        .clone();
+      accumulator.update(network, board, &undo.changes(board));
+      self.accumulators.push(accumulator);
+    }
+
+    Ok(undo)
+  }
+
+  pub fn unmake_move(
+    &mut self,
+    board: &mut Board,
+    undo: Undo
+  ) {
+    board.unmake_move(undo);
+
+    if let Evaluator::Nnue(_) = self.evaluator {
+      self.accumulators.pop();
+    }
+  }
+
+  pub fn evaluate(
+    &self,
+    board: &Board
+  ) -> i32 {
+    match self.evaluator {
+      Evaluator::Handcrafted(params) => params.evaluate(board),
+      Evaluator::Nnue(network) => network.evaluate(
+        self
+          .accumulators
+          .last()
+          .expect("Accumulator stack is never empty"),
+        board.turn
+      )
+    }
+  }
+}
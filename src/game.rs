@@ -0,0 +1,397 @@
+use core::fmt;
+
+use crate::pgn;
+use crate::{Board, Color, Move, Pieces, Undo};
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum GameResult {
+  WhiteWins,
+  BlackWins,
+  Draw
+}
+
+impl GameResult {
+  pub fn win(color: Color) -> Self {
+    match color {
+      Color::White => GameResult::WhiteWins,
+      Color::Black => GameResult::BlackWins
+    }
+  }
+}
+
+impl fmt::Display for GameResult {
+  fn fmt(
+    &self,
+    f: &mut fmt::Formatter<'_>
+  ) -> fmt::Result {
+    write!(
+      f,
+      "{}",
+      match self {
+        GameResult::WhiteWins => "1-0",
+        GameResult::BlackWins => "0-1",
+        GameResult::Draw => "1/2-1/2"
+      }
+    )
+  }
+}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum Outcome {
+  Checkmate(Color),
+  Stalemate,
+  FiftyMoves,
+  Repetition,
+  InsufficientMaterial,
+  // Decided outside of the rules: time forfeit, resignation, adjudication
+  Decision(GameResult, String)
+}
+
+impl Outcome {
+  pub fn result(&self) -> GameResult {
+    match self {
+      Outcome::Checkmate(winner) => GameResult::win(*winner),
+      Outcome::Decision(result, _) => *result,
+      _ => GameResult::Draw
+    }
+  }
+}
+
+impl fmt::Display for Outcome {
+  fn fmt(
+    &self,
+    f: &mut fmt::Formatter<'_>
+  ) -> fmt::Result {
+    match self {
+      Outcome::Checkmate(Color::White) => write!(f, "White mates"),
+      Outcome::Checkmate(Color::Black) => write!(f, "Black mates"),
+      Outcome::Stalemate => write!(f, "Draw by stalemate"),
+      Outcome::FiftyMoves => write!(f, "Draw by fifty move rule"),
+      Outcome::Repetition => write!(f, "Draw by repetition"),
+      Outcome::InsufficientMaterial => write!(f, "Draw by insufficient material"),
+      Outcome::Decision(_, reason) => write!(f, "{}", reason)
+    }
+  }
+}
+
+impl Board {
+  /// Neither side has enough material left to mate
+  pub fn is_insufficient_material(&self) -> bool {
+    let mut minors = Vec::new();
+
+    for (coord, piece) in self.pieces.iter() {
+      match piece.breed {
+        Pieces::King => {},
+        Pieces::Bishop | Pieces::Knight => minors.push((*coord, *piece)),
+        _ => return false
+      }
+    }
+
+    match minors.as_slice() {
+      [] | [_] => true,
+      // Bishops only, all on squares of the same color
+      _ => {
+        minors
+          .iter()
+          .all(|(_, piece)| piece.breed == Pieces::Bishop)
+          && minors.iter().all(|(coord, _)| {
+            (coord.row + coord.col) % 2 == (minors[0].0.row + minors[0].0.col) % 2
+          })
+      },
+    }
+  }
+}
+
+/// A game from a starting position, with the moves played so far
+#[derive(Clone)]
+pub struct Game {
+  start:    Board,
+  board:    Board,
+  moves:    Vec<Move>,
+  undos:    Vec<Undo>,
+  comments: Vec<Option<String>>,
+  // Position keys after every ply, the starting position included
+  keys:     Vec<u64>,
+  decision: Option<Outcome>
+}
+
+impl Default for Game {
+  fn default() -> Self { Game::new(Board::default()) }
+}
+
+impl Game {
+  pub fn new(start: Board) -> Self {
+    Game {
+      keys: vec![start.key()],
+      board: start.clone(),
+      start,
+      moves: Vec::new(),
+      undos: Vec::new(),
+      comments: Vec::new(),
+      decision: None
+    }
+  }
+
+  pub fn from_fen(fen: &str) -> Result<Self, String> {
+    let mut board = Board::new();
+    board.load_fen(fen).map_err(String::from)?;
+    Ok(Game::new(board))
+  }
+
+  pub fn start(&self) -> &Board { &self.start }
+
+  pub fn board(&self) -> &Board { &self.board }
+
+  pub fn moves(&self) -> &[Move] { &self.moves }
+
+  pub fn comments(&self) -> &[Option<String>] { &self.comments }
+
+  /// Position after the first `ply` moves
+  pub fn board_at(
+    &self,
+    ply: usize
+  ) -> Board {
+    let mut board = self.start.clone();
+    for &mv in self.moves.iter().take(ply) {
+      board.apply_move(mv).expect("Moves are legal");
+    }
+    board
+  }
+
+  /// Plays a legal move
+  pub fn play(
+    &mut self,
+    mv: Move
+  ) -> Result<(), String> {
+    if self.outcome().is_some() {
+      return Err(String::from("The game is over"))
+    }
+
+    // A pawn reaching the last rank becomes a queen unless told otherwise
+    let mv = self.board.normalize(mv);
+    if !self.board.legal_moves().contains(&mv) {
+      return Err(format!("Illegal move {}", mv))
+    }
+
+    self.undos.push(self.board.make_move(mv)?);
+    self.moves.push(mv);
+    self.comments.push(None);
+    self.keys.push(self.board.key());
+
+    Ok(())
+  }
+
+  /// Takes the last move back
+  pub fn undo(&mut self) -> Option<Move> {
+    let undo = self.undos.pop()?;
+    self.board.unmake_move(undo);
+    self.comments.pop();
+    self.keys.pop();
+    self.decision = None;
+    self.moves.pop()
+  }
+
+  /// Attaches a comment to the last move
+  pub fn comment(
+    &mut self,
+    text: String
+  ) {
+    if let Some(comment) = self.comments.last_mut() {
+      *comment = Some(text);
+    }
+  }
+
+  /// Ends the game regardless of the position
+  pub fn decide(
+    &mut self,
+    result: GameResult,
+    reason: String
+  ) {
+    self.decision = Some(Outcome::Decision(result, reason));
+  }
+
+  // How many times the current position has occurred
+  pub fn repetitions(&self) -> usize {
+    let current = self.keys.last().expect("Keys are never empty");
+
+    // Only positions since the last pawn move or capture can repeat
+    self
+      .keys
+      .iter()
+      .rev()
+      .take(self.board.halfmove_clock as usize + 1)
+      .filter(|&key| key == current)
+      .count()
+  }
+
+  // Keys of the positions since the last irreversible move, for repetition checks
+  pub fn history(&self) -> &[u64] {
+    let len = self.keys.len();
+    &self.keys[len - (self.board.halfmove_clock as usize + 1).min(len)..]
+  }
+
+  /// How the game ended, if it did. Threefold repetition and fifty moves
+  /// only end it when claimed, fivefold repetition and 75 moves always do
+  pub fn outcome(&self) -> Option<Outcome> {
+    if let Some(decision) = &self.decision {
+      return Some(decision.clone())
+    }
+
+    let turn = self.board.turn;
+
+    if self.board.is_in_checkmate(turn) {
+      Some(Outcome::Checkmate(turn.opposite()))
+    } else if self.board.is_in_stalemate(turn) {
+      Some(Outcome::Stalemate)
+    } else if self.board.is_insufficient_material() {
+      Some(Outcome::InsufficientMaterial)
+    } else if self.board.halfmove_clock >= 150 {
+      Some(Outcome::FiftyMoves)
+    } else if self.repetitions() >= 5 {
+      Some(Outcome::Repetition)
+    } else {
+      None
+    }
+  }
+
+  /// Draw the side to move may claim: the position occurred three times, or
+  /// fifty moves went by without a pawn move or a capture
+  pub fn claimable_draw(&self) -> Option<Outcome> {
+    if self.outcome().is_some() {
+      None
+    } else if self.repetitions() >= 3 {
+      Some(Outcome::Repetition)
+    } else if self.board.halfmove_clock >= 100 {
+      Some(Outcome::FiftyMoves)
+    } else {
+      None
+    }
+  }
+
+  /// Ends the game with the draw that may be claimed
+  pub fn claim_draw(&mut self) -> Result<Outcome, String> {
+    let outcome = self.claimable_draw().ok_or("There is no draw to claim")?;
+    self.decision = Some(outcome.clone());
+    Ok(outcome)
+  }
+
+  /// Moves in standard algebraic notation
+  pub fn san_moves(&self) -> Vec<String> {
+    let mut board = self.start.clone();
+
+    self
+      .moves
+      .iter()
+      .map(|&mv| {
+        let san = board.to_san(mv);
+        board.apply_move(mv).expect("Moves are legal");
+        san
+      })
+      .collect()
+  }
+
+  /// Portable game notation, with the given tags before the seven tag roster ones that are missing
+  pub fn to_pgn(
+    &self,
+    tags: &[(&str, String)]
+  ) -> String {
+    self.to_annotated_pgn(tags, &[], &self.comments)
+  }
+
+  /// Portable game notation with numeric annotation glyphs, `$2` for `?`,
+  /// and comments after the moves instead of the game's own, both by ply
+  pub fn to_annotated_pgn(
+    &self,
+    tags: &[(&str, String)],
+    nags: &[Option<u8>],
+    comments: &[Option<String>]
+  ) -> String {
+    let result = match self.outcome() {
+      Some(outcome) => outcome.result().to_string(),
+      None => String::from("*")
+    };
+
+    let mut all_tags: Vec<(&str, String)> = Vec::new();
+    for (name, default) in [
+      ("Event", "?"),
+      ("Site", "?"),
+      ("Date", "????.??.??"),
+      ("Round", "?"),
+      ("White", "?"),
+      ("Black", "?")
+    ] {
+      let value = tags
+        .iter()
+        .find(|(tag, _)| *tag == name)
+        .map_or_else(|| String::from(default), |(_, value)| value.clone());
+      all_tags.push((name, value));
+    }
+    all_tags.push(("Result", result.clone()));
+
+    let start_fen = self.start.get_fen();
+    if start_fen != Board::default().get_fen() {
+      all_tags.push(("SetUp", String::from("1")));
+      all_tags.push(("FEN", start_fen));
+    }
+
+    for (name, value) in tags {
+      if !all_tags.iter().any(|(tag, _)| tag == name) {
+        all_tags.push((name, value.clone()));
+      }
+    }
+
+    let mut pgn = String::new();
+    for (name, value) in all_tags {
+      pgn.push_str(&format!(
+        "[{} \"{}\"]\n",
+        name,
+        value.replace('\\', "\\\\").replace('"', "\\\"")
+      ));
+    }
+    pgn.push('\n');
+
+    // Movetext, wrapped at 80 columns
+    let mut tokens = Vec::new();
+    let mut number = self.start.fullmove_number;
+    let mut turn = self.start.turn;
+
+    for (index, san) in self.san_moves().into_iter().enumerate() {
+      if turn == Color::White {
+        tokens.push(format!("{}.", number));
+      } else if index == 0 {
+        tokens.push(format!("{}...", number));
+      }
+      tokens.push(san);
+
+      if let Some(nag) = nags.get(index).copied().flatten() {
+        tokens.push(format!("${}", nag));
+      }
+      if let Some(Some(comment)) = comments.get(index) {
+        tokens.push(pgn::comment_token(comment));
+      }
+
+      if turn == Color::Black {
+        number += 1;
+      }
+      turn = turn.opposite();
+    }
+    tokens.push(result);
+
+    let mut line = String::new();
+    for token in tokens {
+      if !line.is_empty() && line.len() + 1 + token.len() > 80 {
+        pgn.push_str(&line);
+        pgn.push('\n');
+        line.clear();
+      }
+      if !line.is_empty() {
+        line.push(' ');
+      }
+      line.push_str(&token);
+    }
+    pgn.push_str(&line);
+    pgn.push('\n');
+
+    pgn
+  }
+}
@@ -1,15 +1,19 @@
 use core::fmt;
+use std::collections::hash_map::DefaultHasher;
 use std::convert::{TryFrom, TryInto};
+use std::hash::{Hash, Hasher};
 use std::{collections::HashMap, str::FromStr};
 
-use tui::style::Style;
 use tui::widgets::Widget;
 
 // Sequence: King, Queen, Rook, Bishop, Knight, Pawn
-// NOTE: Might be changable in the future, via a command line argument
+// The default glyphs, other sets are in `theme::GlyphSet`
 pub(crate) const BLACK_PIECES: [char; 6] = ['♔', '♕', '♖', '♗', '♘', '♙'];
 pub(crate) const WHITE_PIECES: [char; 6] = ['♚', '♛', '♜', '♝', '♞', '♟'];
 
+/// Pieces a pawn can become, the strongest first
+pub const PROMOTIONS: [Pieces; 4] = [Pieces::Queen, Pieces::Rook, Pieces::Bishop, Pieces::Knight];
+
 macro_rules! coord {
   ($x:expr, $y:expr) => {
     Coordinate {
@@ -18,6 +22,32 @@ macro_rules! coord {
   };
 }
 
+pub mod app;
+mod attacks;
+pub mod clock;
+pub mod coach;
+pub mod config;
+pub mod describe;
+pub mod eval;
+pub mod game;
+pub mod nnue;
+pub mod notation;
+pub mod pgn;
+pub mod render;
+pub mod report;
+pub mod search;
+mod see;
+pub mod selfplay;
+pub mod setup;
+pub mod skill;
+pub mod tablebase;
+pub mod terminal;
+pub mod theme;
+pub mod tuning;
+pub mod uci;
+pub mod view;
+pub mod widget;
+
 // Coordinate struct
 #[derive(Hash, Clone, Copy, PartialEq, Eq, Debug)]
 pub struct Coordinate {
@@ -122,13 +152,42 @@ impl Coordinate {
   pub fn to_notation(&self) -> String { self.to_string() }
 }
 
-#[derive(Hash, Clone, Copy, PartialEq, Eq)]
+#[derive(Hash, Clone, Copy, PartialEq, Eq, Debug)]
 pub enum Color {
   Black = 0,
   White = 1
 }
 
-#[derive(Hash, Clone, Copy, PartialEq, Eq)]
+impl Color {
+  pub fn opposite(&self) -> Color {
+    match self {
+      Color::White => Color::Black,
+      Color::Black => Color::White
+    }
+  }
+
+  /// `White` or `Black`
+  pub fn name(&self) -> &'static str {
+    match self {
+      Color::White => "White",
+      Color::Black => "Black"
+    }
+  }
+}
+
+impl FromStr for Color {
+  type Err = String;
+
+  fn from_str(text: &str) -> Result<Self, Self::Err> {
+    match text.trim().to_lowercase().as_str() {
+      "white" | "w" => Ok(Color::White),
+      "black" | "b" => Ok(Color::Black),
+      _ => Err(format!("Unknown side `{}`, expected white or black", text))
+    }
+  }
+}
+
+#[derive(Hash, Clone, Copy, PartialEq, Eq, Debug)]
 pub enum Pieces {
   King,
   Queen,
@@ -157,6 +216,18 @@ impl std::ops::Index<Pieces> for [char; 6] {
 }
 
 impl Pieces {
+  // Material value in centipawns
+  pub fn value(&self) -> i32 {
+    match self {
+      Pieces::King => 20000,
+      Pieces::Queen => 900,
+      Pieces::Rook => 500,
+      Pieces::Bishop => 330,
+      Pieces::Knight => 320,
+      Pieces::Pawn => 100
+    }
+  }
+
   pub fn to_unicode(
     &self,
     color: Color
@@ -169,12 +240,24 @@ impl Pieces {
   }
 }
 
-#[derive(Hash, Clone, Copy)]
+#[derive(Hash, Clone, Copy, PartialEq, Eq, Debug)]
 pub struct Piece {
   breed: Pieces,
   color: Color
 }
 
+impl Piece {
+  pub fn new(
+    color: Color,
+    breed: Pieces
+  ) -> Self {
+    Piece {
+      breed,
+      color
+    }
+  }
+}
+
 impl fmt::Display for Piece {
   fn fmt(
     &self,
@@ -184,6 +267,83 @@ impl fmt::Display for Piece {
   }
 }
 
+#[derive(Hash, Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Move {
+  pub start:     Coordinate,
+  pub target:    Coordinate,
+  // Piece a pawn becomes on the last rank, a queen when left out
+  pub promotion: Option<Pieces>
+}
+
+impl Move {
+  pub fn new(
+    start: Coordinate,
+    target: Coordinate
+  ) -> Self {
+    Move {
+      start,
+      target,
+      promotion: None
+    }
+  }
+
+  /// Pawn move to the last rank, becoming the given piece
+  pub fn promoting(
+    start: Coordinate,
+    target: Coordinate,
+    breed: Pieces
+  ) -> Self {
+    Move {
+      start,
+      target,
+      promotion: Some(breed)
+    }
+  }
+}
+
+impl fmt::Display for Move {
+  fn fmt(
+    &self,
+    f: &mut fmt::Formatter<'_>
+  ) -> fmt::Result {
+    // Long algebraic notation: e2e4, e7e8n
+    write!(f, "{}{}", self.start, self.target)?;
+    match self.promotion {
+      Some(breed) => write!(f, "{}", ['k', 'q', 'r', 'b', 'n', 'p'][breed]),
+      None => Ok(())
+    }
+  }
+}
+
+// Everything needed to take a move back
+#[derive(Clone)]
+pub struct Undo {
+  mv:                   Move,
+  squares:              Vec<(Coordinate, Option<Piece>)>,
+  turn:                 Color,
+  en_passant_target_sq: Option<Coordinate>,
+  castling:             [[bool; 2]; 2],
+  halfmove_clock:       u16,
+  fullmove_number:      u16
+}
+
+impl Undo {
+  pub fn mv(&self) -> Move { self.mv }
+
+  // Squares touched by the move as (square, piece before, piece after)
+  pub fn changes(
+    &self,
+    board: &Board
+  ) -> Vec<(Coordinate, Option<Piece>, Option<Piece>)> {
+    self
+      .squares
+      .iter()
+      .map(|&(coord, before)| (coord, before, board.get_piece(&coord)))
+      .filter(|(_, before, after)| before != after)
+      .collect()
+  }
+}
+
 #[derive(Hash, Eq, PartialEq, Copy, Clone)]
 enum CastlingSides {
   KingSide = 0,
@@ -201,7 +361,10 @@ pub struct Board {
 }
 
 impl Default for Board {
-  fn default() -> Self { Self::default() }
+  fn default() -> Self {
+    Board::from_fen("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1")
+      .expect("Couldn't create default board")
+  }
 }
 
 impl Widget for Board {
@@ -210,28 +373,7 @@ impl Widget for Board {
     area: tui::layout::Rect,
     buf: &mut tui::buffer::Buffer
   ) {
-    for row in 0..8 {
-      for col in 0..8 {
-        // Determine what is background color for given coordinate
-        let color: tui::style::Color = if (row + col) % 2 == 0 {
-          tui::style::Color::Rgb(161, 189, 203) // Light
-        } else {
-          tui::style::Color::Rgb(254, 255, 255) // Dark
-        };
-
-        // Draw piece if there is one
-        let piece_as_str = match self.pieces.get(&coord!(row as u8, col as u8)) {
-          Some(piece) => piece.to_string(),
-          None => " ".to_string()
-        };
-        buf.set_string(
-          area.x + col,
-          area.y + row,
-          piece_as_str,
-          Style::default().fg(tui::style::Color::Black).bg(color)
-        );
-      }
-    }
+    view::BoardView::new(&self).render(area, buf)
   }
 }
 
@@ -240,28 +382,11 @@ impl fmt::Display for Board {
     &self,
     f: &mut fmt::Formatter<'_>
   ) -> fmt::Result {
-    for row in 0..8 {
-      for col in 0..8 {
-        let coord = coord!(row, col);
-        if let Some(piece) = self.pieces.get(&coord) {
-          write!(f, "{}", piece)?;
-        } else {
-          write!(f, ".")?;
-        }
-      }
-      writeln!(f)?;
-    }
-
-    Ok(())
+    write!(f, "{}", view::BoardView::new(self))
   }
 }
 
 impl Board {
-  pub fn default() -> Self {
-    return Board::from_fen("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1")
-      .expect("Couldn't create default board")
-  }
-
   pub fn load_fen(
     &mut self,
     fen: &str
@@ -278,7 +403,7 @@ impl Board {
       fullmove_number
     ) = match fen.split(' ').collect::<Vec<_>>().as_slice() {
       [pp, ac, ca, ep, hc, fulln] => (*pp, *ac, *ca, *ep, *hc, *fulln),
-      _ => panic!("The FEN string doesn't match the pattern")
+      _ => return Err("The FEN string doesn't match the pattern")
     };
 
     self.reset();
@@ -510,6 +635,22 @@ impl Board {
     old_piece
   }
 
+  /// Empties the square, returning the piece that was there
+  pub fn remove_piece(
+    &mut self,
+    coord: Coordinate
+  ) -> Option<Piece> {
+    self.pieces.remove(&coord)
+  }
+
+  /// Removes every piece, with the castling rights and en passant square
+  /// that went with them
+  pub fn clear(&mut self) {
+    self.pieces.clear();
+    self.castling = [[false; 2]; 2];
+    self.en_passant_target_sq = None;
+  }
+
   pub fn get_piece(
     &self,
     coordinate: &Coordinate
@@ -519,22 +660,27 @@ impl Board {
     result.copied()
   }
 
-  pub fn draw(&self) {
-    for row in 0..8 {
-      for col in 0..8 {
-        let piece = self.get_piece(&coord!(row, col));
+  pub fn get_turn(&self) -> Color { self.turn }
 
-        match piece {
-          Some(piece) => {
-            print!("{} ", piece.breed.to_unicode(piece.color));
-          },
-          None => print!(". ")
-        }
-      }
-      println!();
+  // Identifies the position for repetition checks, ignoring the clocks
+  pub fn key(&self) -> u64 {
+    let mut hasher = DefaultHasher::new();
+
+    for index in 0..64 {
+      self
+        .pieces
+        .get(&Coordinate::try_from(index).unwrap())
+        .hash(&mut hasher);
     }
+    self.turn.hash(&mut hasher);
+    self.castling.hash(&mut hasher);
+    self.en_passant_target_sq.hash(&mut hasher);
+
+    hasher.finish()
   }
 
+  pub fn draw(&self) { view::BoardView::new(self).draw() }
+
   fn generate_pseudo_legal_moves(
     &self,
     coordinate: Coordinate
@@ -578,6 +724,32 @@ impl Board {
             }
           }
         }
+
+        // Castling: the king can't leave, cross or land on an attacked square
+        let home_row = if piece.color == Color::White { 7 } else { 0 };
+
+        if coordinate == coord!(home_row, 4)
+          && !self.is_attacked(coordinate, piece.color.opposite())
+        {
+          for (side, rook_col, empty_cols, king_path) in [
+            (CastlingSides::KingSide, 7, [5, 6].as_slice(), [5, 6]),
+            (CastlingSides::QueenSide, 0, [1, 2, 3].as_slice(), [3, 2])
+          ] {
+            let rook = self.get_piece(&coord!(home_row, rook_col));
+
+            if self.castling[piece.color as usize][side as usize]
+              && rook.is_some_and(|rook| rook.breed == Rook && rook.color == piece.color)
+              && empty_cols
+                .iter()
+                .all(|&col| self.get_piece(&coord!(home_row, col)).is_none())
+              && king_path
+                .iter()
+                .all(|&col| !self.is_attacked(coord!(home_row, col), piece.color.opposite()))
+            {
+              moves.push(coord!(home_row, king_path[1]));
+            }
+          }
+        }
       },
       Queen => {
         /*
@@ -698,7 +870,7 @@ impl Board {
         }
       },
       Knight => {
-        let deltas = vec![-17, -15, -10, -6, 6, 10, 15, 17];
+        let deltas = [-17, -15, -10, -6, 6, 10, 15, 17];
 
         for delta in deltas.iter() {
           let new_numeric = numeric as i8 + delta;
@@ -726,6 +898,11 @@ impl Board {
       Pawn => {
         let increment = if piece.color == Color::White { -1 } else { 1 };
         let starting_row = if piece.color == Color::White { 6 } else { 1 };
+        // Only the opponent's double step can be taken en passant
+        let en_passant_row = if piece.color == Color::White { 2 } else { 5 };
+        let en_passant = self
+          .en_passant_target_sq
+          .filter(|coord| coord.row == en_passant_row);
 
         let new_coord = coord!((coordinate.row as i8 + increment) as u8, coordinate.col);
 
@@ -764,7 +941,7 @@ impl Board {
               moves.push(move1);
             }
 
-            if self.en_passant_target_sq.is_some() && self.en_passant_target_sq.unwrap() == move1 {
+            if en_passant == Some(move1) {
               moves.push(move1);
             }
           }
@@ -780,7 +957,7 @@ impl Board {
               moves.push(move2);
             }
 
-            if self.en_passant_target_sq.is_some() && self.en_passant_target_sq.unwrap() == move2 {
+            if en_passant == Some(move2) {
               moves.push(move2);
             }
           }
@@ -791,11 +968,30 @@ impl Board {
     Ok(moves)
   }
 
+  /// Moves a piece without checking the move is legal, a pawn reaching the
+  /// last rank becomes a queen
   pub fn move_piece(
     &mut self,
     start: Coordinate,
     target: Coordinate
   ) -> Result<Option<Piece>, String> {
+    self.apply_move(Move::new(start, target))
+  }
+
+  /// Same as `move_piece`, promoting to the piece the move names
+  pub fn apply_move(
+    &mut self,
+    mv: Move
+  ) -> Result<Option<Piece>, String> {
+    let (start, target) = (mv.start, mv.target);
+    let promotion = mv.promotion.unwrap_or(Pieces::Queen);
+    if matches!(promotion, Pieces::King | Pieces::Pawn) {
+      return Err(format!(
+        "A pawn can't become a {}",
+        describe::piece_name(promotion)
+      ))
+    }
+
     let start_piece = self
       .get_piece(&start)
       .expect("No piece at start coordinate");
@@ -813,15 +1009,36 @@ impl Board {
     // Remove start piece from hashmap
     self.pieces.remove(&start);
 
+    // En passant removes the pawn that has passed the target square
+    let old_piece = if start_piece.breed == Pieces::Pawn
+      && old_piece.is_none()
+      && start.col != target.col
+      && self.en_passant_target_sq == Some(target)
+    {
+      self.pieces.remove(&coord!(start.row, target.col))
+    } else {
+      old_piece
+    };
+
+    // Castling also moves the rook
+    if start_piece.breed == Pieces::King && (start.col as i8 - target.col as i8).abs() == 2 {
+      let (rook_start, rook_target) = if target.col == 6 { (7, 5) } else { (0, 3) };
+
+      if let Some(rook) = self.pieces.remove(&coord!(start.row, rook_start)) {
+        self.pieces.insert(coord!(start.row, rook_target), rook);
+      }
+    }
+
     // Update en passant target square
     if
     /* piece is a pawn */
     start_piece.breed == Pieces::Pawn {
       if (start.row as i8 - target.row as i8).abs() == 2 {
+        // The square passed, behind the pawn
         let increment = if start_piece.color == Color::White {
-          -1
-        } else {
           1
+        } else {
+          -1
         };
         self.en_passant_target_sq = Some(coord!((target.row as i8 + increment) as u8, target.col));
       } else {
@@ -864,6 +1081,17 @@ impl Board {
       }
     }
 
+    // Capturing a rook in its corner
+    if target == coord!(7, 0) {
+      set_castling!(White, QueenSide) = false;
+    } else if target == coord!(7, 7) {
+      set_castling!(White, KingSide) = false;
+    } else if target == coord!(0, 0) {
+      set_castling!(Black, QueenSide) = false;
+    } else if target == coord!(0, 7) {
+      set_castling!(Black, KingSide) = false;
+    }
+
     // Promotion
     if start_piece.breed == Pieces::Pawn {
       let promotion_row = if start_piece.color == Color::White {
@@ -873,16 +1101,20 @@ impl Board {
       };
 
       if target.row == promotion_row {
-        let queen = Piece {
+        let promoted = Piece {
           color: start_piece.color,
-          breed: Pieces::Queen
+          breed: promotion
         };
-        self.place_piece(queen, target);
+        self.place_piece(promoted, target);
       }
     }
 
-    // Incrementing clocks
-    self.halfmove_clock += 1;
+    // Incrementing clocks, pawn moves and captures reset the halfmove clock
+    if start_piece.breed == Pieces::Pawn || old_piece.is_some() {
+      self.halfmove_clock = 0;
+    } else {
+      self.halfmove_clock += 1;
+    }
     if self.turn == Color::Black {
       self.fullmove_number += 1;
     }
@@ -897,6 +1129,66 @@ impl Board {
     Ok(old_piece)
   }
 
+  pub fn make_move(
+    &mut self,
+    mv: Move
+  ) -> Result<Undo, String> {
+    if self.get_piece(&mv.start).is_none() {
+      return Err(String::from("No piece at start coordinate"))
+    }
+
+    // En passant and castling touch squares besides start and target, each
+    // square is recorded once so the changes aren't counted twice
+    let mut squares = vec![mv.start, mv.target];
+    let extra = match self.get_piece(&mv.start).map(|piece| piece.breed) {
+      Some(Pieces::Pawn) if mv.start.col != mv.target.col => {
+        vec![coord!(mv.start.row, mv.target.col)]
+      },
+      Some(Pieces::King) => [0, 3, 5, 7].map(|col| coord!(mv.start.row, col)).to_vec(),
+      _ => Vec::new()
+    };
+    for coord in extra {
+      if !squares.contains(&coord) {
+        squares.push(coord);
+      }
+    }
+
+    let undo = Undo {
+      mv,
+      squares: squares
+        .iter()
+        .map(|&coord| (coord, self.get_piece(&coord)))
+        .collect(),
+      turn: self.turn,
+      en_passant_target_sq: self.en_passant_target_sq,
+      castling: self.castling,
+      halfmove_clock: self.halfmove_clock,
+      fullmove_number: self.fullmove_number
+    };
+
+    self.apply_move(mv)?;
+
+    Ok(undo)
+  }
+
+  pub fn unmake_move(
+    &mut self,
+    undo: Undo
+  ) {
+    for (coord, piece) in undo.squares {
+      match piece {
+        Some(piece) => self.pieces.insert(coord, piece),
+        None => self.pieces.remove(&coord)
+      };
+    }
+
+    self.turn = undo.turn;
+    self.en_passant_target_sq = undo.en_passant_target_sq;
+    self.castling = undo.castling;
+    self.halfmove_clock = undo.halfmove_clock;
+    self.fullmove_number = undo.fullmove_number;
+  }
+
   pub fn generate_moves(
     &self,
     coord: Coordinate
@@ -935,49 +1227,73 @@ impl Board {
     &self,
     color: Color
   ) -> bool {
-    let king_coord = self.get_king_coord(color);
-
-    if king_coord.is_none() {
+    match self.get_king_coord(color) {
+      Some(king_coord) => self.is_attacked(king_coord, color.opposite()),
       // No king - no check
-      return false
+      None => false
     }
+  }
 
-    for (coord, piece) in self.pieces.iter() {
-      if piece.color != color && piece.breed != Pieces::King {
-        let moves = self
-          .generate_pseudo_legal_moves(*coord)
-          .expect("Couldn't generate moves to check if the King is in check");
-
-        if moves.contains(&king_coord.unwrap()) {
-          return true
-        }
-      }
-    }
+  pub fn is_in_checkmate(
+    &self,
+    color: Color
+  ) -> bool {
+    self.is_in_check(color) && !self.has_moves(color)
+  }
 
-    false
+  pub fn is_in_stalemate(
+    &self,
+    color: Color
+  ) -> bool {
+    self.get_king_coord(color).is_some() && !self.is_in_check(color) && !self.has_moves(color)
   }
 
-  pub fn is_in_checkmate(
+  fn has_moves(
     &self,
     color: Color
   ) -> bool {
-    if self.get_king_coord(color).is_none() {
-      return false
-    }
+    self.pieces.iter().any(|(coord, piece)| {
+      piece.color == color
+        && !self
+          .generate_moves(*coord)
+          .expect("Couldn't generate moves")
+          .is_empty()
+    })
+  }
+
+  // All legal moves of the side to move, a pawn reaching the last rank has
+  // one move for each piece it can become
+  pub fn legal_moves(&self) -> Vec<Move> {
+    let mut moves = Vec::new();
 
     for (coord, piece) in self.pieces.iter() {
-      if piece.color == color && piece.breed != Pieces::King {
-        let moves = self
+      if piece.color == self.turn {
+        for target in self
           .generate_moves(*coord)
-          .expect("Couldn't geenrate moves");
-
-        if !moves.is_empty() {
-          return false
+          .expect("Couldn't generate moves")
+        {
+          if piece.breed == Pieces::Pawn && (target.row == 0 || target.row == 7) {
+            for breed in PROMOTIONS {
+              moves.push(Move::promoting(*coord, target, breed));
+            }
+          } else {
+            moves.push(Move::new(*coord, target));
+          }
         }
       }
     }
 
-    true
+    // HashMap order isn't stable, keep the list reproducible
+    moves.sort_by_key(|mv| {
+      (
+        mv.start.row,
+        mv.start.col,
+        mv.target.row,
+        mv.target.col,
+        mv.promotion.map(|breed| -breed.value())
+      )
+    });
+    moves
   }
 }
 
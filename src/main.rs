@@ -1,47 +1,373 @@
-use std::{io};
-
-use chyes::Board;
-use crossterm::{
-  event::{self, DisableMouseCapture, EnableMouseCapture, Event, KeyCode},
-  execute,
-  terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen}
-};
-use tui::{
-  backend::CrosstermBackend,
-  layout::{Margin, Rect},
-  widgets::{Block, Borders},
-  Terminal
-};
+use std::io::{self, BufRead, Write};
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use std::{fs, process};
+
+use chyes::app::App;
+use chyes::clock::TimeControl;
+use chyes::config::{self, Config, Mode};
+use chyes::eval::{EvalParams, Evaluator};
+use chyes::game::Game;
+use chyes::pgn;
+use chyes::report;
+use chyes::skill::Difficulty;
+use chyes::terminal::{panicked, quit_signals, Session};
+use chyes::theme::{ColorDepth, Theme};
+use chyes::view::Orientation;
+use chyes::{Board, Color};
+use cli::Usage;
+use crossterm::event::{self, Event, KeyCode, KeyEvent, KeyModifiers};
+use tui::layout::Rect;
+
+mod cli;
+
+const USAGE: Usage = Usage(
+  "Usage: chyes [options]
+
+Starting position:
+  --fen <fen>             Start from this position
+  --pgn <file>            Load a game from a PGN file, or replay it
+  --game <n>              Which game of the PGN file (default: 1)
+  --report <file>         Go over the game with the engine, print how both sides played
+                          and write it annotated to the file, `-` for the output
+
+Play:
+  --mode <mode>           hvh, engine, analysis or replay (default: hvh)
+  --side <color>          Side you play, white or black (default: white)
+  --orientation <side>    white, black, side or auto (default: the side you play)
+  --time <control>        Clock, e.g. 5+3, 15d10 or 40/90+30,30+30 (default: none)
+
+Engine:
+  --level <spec>          1 to 8, or level=<n>,depth=<n>,time=<s>,nodes=<n>,skill=<n>
+  --lines <n>             Lines shown by the analysis (default: 3)
+  --network <file>        Neural network for the evaluation
+  --params <file>         Handcrafted evaluation weights
+
+Display:
+  --theme <name|file>     Board colors: default, wood, green or a theme file
+  --pieces <set>          filled, outline, ascii or 12 characters in KQRBNPkqrbnp order
+  --colors <depth>        truecolor, 256 or 16 (default: guessed from the terminal)
+  --accessible            Plain lines for screen readers instead of the board, moves
+                          and commands are typed and moves are announced in words
+
+Everything but the starting position can be put in the config file as
+`mode = \"engine\"` lines."
+);
+
+// A setting from the command line or the config file, parsed before the
+// terminal is taken over so errors are printed normally
+fn setting<T: FromStr<Err = String>>(value: &Option<String>) -> Option<T> {
+  value
+    .as_ref()
+    .map(|value| value.parse().unwrap_or_else(|err: String| USAGE.fail(&err)))
+}
+
+// Game to start with, from --fen or --pgn, with the tags of the PGN
+fn starting_game(
+  fen: Option<String>,
+  pgn: Option<String>,
+  number: Option<usize>
+) -> (Game, Vec<(String, String)>) {
+  match (fen, pgn) {
+    (Some(_), Some(_)) => USAGE.fail("Use either --fen or --pgn"),
+    (Some(fen), None) => {
+      if number.is_some() {
+        USAGE.fail("--game needs a --pgn file");
+      }
+      let board =
+        Board::from_fen(&fen).unwrap_or_else(|err| USAGE.fail(&format!("Invalid FEN: {}", err)));
+      board
+        .validate()
+        .unwrap_or_else(|err| USAGE.fail(&format!("Invalid position: {}", err)));
+      (Game::new(board), Vec::new())
+    },
+    (None, Some(path)) => {
+      let games = pgn::load(&path).unwrap_or_else(|err| USAGE.fail(&err));
+      let number = number.unwrap_or(1);
+      match games.into_iter().nth(number.wrapping_sub(1)) {
+        Some(game) => (game.game, game.tags),
+        None => USAGE.fail(&format!("{} has no game {}", path, number))
+      }
+    },
+    (None, None) => {
+      if number.is_some() {
+        USAGE.fail("--game needs a --pgn file");
+      }
+      (Game::default(), Vec::new())
+    }
+  }
+}
+
+// Asks the terminal to put the text on the clipboard (OSC 52), which also
+// works over ssh
+fn copy_to_clipboard(text: &str) -> io::Result<()> {
+  const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+  let mut encoded = String::new();
+  for chunk in text.as_bytes().chunks(3) {
+    let bytes = [
+      chunk[0],
+      *chunk.get(1).unwrap_or(&0),
+      *chunk.get(2).unwrap_or(&0)
+    ];
+    let bits = (bytes[0] as u32) << 16 | (bytes[1] as u32) << 8 | bytes[2] as u32;
+
+    for index in 0..4 {
+      if index <= chunk.len() {
+        encoded.push(ALPHABET[(bits >> (18 - 6 * index) & 63) as usize] as char);
+      } else {
+        encoded.push('=');
+      }
+    }
+  }
 
-fn main() -> Result<(), io::Error> {
-  // setup terminal
-  enable_raw_mode()?;
   let mut stdout = io::stdout();
-  execute!(stdout, EnterAlternateScreen, EnableMouseCapture)?;
-  let backend = CrosstermBackend::new(stdout);
-  let mut terminal = Terminal::new(backend)?;
-
-  loop {
-    terminal.draw(|f| {
-      let board = Board::default();
-      let size = f.size();
-      let block = Block::default().title("Chyes").borders(Borders::ALL);
-      f.render_widget(block, size);
-      f.render_widget(
-        board,
-        size.inner(&Margin {
-          horizontal: 10,
-          vertical:   10
-        })
-      );
-    })?;
+  write!(stdout, "\x1b]52;c;{}\x07", encoded)?;
+  stdout.flush()
+}
+
+// Plain lines on the standard streams instead of the board, for screen
+// readers. Lines are moves or commands of the command line, the moves played
+// are read out in words
+fn run_accessible(app: &mut App) -> io::Result<()> {
+  app.set_accessible(true);
+  println!(
+    "Accessible mode. Type moves like Nf3, describe, square e4, rank 4, file e, pieces, help or \
+     quit"
+  );
+  println!("{}", app.view().board().describe_position());
+
+  // Prints the moves played since the last call, the result once it's over
+  let speak = |app: &mut App| {
+    let spoken = app.take_announcements();
+    for words in &spoken {
+      println!("{}", words);
+    }
+    if !spoken.is_empty() && app.game().outcome().is_some() {
+      println!("{}", app.status());
+    }
+    spoken
+  };
+
+  let mut lines = io::stdin().lock().lines();
+  while !app.should_quit() {
+    // The computer's answer, or its first move when it plays white
+    app.tick();
+    app.wait_for_engine();
+    speak(app);
+
+    print!("> ");
+    io::stdout().flush()?;
+    let line = match lines.next() {
+      Some(line) => line?,
+      None => break
+    };
+    if line.trim().is_empty() {
+      continue
+    }
+
+    let reply = app.run_command(line.trim());
+    let spoken = speak(app);
+    match reply {
+      Ok(Some(message)) if !spoken.contains(&message) => println!("{}", message),
+      Ok(_) => {},
+      Err(err) => println!("{}", err)
+    }
+  }
+
+  Ok(())
+}
+
+// Prints how both sides played and writes the annotated game
+fn write_report(
+  game: &Game,
+  tags: &[(String, String)],
+  evaluator: &Evaluator,
+  path: &str
+) -> io::Result<()> {
+  let report = report::analyse(
+    game,
+    evaluator,
+    &report::limits(),
+    &AtomicBool::new(false),
+    |searched, total| eprint!("\rSearched {} of {} positions", searched, total)
+  );
+  eprintln!();
+
+  let mut tags: Vec<(&str, String)> = tags
+    .iter()
+    .map(|(name, value)| (name.as_str(), value.clone()))
+    .collect();
+  if !tags.iter().any(|(name, _)| *name == "Annotator") {
+    tags.push(("Annotator", String::from("chyes")));
+  }
+  let pgn = report.to_pgn(game, &tags);
+
+  if path == "-" {
+    print!("{}", pgn);
+  } else {
+    fs::write(path, pgn)?;
+  }
+  for color in [Color::White, Color::Black] {
+    eprintln!(
+      "{}, {:.0} centipawns lost a move",
+      report.summary(color),
+      report.average_loss(color).unwrap_or(0.0)
+    );
+  }
+  Ok(())
+}
+
+fn main() -> Result<(), io::Error> {
+  let mut config = Config::load().unwrap_or_else(|err| USAGE.fail(&err));
+  let mut fen = None;
+  let mut pgn = None;
+  let mut number = None;
+  let mut report_path = None;
+
+  let mut args = USAGE.args();
+  while let Some(arg) = args.next() {
+    match arg.as_str() {
+      "-h" | "--help" => {
+        USAGE.print();
+        if let Some(path) = config::path() {
+          println!("Config file: {}", path.display());
+        }
+        return Ok(())
+      },
+      "--fen" => fen = Some(args.value(&arg)),
+      "--pgn" => pgn = Some(args.value(&arg)),
+      "--game" => {
+        number = Some(
+          args
+            .value(&arg)
+            .parse()
+            .unwrap_or_else(|_| USAGE.fail("Invalid game number"))
+        )
+      },
+      "--report" => report_path = Some(args.value(&arg)),
+      "--mode" => config.mode = Some(args.value(&arg)),
+      "--side" => config.side = Some(args.value(&arg)),
+      "--orientation" => config.orientation = Some(args.value(&arg)),
+      "--time" => config.time = Some(args.value(&arg)),
+      "--level" => config.level = Some(args.value(&arg)),
+      "--lines" => config.lines = Some(args.value(&arg)),
+      "--network" => config.network = Some(args.value(&arg)),
+      "--params" => config.params = Some(args.value(&arg)),
+      "--theme" => config.theme = Some(args.value(&arg)),
+      "--pieces" => config.pieces = Some(args.value(&arg)),
+      "--colors" => config.colors = Some(args.value(&arg)),
+      "--accessible" => config.accessible = Some(String::from("true")),
+      _ => USAGE.fail(&format!("Unexpected argument {}", arg))
+    }
+  }
+
+  let mode: Mode = setting(&config.mode).unwrap_or_default();
+  if mode == Mode::Replay && pgn.is_none() {
+    USAGE.fail("Replay mode needs a --pgn file");
+  }
+  let (game, tags) = starting_game(fen, pgn.clone(), number);
+
+  let side = setting(&config.side).unwrap_or(Color::White);
+  let orientation = setting(&config.orientation).unwrap_or(match side {
+    Color::White => Orientation::White,
+    Color::Black => Orientation::Black
+  });
+  let control: Option<TimeControl> = setting(&config.time);
+  let difficulty: Option<Difficulty> = setting(&config.level);
+  let lines = config.lines.as_ref().map(|lines| {
+    lines
+      .parse::<usize>()
+      .ok()
+      .filter(|lines| (1..=10).contains(lines))
+      .unwrap_or_else(|| USAGE.fail("Analysis lines go from 1 to 10"))
+  });
+  let evaluator = match (&config.network, &config.params) {
+    (Some(_), Some(_)) => USAGE.fail("Use either a network or evaluation weights"),
+    (Some(network), None) => Evaluator::load(Some(network.as_ref()))
+      .unwrap_or_else(|err| USAGE.fail(&format!("{}: {}", network, err))),
+    (None, Some(params)) => Evaluator::Handcrafted(
+      EvalParams::load(params).unwrap_or_else(|err| USAGE.fail(&format!("{}: {}", params, err)))
+    ),
+    (None, None) => Evaluator::default()
+  };
+
+  if let Some(path) = report_path {
+    if game.moves().is_empty() {
+      USAGE.fail("--report needs a game with moves, from --pgn");
+    }
+    return write_report(&game, &tags, &evaluator, &path)
+  }
+
+  let depth = setting(&config.colors).unwrap_or_else(ColorDepth::detect);
+  let theme = match &config.theme {
+    Some(theme) => Theme::load(theme).unwrap_or_else(|err| USAGE.fail(&err)),
+    None => Theme::default()
+  };
+  let glyphs = setting(&config.pieces).unwrap_or_default();
+
+  let mut app = App::new(game);
+  app.set_theme(theme.with_depth(depth));
+  app.set_glyphs(glyphs);
+  app.set_orientation(orientation);
+  app.set_evaluator(Arc::new(evaluator));
+  if let Some(difficulty) = difficulty {
+    app.set_difficulty(difficulty);
+  }
+  if let Some(lines) = lines {
+    app.set_analysis_lines(lines);
+  }
+  app.set_clock(control);
+
+  match mode {
+    Mode::HumanVsHuman => {},
+    Mode::Engine => app.set_engine(Some(side.opposite())),
+    Mode::Analysis => app.set_analysis(true),
+    // The chosen game is shown from its first move, without --game the
+    // games of the file are offered
+    Mode::Replay => {
+      let path = pgn.expect("Replay mode has a PGN file");
+      let mut games = pgn::load(&path).unwrap_or_else(|err| USAGE.fail(&err));
+      match number {
+        Some(number) => app.replay(games.swap_remove(number - 1)),
+        None => app.open_pgn(games).unwrap_or_else(|err| USAGE.fail(&err))
+      }
+    }
+  }
+
+  if config.accessible.as_deref() == Some("true") {
+    return run_accessible(&mut app)
+  }
+
+  // The session restores the terminal when dropped, on errors and panics too
+  let signals = quit_signals()?;
+  let mut session = Session::start()?;
+  let terminal = session.terminal();
+
+  while !app.should_quit() && !signals.load(Ordering::Relaxed) && !panicked() {
+    terminal.draw(|f| app.draw(f))?;
+
+    // Wakes up regularly for the thinking indicator and the computer's moves
+    app.tick();
+    if !event::poll(Duration::from_millis(100))? {
+      continue
+    }
 
     match event::read()? {
-      Event::Key(event) => match event.code {
-        KeyCode::Char('q') => break,
-        _ => {}
+      // Raw mode turns ctrl-c into a key, it still quits
+      Event::Key(KeyEvent {
+        code: KeyCode::Char('c'),
+        modifiers
+      }) if modifiers.contains(KeyModifiers::CONTROL) => break,
+      Event::Key(event) => {
+        app.handle_key(event);
+        if let Some(text) = app.take_clipboard() {
+          copy_to_clipboard(&text)?;
+        }
       },
-      Event::Mouse(_) => (),
+      Event::Mouse(event) => app.handle_mouse(event),
       Event::Resize(width, height) => {
         {
           terminal.resize(Rect::new(0, 0, width, height))?;
@@ -50,14 +376,9 @@ fn main() -> Result<(), io::Error> {
     }
   }
 
-  // restore terminal
-  disable_raw_mode()?;
-  execute!(
-    terminal.backend_mut(),
-    LeaveAlternateScreen,
-    DisableMouseCapture
-  )?;
-  terminal.show_cursor()?;
-
+  drop(session);
+  if panicked() {
+    process::exit(101)
+  }
   Ok(())
 }
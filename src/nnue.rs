@@ -0,0 +1,468 @@
+//! Efficiently updatable neural network evaluation
+//!
+//! The network uses HalfKP features: for each perspective, every non-king
+//! piece is a feature relative to the square of that perspective's king.
+//! Black's perspective is mirrored vertically, so both sides share weights.
+//!
+//! ```text
+//! 40960 features -> L1 (x2 perspectives) -> L2 -> 1
+//! ```
+//!
+//! The first layer is kept as an accumulator that is updated on every move
+//! instead of being recomputed. Activations are clipped to `0..=127` and
+//! hidden sums are shifted right by 6 before clipping.
+//!
+//! # File format
+//!
+//! All numbers are little-endian:
+//!
+//! | Field            | Type  | Count            |
+//! |------------------|-------|------------------|
+//! | magic `CHYESNN1` | bytes | 8                |
+//! | version (1)      | u32   | 1                |
+//! | feature set (1)  | u32   | 1                |
+//! | L1               | u32   | 1                |
+//! | L2               | u32   | 1                |
+//! | output scale     | i32   | 1                |
+//! | L1 biases        | i16   | L1               |
+//! | L1 weights       | i16   | 40960 * L1       |
+//! | L2 biases        | i32   | L2               |
+//! | L2 weights       | i8    | L2 * 2 * L1      |
+//! | output bias      | i32   | 1                |
+//! | output weights   | i8    | L2               |
+//!
+//! L1 weights are stored feature by feature, L2 weights neuron by neuron with
+//! the side to move's half first. The output is divided by the output scale
+//! to get centipawns.
+
+use std::io::{self, Read, Write};
+use std::path::Path;
+use std::{fs, iter};
+
+use crate::{Board, Color, Coordinate, Piece, Pieces};
+
+pub const FEATURES: usize = 64 * 10 * 64;
+
+const MAGIC: &[u8; 8] = b"CHYESNN1";
+const VERSION: u32 = 1;
+const HALF_KP: u32 = 1;
+const HIDDEN_SHIFT: i32 = 6;
+
+pub struct Network {
+  l1:             usize,
+  l2:             usize,
+  output_scale:   i32,
+  ft_biases:      Vec<i16>,
+  ft_weights:     Vec<i16>,
+  hidden_biases:  Vec<i32>,
+  hidden_weights: Vec<i8>,
+  output_bias:    i32,
+  output_weights: Vec<i8>
+}
+
+fn invalid(message: &str) -> io::Error {
+  io::Error::new(io::ErrorKind::InvalidData, message.to_string())
+}
+
+fn read_array<const N: usize>(reader: &mut impl Read) -> io::Result<[u8; N]> {
+  let mut bytes = [0; N];
+  reader.read_exact(&mut bytes)?;
+  Ok(bytes)
+}
+
+fn read_vec<T, const N: usize>(
+  reader: &mut impl Read,
+  count: usize,
+  decode: fn([u8; N]) -> T
+) -> io::Result<Vec<T>> {
+  (0..count)
+    .map(|_| read_array::<N>(reader).map(decode))
+    .collect()
+}
+
+impl Network {
+  /// Network with small pseudo-random weights, a starting point for training
+  pub fn random(
+    l1: usize,
+    l2: usize,
+    seed: u64
+  ) -> Self {
+    // xorshift64*
+    let mut state = seed.max(1);
+    let mut next = move |range: i64| -> i64 {
+      state ^= state >> 12;
+      state ^= state << 25;
+      state ^= state >> 27;
+      (state.wrapping_mul(0x2545_f491_4f6c_dd1d) >> 33) as i64 % (2 * range + 1) - range
+    };
+
+    Network {
+      l1,
+      l2,
+      output_scale: 16,
+      ft_biases: (0..l1).map(|_| next(32) as i16).collect(),
+      ft_weights: (0..FEATURES * l1).map(|_| next(16) as i16).collect(),
+      hidden_biases: (0..l2).map(|_| next(256) as i32).collect(),
+      hidden_weights: (0..l2 * 2 * l1).map(|_| next(32) as i8).collect(),
+      output_bias: next(64) as i32,
+      output_weights: (0..l2).map(|_| next(64) as i8).collect()
+    }
+  }
+
+  pub fn load(path: impl AsRef<Path>) -> io::Result<Self> {
+    Self::read(&mut io::BufReader::new(fs::File::open(path)?))
+  }
+
+  pub fn save(
+    &self,
+    path: impl AsRef<Path>
+  ) -> io::Result<()> {
+    let mut writer = io::BufWriter::new(fs::File::create(path)?);
+    self.write(&mut writer)?;
+    writer.flush()
+  }
+
+  pub fn read(reader: &mut impl Read) -> io::Result<Self> {
+    if &read_array::<8>(reader)? != MAGIC {
+      return Err(invalid("Not a chyes network file"))
+    }
+
+    let read_u32 = |reader: &mut _| read_array::<4>(reader).map(u32::from_le_bytes);
+
+    if read_u32(reader)? != VERSION {
+      return Err(invalid("Unsupported network version"))
+    }
+
+    if read_u32(reader)? != HALF_KP {
+      return Err(invalid("Unsupported network feature set"))
+    }
+
+    let l1 = read_u32(reader)? as usize;
+    let l2 = read_u32(reader)? as usize;
+    let output_scale = read_array::<4>(reader).map(i32::from_le_bytes)?;
+
+    if l1 == 0 || l2 == 0 || l1 > 4096 || l2 > 4096 || output_scale == 0 {
+      return Err(invalid("Invalid network dimensions"))
+    }
+
+    let ft_biases = read_vec(reader, l1, i16::from_le_bytes)?;
+    let ft_weights = read_vec(reader, FEATURES * l1, i16::from_le_bytes)?;
+    let hidden_biases = read_vec(reader, l2, i32::from_le_bytes)?;
+    let hidden_weights = read_vec(reader, l2 * 2 * l1, i8::from_le_bytes)?;
+    let output_bias = read_array::<4>(reader).map(i32::from_le_bytes)?;
+    let output_weights = read_vec(reader, l2, i8::from_le_bytes)?;
+
+    Ok(Network {
+      l1,
+      l2,
+      output_scale,
+      ft_biases,
+      ft_weights,
+      hidden_biases,
+      hidden_weights,
+      output_bias,
+      output_weights
+    })
+  }
+
+  pub fn write(
+    &self,
+    writer: &mut impl Write
+  ) -> io::Result<()> {
+    writer.write_all(MAGIC)?;
+
+    for value in [VERSION, HALF_KP, self.l1 as u32, self.l2 as u32] {
+      writer.write_all(&value.to_le_bytes())?;
+    }
+    writer.write_all(&self.output_scale.to_le_bytes())?;
+
+    for value in self.ft_biases.iter().chain(self.ft_weights.iter()) {
+      writer.write_all(&value.to_le_bytes())?;
+    }
+
+    for value in self.hidden_biases.iter() {
+      writer.write_all(&value.to_le_bytes())?;
+    }
+
+    for value in self.hidden_weights.iter() {
+      writer.write_all(&value.to_le_bytes())?;
+    }
+
+    writer.write_all(&self.output_bias.to_le_bytes())?;
+
+    for value in self.output_weights.iter() {
+      writer.write_all(&value.to_le_bytes())?;
+    }
+
+    Ok(())
+  }
+
+  fn feature_weights(
+    &self,
+    feature: usize
+  ) -> &[i16] {
+    &self.ft_weights[feature * self.l1..(feature + 1) * self.l1]
+  }
+
+  /// Evaluation in centipawns from the side to move's point of view
+  pub fn evaluate(
+    &self,
+    accumulator: &Accumulator,
+    turn: Color
+  ) -> i32 {
+    // Side to move first, then the other side
+    let input: Vec<u8> = [turn, turn.opposite()]
+      .iter()
+      .flat_map(|&color| accumulator.values[color as usize].iter())
+      .map(|&value| value.clamp(0, 127) as u8)
+      .collect();
+
+    let hidden: Vec<u8> = (0..self.l2)
+      .map(|neuron| {
+        let weights = &self.hidden_weights[neuron * 2 * self.l1..(neuron + 1) * 2 * self.l1];
+        let sum = self.hidden_biases[neuron] + simd::dot(&input, weights);
+        (sum >> HIDDEN_SHIFT).clamp(0, 127) as u8
+      })
+      .collect();
+
+    (self.output_bias + simd::dot(&hidden, &self.output_weights)) / self.output_scale
+  }
+}
+
+// Feature index of a non-king piece as seen from one side
+fn feature(
+  perspective: Color,
+  king: Coordinate,
+  piece: Piece,
+  coord: Coordinate
+) -> Option<usize> {
+  let breed = match piece.breed {
+    Pieces::Queen => 0,
+    Pieces::Rook => 1,
+    Pieces::Bishop => 2,
+    Pieces::Knight => 3,
+    Pieces::Pawn => 4,
+    Pieces::King => return None
+  };
+
+  let side = if piece.color == perspective { 0 } else { 5 };
+  let orient = |coord: Coordinate| {
+    let row = if perspective == Color::White {
+      coord.row
+    } else {
+      7 - coord.row
+    };
+    row as usize * 8 + coord.col as usize
+  };
+
+  Some((orient(king) * 10 + breed + side) * 64 + orient(coord))
+}
+
+/// First layer outputs for both perspectives, indexed by `Color`
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Accumulator {
+  values: [Vec<i16>; 2],
+  kings:  [Option<Coordinate>; 2]
+}
+
+impl Accumulator {
+  pub fn new(
+    network: &Network,
+    board: &Board
+  ) -> Self {
+    let mut accumulator = Accumulator {
+      values: [Vec::new(), Vec::new()],
+      kings:  [None, None]
+    };
+
+    for color in [Color::White, Color::Black] {
+      accumulator.refresh(network, board, color);
+    }
+
+    accumulator
+  }
+
+  fn refresh(
+    &mut self,
+    network: &Network,
+    board: &Board,
+    perspective: Color
+  ) {
+    let values = &mut self.values[perspective as usize];
+    values.clear();
+    values.extend_from_slice(&network.ft_biases);
+
+    let king = board.get_king_coord(perspective);
+    self.kings[perspective as usize] = king;
+
+    // Without a king there is nothing to relate the pieces to
+    if let Some(king) = king {
+      for (coord, piece) in board.pieces.iter() {
+        if let Some(feature) = feature(perspective, king, *piece, *coord) {
+          simd::add_assign(values, network.feature_weights(feature));
+        }
+      }
+    }
+  }
+
+  /// Applies the squares changed by a move, as returned by `Undo::changes`
+  pub fn update(
+    &mut self,
+    network: &Network,
+    board: &Board,
+    changes: &[(Coordinate, Option<Piece>, Option<Piece>)]
+  ) {
+    for perspective in [Color::White, Color::Black] {
+      let king = board.get_king_coord(perspective);
+
+      // A king move changes every feature of its side
+      let king = match king {
+        Some(king) if Some(king) == self.kings[perspective as usize] => king,
+        _ => {
+          self.refresh(network, board, perspective);
+          continue
+        }
+      };
+
+      let values = &mut self.values[perspective as usize];
+      for &(coord, before, after) in changes {
+        if let Some(feature) = before.and_then(|piece| feature(perspective, king, piece, coord)) {
+          simd::sub_assign(values, network.feature_weights(feature));
+        }
+
+        if let Some(feature) = after.and_then(|piece| feature(perspective, king, piece, coord)) {
+          simd::add_assign(values, network.feature_weights(feature));
+        }
+      }
+    }
+  }
+}
+
+// Vector kernels with a scalar fallback, AVX2 is detected at runtime
+mod simd {
+  use super::iter;
+
+  pub fn add_assign(
+    values: &mut [i16],
+    weights: &[i16]
+  ) {
+    #[cfg(target_arch = "x86_64")]
+    if is_x86_feature_detected!("avx2") {
+      // Safety: AVX2 support was checked above
+      return unsafe { avx2::add_assign(values, weights) }
+    }
+
+    for (value, weight) in values.iter_mut().zip(weights) {
+      *value = value.wrapping_add(*weight);
+    }
+  }
+
+  pub fn sub_assign(
+    values: &mut [i16],
+    weights: &[i16]
+  ) {
+    #[cfg(target_arch = "x86_64")]
+    if is_x86_feature_detected!("avx2") {
+      // Safety: AVX2 support was checked above
+      return unsafe { avx2::sub_assign(values, weights) }
+    }
+
+    for (value, weight) in values.iter_mut().zip(weights) {
+      *value = value.wrapping_sub(*weight);
+    }
+  }
+
+  pub fn dot(
+    input: &[u8],
+    weights: &[i8]
+  ) -> i32 {
+    #[cfg(target_arch = "x86_64")]
+    if is_x86_feature_detected!("avx2") {
+      // Safety: AVX2 support was checked above
+      return unsafe { avx2::dot(input, weights) }
+    }
+
+    scalar_dot(input, weights)
+  }
+
+  pub fn scalar_dot(
+    input: &[u8],
+    weights: &[i8]
+  ) -> i32 {
+    iter::zip(input, weights)
+      .map(|(&x, &w)| x as i32 * w as i32)
+      .sum()
+  }
+
+  #[cfg(target_arch = "x86_64")]
+  mod avx2 {
+    use std::arch::x86_64::*;
+
+    #[target_feature(enable = "avx2")]
+    pub unsafe fn add_assign(
+      values: &mut [i16],
+      weights: &[i16]
+    ) {
+      let chunks = values.len().min(weights.len()) / 16;
+
+      for i in 0..chunks {
+        let value = _mm256_loadu_si256(values.as_ptr().add(i * 16) as *const __m256i);
+        let weight = _mm256_loadu_si256(weights.as_ptr().add(i * 16) as *const __m256i);
+        _mm256_storeu_si256(
+          values.as_mut_ptr().add(i * 16) as *mut __m256i,
+          _mm256_add_epi16(value, weight)
+        );
+      }
+
+      for i in chunks * 16..values.len().min(weights.len()) {
+        values[i] = values[i].wrapping_add(weights[i]);
+      }
+    }
+
+    #[target_feature(enable = "avx2")]
+    pub unsafe fn sub_assign(
+      values: &mut [i16],
+      weights: &[i16]
+    ) {
+      let chunks = values.len().min(weights.len()) / 16;
+
+      for i in 0..chunks {
+        let value = _mm256_loadu_si256(values.as_ptr().add(i * 16) as *const __m256i);
+        let weight = _mm256_loadu_si256(weights.as_ptr().add(i * 16) as *const __m256i);
+        _mm256_storeu_si256(
+          values.as_mut_ptr().add(i * 16) as *mut __m256i,
+          _mm256_sub_epi16(value, weight)
+        );
+      }
+
+      for i in chunks * 16..values.len().min(weights.len()) {
+        values[i] = values[i].wrapping_sub(weights[i]);
+      }
+    }
+
+    // Inputs are at most 127, so the pairwise i16 sums can't saturate
+    #[target_feature(enable = "avx2")]
+    pub unsafe fn dot(
+      input: &[u8],
+      weights: &[i8]
+    ) -> i32 {
+      let length = input.len().min(weights.len());
+      let chunks = length / 32;
+      let ones = _mm256_set1_epi16(1);
+      let mut sum = _mm256_setzero_si256();
+
+      for i in 0..chunks {
+        let x = _mm256_loadu_si256(input.as_ptr().add(i * 32) as *const __m256i);
+        let w = _mm256_loadu_si256(weights.as_ptr().add(i * 32) as *const __m256i);
+        let pairs = _mm256_maddubs_epi16(x, w);
+        sum = _mm256_add_epi32(sum, _mm256_madd_epi16(pairs, ones));
+      }
+
+      let mut lanes = [0i32; 8];
+      _mm256_storeu_si256(lanes.as_mut_ptr() as *mut __m256i, sum);
+
+      lanes.iter().sum::<i32>()
+        + super::scalar_dot(&input[chunks * 32..length], &weights[chunks * 32..length])
+    }
+  }
+}
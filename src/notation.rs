@@ -0,0 +1,239 @@
+use std::str::FromStr;
+
+use crate::{Board, Coordinate, Move, Pieces};
+
+fn letter(breed: Pieces) -> &'static str {
+  match breed {
+    Pieces::King => "K",
+    Pieces::Queen => "Q",
+    Pieces::Rook => "R",
+    Pieces::Bishop => "B",
+    Pieces::Knight => "N",
+    Pieces::Pawn => ""
+  }
+}
+
+fn breed_from_letter(c: char) -> Option<Pieces> {
+  match c {
+    'K' => Some(Pieces::King),
+    'Q' => Some(Pieces::Queen),
+    'R' => Some(Pieces::Rook),
+    'B' => Some(Pieces::Bishop),
+    'N' => Some(Pieces::Knight),
+    _ => None
+  }
+}
+
+impl Board {
+  fn is_castling(
+    &self,
+    mv: Move
+  ) -> bool {
+    self
+      .get_piece(&mv.start)
+      .is_some_and(|piece| piece.breed == Pieces::King)
+      && (mv.start.col as i8 - mv.target.col as i8).abs() == 2
+  }
+
+  fn is_promotion(
+    &self,
+    mv: Move
+  ) -> bool {
+    self
+      .get_piece(&mv.start)
+      .is_some_and(|piece| piece.breed == Pieces::Pawn)
+      && (mv.target.row == 0 || mv.target.row == 7)
+  }
+
+  /// The move as `legal_moves` lists it, a pawn reaching the last rank
+  /// without a piece named becomes a queen
+  pub fn normalize(
+    &self,
+    mv: Move
+  ) -> Move {
+    match mv.promotion {
+      None if self.is_promotion(mv) => Move::promoting(mv.start, mv.target, Pieces::Queen),
+      _ => mv
+    }
+  }
+
+  /// Standard algebraic notation of a legal move, e.g. `Nbd7`, `exd5`, `O-O`, `e8=Q#`
+  pub fn to_san(
+    &self,
+    mv: Move
+  ) -> String {
+    let piece = match self.get_piece(&mv.start) {
+      Some(piece) => piece,
+      None => return mv.to_string()
+    };
+
+    let mut san = if self.is_castling(mv) {
+      String::from(if mv.target.col == 6 { "O-O" } else { "O-O-O" })
+    } else {
+      let mut san = String::from(letter(piece.breed));
+      let is_capture = self.get_piece(&mv.target).is_some()
+        || (piece.breed == Pieces::Pawn && mv.start.col != mv.target.col);
+
+      if piece.breed == Pieces::Pawn {
+        if is_capture {
+          san.push((b'a' + mv.start.col) as char);
+        }
+      } else {
+        // Other pieces of the same kind that can go to the same square
+        let others: Vec<Coordinate> = self
+          .legal_moves()
+          .into_iter()
+          .filter(|other| {
+            other.target == mv.target
+              && other.start != mv.start
+              && self.get_piece(&other.start).map(|other| other.breed) == Some(piece.breed)
+          })
+          .map(|other| other.start)
+          .collect();
+
+        let start = mv.start.to_string();
+        if !others.is_empty() {
+          if others.iter().all(|other| other.col != mv.start.col) {
+            san.push_str(&start[..1]);
+          } else if others.iter().all(|other| other.row != mv.start.row) {
+            san.push_str(&start[1..]);
+          } else {
+            san.push_str(&start);
+          }
+        }
+      }
+
+      if is_capture {
+        san.push('x');
+      }
+      san.push_str(&mv.target.to_string());
+
+      if self.is_promotion(mv) {
+        san.push('=');
+        san.push_str(letter(mv.promotion.unwrap_or(Pieces::Queen)));
+      }
+
+      san
+    };
+
+    let mut after = self.clone();
+    if after.apply_move(mv).is_ok() {
+      if after.is_in_checkmate(after.turn) {
+        san.push('#');
+      } else if after.is_in_check(after.turn) {
+        san.push('+');
+      }
+    }
+
+    san
+  }
+
+  /// Finds the legal move written in standard algebraic notation
+  pub fn parse_san(
+    &self,
+    san: &str
+  ) -> Result<Move, String> {
+    let text = san.trim().trim_end_matches(['+', '#', '!', '?']);
+    let legal = self.legal_moves();
+
+    if matches!(text, "O-O" | "0-0" | "O-O-O" | "0-0-0") {
+      let col = if text.len() == 3 { 6 } else { 2 };
+
+      return legal
+        .into_iter()
+        .find(|&mv| self.is_castling(mv) && mv.target.col == col)
+        .ok_or_else(|| format!("Illegal move {}", san))
+    }
+
+    // Promotion suffix, `e8=Q` or `e8Q`
+    let (text, promotion) = match text.char_indices().last() {
+      Some((index, c)) if breed_from_letter(c).is_some() && index > 0 => {
+        (text[..index].trim_end_matches('='), breed_from_letter(c))
+      },
+      _ => (text, None)
+    };
+
+    let breed = text
+      .chars()
+      .next()
+      .and_then(breed_from_letter)
+      .unwrap_or(Pieces::Pawn);
+    let text: String = text
+      .chars()
+      .skip(if breed == Pieces::Pawn { 0 } else { 1 })
+      .filter(|&c| c != 'x' && c != '-' && c != ':')
+      .collect();
+
+    if text.len() < 2 || !text.is_ascii() {
+      return Err(format!("Couldn't parse move {}", san))
+    }
+
+    let (hint, target) = text.split_at(text.len() - 2);
+    let target =
+      Coordinate::from_str(target).map_err(|_| format!("Couldn't parse move {}", san))?;
+
+    let candidates: Vec<Move> = legal
+      .into_iter()
+      .filter(|mv| {
+        let start = mv.start.to_string();
+
+        mv.target == target
+          && self.get_piece(&mv.start).map(|piece| piece.breed) == Some(breed)
+          && hint.chars().all(|c| start.contains(c))
+          // A promotion without a piece is taken as a queen
+          && match mv.promotion {
+            Some(piece) => piece == promotion.unwrap_or(Pieces::Queen),
+            None => promotion.is_none()
+          }
+      })
+      .collect();
+
+    match candidates.as_slice() {
+      [mv] => Ok(*mv),
+      [] => Err(format!("Illegal move {}", san)),
+      _ => Err(format!("Ambiguous move {}", san))
+    }
+  }
+
+  /// Long algebraic notation used by UCI, e.g. `e2e4` or `e7e8q`
+  pub fn to_uci(
+    &self,
+    mv: Move
+  ) -> String {
+    self.normalize(mv).to_string()
+  }
+
+  /// Finds the legal move written in UCI notation
+  pub fn parse_uci(
+    &self,
+    text: &str
+  ) -> Result<Move, String> {
+    let text = text.trim();
+
+    if !text.is_ascii() || !(4..=5).contains(&text.len()) {
+      return Err(format!("Couldn't parse move {}", text))
+    }
+
+    let start =
+      Coordinate::from_str(&text[0..2]).map_err(|_| format!("Couldn't parse move {}", text))?;
+    let target =
+      Coordinate::from_str(&text[2..4]).map_err(|_| format!("Couldn't parse move {}", text))?;
+
+    let mv = match text[4..].chars().next() {
+      Some(c) => Move::promoting(
+        start,
+        target,
+        breed_from_letter(c.to_ascii_uppercase())
+          .ok_or_else(|| format!("Couldn't parse move {}", text))?
+      ),
+      // Queening when the piece is left out
+      None => self.normalize(Move::new(start, target))
+    };
+
+    if self.legal_moves().contains(&mv) {
+      Ok(mv)
+    } else {
+      Err(format!("Illegal move {}", text))
+    }
+  }
+}
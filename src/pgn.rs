@@ -0,0 +1,390 @@
+use std::fs;
+use std::iter::Peekable;
+use std::path::Path;
+use std::str::Chars;
+
+use crate::game::{Game, GameResult};
+use crate::{Board, Color, Move};
+
+/// Comment in braces, a `}` inside would end it early and is left out
+pub fn comment_token(comment: &str) -> String { format!("{{{}}}", comment.replace('}', "")) }
+
+/// Moves given instead of one of the game's, `(12... Nf6 13. Bg5)`
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Variation {
+  /// Index of the move it replaces in the line it branches from
+  pub ply:        usize,
+  pub moves:      Vec<Move>,
+  pub comments:   Vec<Option<String>>,
+  /// Variations of this one, their plies counted from its first move
+  pub variations: Vec<Variation>
+}
+
+impl Variation {
+  /// Movetext with numbers, comments and nested variations, from the
+  /// position before the move it replaces
+  pub fn to_pgn(
+    &self,
+    start: &Board
+  ) -> String {
+    let mut tokens = Vec::new();
+    let mut board = start.clone();
+    // Black's moves get a number of their own after a break in the text
+    let mut resume = true;
+
+    for (index, &mv) in self.moves.iter().enumerate() {
+      if board.turn == Color::White {
+        tokens.push(format!("{}.", board.fullmove_number));
+      } else if resume {
+        tokens.push(format!("{}...", board.fullmove_number));
+      }
+      tokens.push(board.to_san(mv));
+      resume = false;
+
+      if let Some(comment) = &self.comments[index] {
+        tokens.push(comment_token(comment));
+        resume = true;
+      }
+      for variation in self
+        .variations
+        .iter()
+        .filter(|variation| variation.ply == index)
+      {
+        tokens.push(format!("({})", variation.to_pgn(&board)));
+        resume = true;
+      }
+
+      if board.apply_move(mv).is_err() {
+        break
+      }
+    }
+
+    tokens.join(" ")
+  }
+}
+
+/// A game read from a PGN file, with its tags and the variations branching
+/// off its moves
+#[derive(Clone)]
+pub struct PgnGame {
+  pub tags:       Vec<(String, String)>,
+  pub game:       Game,
+  pub variations: Vec<Variation>
+}
+
+impl PgnGame {
+  pub fn tag(
+    &self,
+    name: &str
+  ) -> Option<&str> {
+    self
+      .tags
+      .iter()
+      .find(|(tag, _)| tag == name)
+      .map(|(_, value)| value.as_str())
+  }
+
+  /// One line summing up the game, `White - Black, Event, Date, Result`,
+  /// with `?` for missing tags
+  pub fn header(&self) -> String {
+    let tag = |name| self.tag(name).unwrap_or("?");
+
+    format!(
+      "{} - {}, {}, {}, {}",
+      tag("White"),
+      tag("Black"),
+      tag("Event"),
+      tag("Date"),
+      tag("Result")
+    )
+  }
+}
+
+// Characters up to the closing one, which is consumed
+fn read_until(
+  chars: &mut Peekable<Chars>,
+  end: char
+) -> String {
+  let mut text = String::new();
+  for c in chars.by_ref() {
+    if c == end {
+      break
+    }
+    text.push(c);
+  }
+  text
+}
+
+// `Name "value"`, the inside of a tag pair
+fn parse_tag(text: &str) -> Result<(String, String), String> {
+  let (name, value) = text
+    .trim()
+    .split_once(char::is_whitespace)
+    .ok_or_else(|| format!("Invalid tag [{}]", text))?;
+  let value = value
+    .trim()
+    .strip_prefix('"')
+    .and_then(|value| value.strip_suffix('"'))
+    .ok_or_else(|| format!("Invalid tag [{}]", text))?;
+
+  let mut unescaped = String::new();
+  let mut chars = value.chars();
+  while let Some(c) = chars.next() {
+    match c {
+      '\\' => unescaped.extend(chars.next()),
+      c => unescaped.push(c)
+    }
+  }
+
+  Ok((String::from(name), unescaped))
+}
+
+// Move numbers, `12.` or `12...`, possibly glued to the move
+fn strip_move_number(token: &str) -> &str {
+  match token.rfind('.') {
+    Some(index)
+      if token[..index]
+        .chars()
+        .all(|c| c.is_ascii_digit() || c == '.') =>
+    {
+      &token[index + 1..]
+    },
+    _ => token
+  }
+}
+
+// Variation being read, with the position after its moves so far
+struct Line {
+  variation: Variation,
+  // Position before its first move
+  start:     Board,
+  board:     Board,
+  // Set at the first move that can't be read, the rest of the line is dropped
+  broken:    bool
+}
+
+impl Line {
+  // Plays a movetext token, giving up on the line at the first bad move
+  fn read(
+    &mut self,
+    token: &str
+  ) {
+    let san = strip_move_number(token);
+    if self.broken || san.is_empty() {
+      return
+    }
+
+    match self.board.parse_san(san) {
+      Ok(mv) if self.board.apply_move(mv).is_ok() => {
+        self.variation.moves.push(mv);
+        self.variation.comments.push(None);
+      },
+      _ => self.broken = true
+    }
+  }
+}
+
+// Game being read, created at its first move once all tags are known
+struct Reader {
+  tags:       Vec<(String, String)>,
+  game:       Option<Game>,
+  variations: Vec<Variation>,
+  // Variations opened and not closed yet, the innermost last
+  lines:      Vec<Line>
+}
+
+impl Reader {
+  fn game(&mut self) -> Result<&mut Game, String> {
+    if self.game.is_none() {
+      let start = match self.tags.iter().find(|(tag, _)| tag == "FEN") {
+        Some((_, fen)) => {
+          let mut board = Board::new();
+          board
+            .load_fen(fen)
+            .map_err(|err| format!("Invalid FEN tag: {}", err))?;
+          board
+        },
+        None => Board::default()
+      };
+      self.game = Some(Game::new(start));
+    }
+
+    Ok(self.game.as_mut().expect("The game was just created"))
+  }
+
+  fn finish(
+    &mut self,
+    games: &mut Vec<PgnGame>
+  ) -> Result<(), String> {
+    if self.tags.is_empty() && self.game.is_none() {
+      return Ok(())
+    }
+
+    let game = self.game()?.clone();
+    games.push(PgnGame {
+      tags: std::mem::take(&mut self.tags),
+      game,
+      variations: std::mem::take(&mut self.variations)
+    });
+    self.game = None;
+    self.lines.clear();
+    Ok(())
+  }
+
+  // `(` branches off before the last move of the line being read
+  fn open_variation(&mut self) -> Result<(), String> {
+    let (ply, start) = match self.lines.last() {
+      Some(line) => {
+        let ply = line.variation.moves.len().saturating_sub(1);
+        let mut board = line.start.clone();
+        for &mv in &line.variation.moves[..ply] {
+          board.apply_move(mv)?;
+        }
+        (ply, board)
+      },
+      None => {
+        let game = self.game()?;
+        let ply = game.moves().len().saturating_sub(1);
+        (ply, game.board_at(ply))
+      }
+    };
+
+    self.lines.push(Line {
+      variation: Variation {
+        ply,
+        moves: Vec::new(),
+        comments: Vec::new(),
+        variations: Vec::new()
+      },
+      board: start.clone(),
+      start,
+      broken: false
+    });
+    Ok(())
+  }
+
+  fn close_variation(&mut self) {
+    if let Some(line) = self.lines.pop() {
+      match self.lines.last_mut() {
+        Some(parent) => parent.variation.variations.push(line.variation),
+        None => self.variations.push(line.variation)
+      }
+    }
+  }
+}
+
+/// Reads every game of a PGN file. Comments are kept on the moves they
+/// follow and NAGs are skipped. A variation ends at its first move that
+/// can't be read, mistakes in the game itself are errors
+pub fn parse(text: &str) -> Result<Vec<PgnGame>, String> {
+  let mut games = Vec::new();
+  let mut reader = Reader {
+    tags:       Vec::new(),
+    game:       None,
+    variations: Vec::new(),
+    lines:      Vec::new()
+  };
+  let mut chars = text.chars().peekable();
+
+  while let Some(c) = chars.next() {
+    match c {
+      '[' if reader.lines.is_empty() => {
+        // Tags after movetext start the next game
+        if reader.game.is_some() {
+          reader.finish(&mut games)?;
+        }
+        reader.tags.push(parse_tag(&read_until(&mut chars, ']'))?);
+      },
+      '{' => {
+        let comment = read_until(&mut chars, '}').trim().replace('\n', " ");
+        match reader.lines.last_mut() {
+          Some(line) => {
+            if let Some(last) = line.variation.comments.last_mut() {
+              *last = Some(comment);
+            }
+          },
+          None => reader.game()?.comment(comment)
+        }
+      },
+      ';' => {
+        read_until(&mut chars, '\n');
+      },
+      '(' => reader.open_variation()?,
+      ')' => reader.close_variation(),
+      c if c.is_whitespace() => {},
+      c => {
+        let mut token = String::from(c);
+        while let Some(&c) = chars.peek() {
+          if c.is_whitespace() || "{}();[".contains(c) {
+            break
+          }
+          token.push(c);
+          chars.next();
+        }
+
+        if token.starts_with('$') {
+          continue
+        }
+
+        let result = match token.as_str() {
+          "1-0" => Some(Some(GameResult::WhiteWins)),
+          "0-1" => Some(Some(GameResult::BlackWins)),
+          "1/2-1/2" => Some(Some(GameResult::Draw)),
+          "*" => Some(None),
+          _ => None
+        };
+
+        if let Some(line) = reader.lines.last_mut() {
+          if result.is_none() {
+            line.read(&token);
+          }
+          continue
+        }
+
+        match result {
+          Some(result) => {
+            let game = reader.game()?;
+            // Results the rules don't explain, resignations, time forfeits and agreed draws
+            if let Some(result) = result {
+              if game.outcome().is_none() {
+                let reason = match result {
+                  GameResult::WhiteWins => "White wins",
+                  GameResult::BlackWins => "Black wins",
+                  GameResult::Draw => "Draw agreed"
+                };
+                game.decide(result, String::from(reason));
+              }
+            }
+            reader.finish(&mut games)?;
+          },
+          None => {
+            let san = strip_move_number(&token);
+            if san.is_empty() {
+              continue
+            }
+
+            let number = games.len() + 1;
+            let game = reader.game()?;
+            let mv = game
+              .board()
+              .parse_san(san)
+              .map_err(|err| format!("Game {}: {}", number, err))?;
+            game
+              .play(mv)
+              .map_err(|err| format!("Game {}: {}", number, err))?;
+          }
+        }
+      }
+    }
+  }
+
+  reader.finish(&mut games)?;
+  Ok(games)
+}
+
+pub fn load(path: impl AsRef<Path>) -> Result<Vec<PgnGame>, String> {
+  let path = path.as_ref();
+  let text =
+    fs::read_to_string(path).map_err(|err| format!("Couldn't read {}: {}", path.display(), err))?;
+  parse(&text)
+}
@@ -0,0 +1,335 @@
+// Boards as plain strings, for logs, bug reports and tests where there is no
+// terminal to draw the widget on
+
+use core::fmt;
+use std::str::FromStr;
+
+use crate::theme::{self, GlyphSet, Theme};
+use crate::{Board, Color, Coordinate, Move};
+
+/// Look of a board rendered as text
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum TextStyle {
+  /// `+---+` grid with FEN letters and rank and file labels, safe anywhere
+  #[default]
+  Ascii,
+  /// Box drawing grid with the glyph set's pieces and labels
+  Unicode,
+  /// Colored squares in the theme's colors, for terminals
+  Ansi,
+  /// One character a square, `.` for empty ones, without labels
+  Compact
+}
+
+impl FromStr for TextStyle {
+  type Err = String;
+
+  fn from_str(text: &str) -> Result<Self, Self::Err> {
+    match text.trim().to_lowercase().as_str() {
+      "ascii" => Ok(TextStyle::Ascii),
+      "unicode" => Ok(TextStyle::Unicode),
+      "ansi" | "color" => Ok(TextStyle::Ansi),
+      "compact" => Ok(TextStyle::Compact),
+      _ => Err(format!(
+        "Unknown text style `{}`, expected ascii, unicode, ansi or compact",
+        text
+      ))
+    }
+  }
+}
+
+// Why a square stands out, highlights win over the last move
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Mark {
+  None,
+  LastMove,
+  Highlight
+}
+
+impl Mark {
+  // Characters around the piece in the grid styles
+  fn brackets(self) -> (char, char) {
+    match self {
+      Mark::None => (' ', ' '),
+      Mark::LastMove => ('(', ')'),
+      Mark::Highlight => ('[', ']')
+    }
+  }
+}
+
+// Pieces of the grid lines: horizontal, vertical, then the corners and
+// joints from the top left, row by row
+struct GridChars {
+  horizontal: char,
+  vertical:   char,
+  top:        [char; 3],
+  middle:     [char; 3],
+  bottom:     [char; 3]
+}
+
+const ASCII_GRID: GridChars = GridChars {
+  horizontal: '-',
+  vertical:   '|',
+  top:        ['+', '+', '+'],
+  middle:     ['+', '+', '+'],
+  bottom:     ['+', '+', '+']
+};
+
+const UNICODE_GRID: GridChars = GridChars {
+  horizontal: '─',
+  vertical:   '│',
+  top:        ['┌', '┬', '┐'],
+  middle:     ['├', '┼', '┤'],
+  bottom:     ['└', '┴', '┘']
+};
+
+/// A board rendered to a `String`, built like the terminal widget. The
+/// ASCII style always uses FEN letters, the others the glyph set
+#[derive(Clone, Copy)]
+pub struct TextBoard<'a> {
+  board:      &'a Board,
+  style:      TextStyle,
+  flipped:    bool,
+  glyphs:     GlyphSet,
+  theme:      Theme,
+  last_move:  Option<Move>,
+  highlights: &'a [Coordinate]
+}
+
+impl<'a> TextBoard<'a> {
+  pub fn new(board: &'a Board) -> Self {
+    TextBoard {
+      board,
+      style: TextStyle::default(),
+      flipped: false,
+      glyphs: GlyphSet::default(),
+      theme: Theme::default(),
+      last_move: None,
+      highlights: &[]
+    }
+  }
+
+  pub fn style(
+    mut self,
+    style: TextStyle
+  ) -> Self {
+    self.style = style;
+    self
+  }
+
+  /// Draws black at the bottom
+  pub fn flipped(
+    mut self,
+    flipped: bool
+  ) -> Self {
+    self.flipped = flipped;
+    self
+  }
+
+  pub fn glyphs(
+    mut self,
+    glyphs: GlyphSet
+  ) -> Self {
+    self.glyphs = glyphs;
+    self
+  }
+
+  /// Colors of the ANSI style
+  pub fn theme(
+    mut self,
+    theme: Theme
+  ) -> Self {
+    self.theme = theme;
+    self
+  }
+
+  /// Marks the squares the move went from and to, `(P)` in the grid styles
+  pub fn last_move(
+    mut self,
+    last_move: Option<Move>
+  ) -> Self {
+    self.last_move = last_move;
+    self
+  }
+
+  /// Marks the squares, `[P]` in the grid styles
+  pub fn highlights(
+    mut self,
+    highlights: &'a [Coordinate]
+  ) -> Self {
+    self.highlights = highlights;
+    self
+  }
+
+  /// The board as text, one line per rank and a newline after each
+  pub fn render(&self) -> String {
+    match self.style {
+      TextStyle::Ascii => self.grid(&ASCII_GRID),
+      TextStyle::Unicode => self.grid(&UNICODE_GRID),
+      TextStyle::Ansi => self.ansi(),
+      TextStyle::Compact => self.compact()
+    }
+  }
+
+  // Squares in text order, from the top left
+  fn rows(&self) -> impl Iterator<Item = impl Iterator<Item = Coordinate>> {
+    let flipped = self.flipped;
+    let index = move |i: u8| if flipped { 7 - i } else { i };
+
+    (0..8).map(move |row| (0..8).map(move |col| coord!(index(row), index(col))))
+  }
+
+  fn mark(
+    &self,
+    coord: Coordinate
+  ) -> Mark {
+    if self.highlights.contains(&coord) {
+      Mark::Highlight
+    } else if self
+      .last_move
+      .is_some_and(|mv| mv.start == coord || mv.target == coord)
+    {
+      Mark::LastMove
+    } else {
+      Mark::None
+    }
+  }
+
+  fn glyph(
+    &self,
+    coord: Coordinate
+  ) -> Option<char> {
+    let glyphs = match self.style {
+      TextStyle::Ascii => GlyphSet::ASCII,
+      _ => self.glyphs
+    };
+
+    self
+      .board
+      .get_piece(&coord)
+      .map(|piece| glyphs.glyph(piece))
+  }
+
+  // File letters under the pieces, the first one `indent` columns in and
+  // the next ones `width` apart
+  fn file_labels(
+    &self,
+    indent: usize,
+    width: usize
+  ) -> String {
+    let files: Vec<String> = self
+      .rows()
+      .next()
+      .into_iter()
+      .flatten()
+      .map(|coord| coord.to_notation()[..1].to_string())
+      .collect();
+
+    format!(
+      "{}{}\n",
+      " ".repeat(indent),
+      files.join(&" ".repeat(width - 1))
+    )
+  }
+
+  fn grid(
+    &self,
+    frame: &GridChars
+  ) -> String {
+    let line = |joints: [char; 3]| {
+      let cell: String = std::iter::repeat_n(frame.horizontal, 3).collect();
+      format!(
+        "  {}{}{}\n",
+        joints[0],
+        vec![cell; 8].join(&joints[1].to_string()),
+        joints[2]
+      )
+    };
+
+    let mut text = line(frame.top);
+    for (index, row) in self.rows().enumerate() {
+      let row: Vec<Coordinate> = row.collect();
+      let mut rank = format!("{} ", &row[0].to_notation()[1..]);
+      for coord in row {
+        let (left, right) = self.mark(coord).brackets();
+        rank.push(frame.vertical);
+        rank.push(left);
+        rank.push(self.glyph(coord).unwrap_or(' '));
+        rank.push(right);
+      }
+      rank.push(frame.vertical);
+      text.push_str(&rank);
+      text.push('\n');
+
+      text.push_str(&line(if index == 7 {
+        frame.bottom
+      } else {
+        frame.middle
+      }));
+    }
+
+    text + &self.file_labels(4, 4)
+  }
+
+  fn ansi(&self) -> String {
+    let theme = self.theme;
+    let mut text = String::new();
+
+    for row in self.rows() {
+      let row: Vec<Coordinate> = row.collect();
+      let mut rank = format!("{} ", &row[0].to_notation()[1..]);
+      for coord in row {
+        // Light squares where row and column add up to an even number, as
+        // in the widget
+        let background = match self.mark(coord) {
+          Mark::Highlight => theme.target,
+          Mark::LastMove => theme.last_move,
+          Mark::None if (coord.row + coord.col) % 2 == 0 => theme.light,
+          Mark::None => theme.dark
+        };
+        let foreground = match self.board.get_piece(&coord) {
+          Some(piece) if piece.color == Color::White => theme.white_piece,
+          _ => theme.black_piece
+        };
+
+        rank.push_str(&format!(
+          "\x1b[{};{}m {} ",
+          theme::sgr(background, true),
+          theme::sgr(foreground, false),
+          self.glyph(coord).unwrap_or(' ')
+        ));
+      }
+      text.push_str(&rank);
+      text.push_str("\x1b[0m\n");
+    }
+
+    text + &self.file_labels(3, 3)
+  }
+
+  // The same marks as the grid would show, on empty squares only
+  fn compact(&self) -> String {
+    let mut text = String::new();
+
+    for row in self.rows() {
+      for coord in row {
+        text.push(match (self.glyph(coord), self.mark(coord)) {
+          (Some(glyph), _) => glyph,
+          (None, Mark::None) => '.',
+          (None, _) => '*'
+        });
+      }
+      text.push('\n');
+    }
+
+    text
+  }
+}
+
+impl fmt::Display for TextBoard<'_> {
+  fn fmt(
+    &self,
+    f: &mut fmt::Formatter<'_>
+  ) -> fmt::Result {
+    write!(f, "{}", self.render())
+  }
+}
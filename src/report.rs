@@ -0,0 +1,349 @@
+// Going over a finished game with the engine: how much every move gave away
+// compared to the best one, how accurately each side played, and the game
+// annotated with it
+
+use core::fmt;
+use std::sync::atomic::AtomicBool;
+use std::time::Duration;
+
+use crate::eval::Evaluator;
+use crate::game::Game;
+use crate::search::{self, Limits, Score};
+use crate::{Board, Color, Move};
+
+// Scores beyond ten pawns are all lost or won, mates included
+const CAP: i32 = 1000;
+
+/// Search of every position unless told otherwise, a game of forty moves
+/// takes half a minute at most
+pub fn limits() -> Limits {
+  Limits {
+    depth: Some(12),
+    movetime: Some(Duration::from_millis(300)),
+    ..Limits::default()
+  }
+}
+
+/// How bad a move was, by the centipawns it gave away
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug)]
+pub enum Judgement {
+  Inaccuracy,
+  Mistake,
+  Blunder
+}
+
+impl Judgement {
+  /// 50 centipawns make an inaccuracy, 100 a mistake and 300 a blunder
+  pub fn from_loss(loss: i32) -> Option<Self> {
+    match loss {
+      300.. => Some(Judgement::Blunder),
+      100.. => Some(Judgement::Mistake),
+      50.. => Some(Judgement::Inaccuracy),
+      _ => None
+    }
+  }
+
+  /// `?!`, `?` or `??`
+  pub fn symbol(self) -> &'static str {
+    match self {
+      Judgement::Inaccuracy => "?!",
+      Judgement::Mistake => "?",
+      Judgement::Blunder => "??"
+    }
+  }
+
+  /// Numeric annotation glyph of the symbol
+  pub fn nag(self) -> u8 {
+    match self {
+      Judgement::Inaccuracy => 6,
+      Judgement::Mistake => 2,
+      Judgement::Blunder => 4
+    }
+  }
+}
+
+impl fmt::Display for Judgement {
+  fn fmt(
+    &self,
+    f: &mut fmt::Formatter<'_>
+  ) -> fmt::Result {
+    match self {
+      Judgement::Inaccuracy => write!(f, "Inaccuracy"),
+      Judgement::Mistake => write!(f, "Mistake"),
+      Judgement::Blunder => write!(f, "Blunder")
+    }
+  }
+}
+
+/// What the engine thinks of a move, scores from white's point of view
+#[derive(Clone, Debug)]
+pub struct MoveReport {
+  pub mv:        Move,
+  pub san:       String,
+  pub color:     Color,
+  /// Score after the move, `None` when it ended the game
+  pub eval:      Option<Score>,
+  /// Best move of the position, with its score
+  pub best:      Option<Move>,
+  pub best_san:  Option<String>,
+  pub best_eval: Score,
+  /// Centipawns given away compared to the best move
+  pub loss:      i32,
+  pub judgement: Option<Judgement>,
+  /// From 0 to 100, by the winning chances the move gave away
+  pub accuracy:  f64
+}
+
+impl MoveReport {
+  /// `Mistake, Nf3 was best (+0.35)` for judged moves
+  pub fn verdict(&self) -> Option<String> {
+    let judgement = self.judgement?;
+    Some(match &self.best_san {
+      Some(best) => format!("{}, {} was best ({})", judgement, best, self.best_eval),
+      None => judgement.to_string()
+    })
+  }
+}
+
+/// Every move of a game as the engine saw it
+#[derive(Clone, Debug)]
+pub struct Report {
+  pub moves: Vec<MoveReport>,
+  // Clamped centipawns of every position from white's point of view
+  curve:     Vec<i32>
+}
+
+// Chances of winning from 0 to 100, the curve lichess fitted to its games
+fn winning_chances(cp: i32) -> f64 {
+  50.0 + 50.0 * (2.0 / (1.0 + (-0.00368208 * cp as f64).exp()) - 1.0)
+}
+
+// `1 mistake`, `2 mistakes`
+fn count_of(
+  count: usize,
+  one: &str,
+  many: &str
+) -> String {
+  match count {
+    1 => format!("1 {}", one),
+    _ => format!("{} {}", count, many)
+  }
+}
+
+impl Report {
+  fn of(
+    &self,
+    color: Color
+  ) -> impl Iterator<Item = &MoveReport> {
+    self
+      .moves
+      .iter()
+      .filter(move |report| report.color == color)
+  }
+
+  /// Average accuracy of the side's moves, `None` before it moved
+  pub fn accuracy(
+    &self,
+    color: Color
+  ) -> Option<f64> {
+    let accuracies: Vec<f64> = self.of(color).map(|report| report.accuracy).collect();
+    if accuracies.is_empty() {
+      return None
+    }
+
+    Some(accuracies.iter().sum::<f64>() / accuracies.len() as f64)
+  }
+
+  /// Average centipawns the side's moves gave away
+  pub fn average_loss(
+    &self,
+    color: Color
+  ) -> Option<f64> {
+    let losses: Vec<i32> = self.of(color).map(|report| report.loss).collect();
+    if losses.is_empty() {
+      return None
+    }
+
+    Some(losses.iter().sum::<i32>() as f64 / losses.len() as f64)
+  }
+
+  /// Moves of the side judged that way
+  pub fn count(
+    &self,
+    color: Color,
+    judgement: Judgement
+  ) -> usize {
+    self
+      .of(color)
+      .filter(|report| report.judgement == Some(judgement))
+      .count()
+  }
+
+  /// `White: 87.3% accuracy, 1 inaccuracy, 0 mistakes, 2 blunders`
+  pub fn summary(
+    &self,
+    color: Color
+  ) -> String {
+    let name = color.name();
+    let accuracy = match self.accuracy(color) {
+      Some(accuracy) => accuracy,
+      None => return format!("{}: no moves", name)
+    };
+
+    format!(
+      "{}: {:.1}% accuracy, {}, {}, {}",
+      name,
+      accuracy,
+      count_of(
+        self.count(color, Judgement::Inaccuracy),
+        "inaccuracy",
+        "inaccuracies"
+      ),
+      count_of(self.count(color, Judgement::Mistake), "mistake", "mistakes"),
+      count_of(self.count(color, Judgement::Blunder), "blunder", "blunders")
+    )
+  }
+
+  /// Scores in centipawns from white's point of view, ten pawns at most,
+  /// from the starting position to the last one. Mates count as ten pawns
+  pub fn curve(&self) -> &[i32] { &self.curve }
+
+  /// The game in PGN with `?!`, `?` and `??` as NAGs and the scores and
+  /// better moves as comments, after the game's own comments
+  pub fn to_pgn(
+    &self,
+    game: &Game,
+    tags: &[(&str, String)]
+  ) -> String {
+    let nags: Vec<Option<u8>> = self
+      .moves
+      .iter()
+      .map(|report| report.judgement.map(Judgement::nag))
+      .collect();
+    let comments: Vec<Option<String>> = self
+      .moves
+      .iter()
+      .zip(game.comments())
+      .map(|(report, comment)| {
+        let words: Vec<String> = comment
+          .iter()
+          .cloned()
+          .chain(report.eval.map(|eval| eval.to_string()))
+          .chain(report.verdict())
+          .collect();
+        Some(words.join(" ")).filter(|words| !words.is_empty())
+      })
+      .collect();
+
+    game.to_annotated_pgn(tags, &nags, &comments)
+  }
+}
+
+// Score of the position for the side to move and the best move, scores of
+// positions without moves aren't searched
+fn evaluate(
+  board: &Board,
+  history: &[u64],
+  evaluator: &Evaluator,
+  limits: &Limits,
+  stop: &AtomicBool
+) -> (Option<Score>, i32, Option<Move>) {
+  if board.legal_moves().is_empty() {
+    let cp = if board.is_in_check(board.turn) {
+      -CAP
+    } else {
+      0
+    };
+    return (None, cp, None)
+  }
+
+  let result = search::search(board, history, evaluator, limits, stop, |_| {});
+  (
+    Some(result.score),
+    result.score.centipawns().clamp(-CAP, CAP),
+    result.best_move
+  )
+}
+
+/// Searches every position of the game, telling `progress` how many are
+/// done out of how many. Once stopped the searches left return right away,
+/// the report is of no use then
+pub fn analyse(
+  game: &Game,
+  evaluator: &Evaluator,
+  limits: &Limits,
+  stop: &AtomicBool,
+  mut progress: impl FnMut(usize, usize)
+) -> Report {
+  let total = game.moves().len() + 1;
+  let mut board = game.start().clone();
+  let mut keys = vec![board.key()];
+  let mut positions = Vec::with_capacity(total);
+
+  for index in 0..total {
+    // Earlier positions since the last pawn move or capture
+    let recent = (board.halfmove_clock as usize + 1).min(keys.len());
+    positions.push((
+      board.clone(),
+      evaluate(
+        &board,
+        &keys[keys.len() - recent..],
+        evaluator,
+        limits,
+        stop
+      )
+    ));
+    progress(index + 1, total);
+
+    if let Some(&mv) = game.moves().get(index) {
+      board.apply_move(mv).expect("Moves are legal");
+      keys.push(board.key());
+    }
+  }
+
+  let curve = positions
+    .iter()
+    .map(|(board, (_, cp, _))| match board.turn {
+      Color::White => *cp,
+      Color::Black => -cp
+    })
+    .collect();
+
+  let moves = positions
+    .windows(2)
+    .zip(game.moves())
+    .map(|(pair, &mv)| {
+      let (before, (best_score, best_cp, best)) = &pair[0];
+      let (after, (score, cp, _)) = &pair[1];
+
+      // From the point of view of the side that moved
+      let played_cp = -cp;
+      let loss = if *best == Some(mv) {
+        0
+      } else {
+        (best_cp - played_cp).max(0)
+      };
+      // Also fitted by lichess, close to 100 for a move that keeps the chances
+      let dropped = winning_chances(*best_cp) - winning_chances(best_cp - loss);
+      let accuracy = 103.1668 * (-0.04354 * dropped).exp() - 3.1669;
+
+      MoveReport {
+        mv,
+        san: before.to_san(mv),
+        color: before.turn,
+        eval: score.map(|score| score.for_white(after)),
+        best: *best,
+        best_san: best.map(|best| before.to_san(best)),
+        best_eval: best_score.unwrap_or(Score::Cp(*best_cp)).for_white(before),
+        loss,
+        judgement: Judgement::from_loss(loss),
+        accuracy: accuracy.clamp(0.0, 100.0)
+      }
+    })
+    .collect();
+
+  Report {
+    moves,
+    curve
+  }
+}
@@ -0,0 +1,508 @@
+use core::fmt;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+
+use crate::eval::{EvalState, Evaluator};
+use crate::{Board, Color, Move, Pieces};
+
+// Iterative deepening alpha-beta with a quiescence search over captures that
+// don't lose material by static exchange evaluation.
+
+pub const MATE: i32 = 30_000;
+const INFINITY: i32 = 32_000;
+const MAX_PLY: usize = 64;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Score {
+  // Centipawns from the side to move's point of view
+  Cp(i32),
+  // Moves until mate, negative when the side to move gets mated
+  Mate(i32)
+}
+
+impl Score {
+  fn from_internal(score: i32) -> Self {
+    if score > MATE - MAX_PLY as i32 {
+      Score::Mate((MATE - score + 1) / 2)
+    } else if score < -MATE + MAX_PLY as i32 {
+      Score::Mate(-(MATE + score + 1) / 2)
+    } else {
+      Score::Cp(score)
+    }
+  }
+
+  /// Mates as very large scores, so scores can be compared and subtracted
+  pub fn centipawns(&self) -> i32 {
+    match *self {
+      Score::Cp(cp) => cp,
+      Score::Mate(moves) if moves > 0 => MATE - 2 * moves + 1,
+      Score::Mate(moves) => -MATE - 2 * moves
+    }
+  }
+
+  /// Score of the board's side to move from white's point of view, the way
+  /// evaluation bars show it
+  pub fn for_white(
+    self,
+    board: &Board
+  ) -> Self {
+    match (board.turn, self) {
+      (Color::White, _) => self,
+      (Color::Black, Score::Cp(cp)) => Score::Cp(-cp),
+      (Color::Black, Score::Mate(moves)) => Score::Mate(-moves)
+    }
+  }
+}
+
+impl fmt::Display for Score {
+  fn fmt(
+    &self,
+    f: &mut fmt::Formatter<'_>
+  ) -> fmt::Result {
+    match self {
+      Score::Cp(cp) => write!(f, "{:+.2}", *cp as f64 / 100.0),
+      Score::Mate(moves) if *moves < 0 => write!(f, "-#{}", -moves),
+      Score::Mate(moves) => write!(f, "#{}", moves)
+    }
+  }
+}
+
+/// When to stop searching, everything that isn't set is unlimited
+#[derive(Clone, Default, Debug)]
+pub struct Limits {
+  pub depth:       Option<u8>,
+  pub nodes:       Option<u64>,
+  pub movetime:    Option<Duration>,
+  // Clock of the side to move
+  pub time:        Option<Duration>,
+  pub increment:   Duration,
+  pub moves_to_go: Option<u32>,
+  // Best lines to search, each one excluding the first moves of the better ones.
+  // Zero is the same as one
+  pub multi_pv:    usize
+}
+
+impl Limits {
+  // Time to spend on the move
+  fn budget(&self) -> Option<Duration> {
+    let from_clock = self.time.map(|time| {
+      let share = time / self.moves_to_go.unwrap_or(30).max(1) + self.increment * 3 / 4;
+      // Keep a margin for communication overhead
+      share.min(time.saturating_sub(Duration::from_millis(50)) / 2 + Duration::from_millis(1))
+    });
+
+    match (self.movetime, from_clock) {
+      (Some(a), Some(b)) => Some(a.min(b)),
+      (a, b) => a.or(b)
+    }
+  }
+}
+
+/// Progress report after every completed iteration, once per line when
+/// searching several
+#[derive(Clone, Debug)]
+pub struct Info {
+  pub depth:   u8,
+  // Rank of the line, 1 for the best one
+  pub multipv: usize,
+  pub score:   Score,
+  pub nodes:   u64,
+  pub time:    Duration,
+  pub pv:      Vec<Move>
+}
+
+impl Info {
+  pub fn nps(&self) -> u64 { (self.nodes as f64 / self.time.as_secs_f64().max(0.001)) as u64 }
+}
+
+#[derive(Clone, Debug)]
+pub struct SearchResult {
+  pub best_move: Option<Move>,
+  pub score:     Score,
+  pub depth:     u8,
+  pub nodes:     u64,
+  pub pv:        Vec<Move>
+}
+
+struct Searcher<'a> {
+  state:    EvalState<'a>,
+  stop:     &'a AtomicBool,
+  limits:   &'a Limits,
+  deadline: Option<Instant>,
+  nodes:    u64,
+  stopped:  bool,
+  // Keys of the positions leading to the current node
+  history:  Vec<u64>,
+  killers:  [[Option<Move>; 2]; MAX_PLY],
+  pv_hint:  Vec<Move>,
+  // Root moves left out, the first moves of the better lines
+  excluded: Vec<Move>
+}
+
+impl<'a> Searcher<'a> {
+  fn should_stop(&mut self) -> bool {
+    if !self.stopped && self.nodes & 1023 == 0 {
+      self.stopped = self.stop.load(Ordering::Relaxed)
+        || self
+          .deadline
+          .is_some_and(|deadline| Instant::now() >= deadline);
+    }
+
+    if self.limits.nodes.is_some_and(|nodes| self.nodes >= nodes) {
+      self.stopped = true;
+    }
+
+    self.stopped
+  }
+
+  // Pseudo-legal moves of the side to move, the best candidates first
+  fn ordered_moves(
+    &self,
+    board: &Board,
+    ply: usize,
+    first: Option<Move>,
+    captures_only: bool
+  ) -> Vec<Move> {
+    let mut moves: Vec<(i32, Move)> = Vec::new();
+
+    for (coord, piece) in board.pieces.iter() {
+      if piece.color != board.turn {
+        continue
+      }
+
+      for target in board
+        .generate_pseudo_legal_moves(*coord)
+        .expect("Coordinates on the board are valid")
+      {
+        let victim = match board.get_piece(&target) {
+          Some(victim) => Some(victim.breed),
+          None if piece.breed == Pieces::Pawn && coord.col != target.col => Some(Pieces::Pawn),
+          None => None
+        };
+        let promotions: &[Option<Pieces>] =
+          if piece.breed == Pieces::Pawn && (target.row == 0 || target.row == 7) {
+            // Underpromotions are left to the full width search
+            if captures_only {
+              &[Some(Pieces::Queen)]
+            } else {
+              &[
+                Some(Pieces::Queen),
+                Some(Pieces::Rook),
+                Some(Pieces::Bishop),
+                Some(Pieces::Knight)
+              ]
+            }
+          } else {
+            &[None]
+          };
+
+        for &promotion in promotions {
+          if captures_only && victim.is_none() && promotion.is_none() {
+            continue
+          }
+
+          let mv = Move {
+            start: *coord,
+            target,
+            promotion
+          };
+          let underpromotion = promotion.is_some_and(|breed| breed != Pieces::Queen);
+
+          let score = if Some(mv) == first {
+            1_000_000
+          } else if underpromotion {
+            // Rarely better than a queen, tried after the quiet moves
+            -1
+          } else if let Some(victim) = victim {
+            // Most valuable victim, least valuable attacker
+            100_000 + victim.value() * 10 - piece.breed.value().min(1000)
+          } else if promotion.is_some() {
+            90_000
+          } else if self.killers[ply].contains(&Some(mv)) {
+            50_000
+          } else {
+            0
+          };
+
+          moves.push((score, mv));
+        }
+      }
+    }
+
+    // Keep the order stable between runs
+    moves.sort_by_key(|(score, mv)| {
+      (
+        -score,
+        mv.start.row,
+        mv.start.col,
+        mv.target.row,
+        mv.target.col,
+        mv.promotion.map(|breed| -breed.value())
+      )
+    });
+    moves.into_iter().map(|(_, mv)| mv).collect()
+  }
+
+  fn is_draw(
+    &self,
+    board: &Board
+  ) -> bool {
+    if board.halfmove_clock >= 100 || board.is_insufficient_material() {
+      return true
+    }
+
+    // A single repetition is enough inside the search
+    let key = self.history.last().expect("History is never empty");
+    self
+      .history
+      .iter()
+      .rev()
+      .skip(1)
+      .take(board.halfmove_clock as usize)
+      .any(|other| other == key)
+  }
+
+  fn quiescence(
+    &mut self,
+    board: &mut Board,
+    mut alpha: i32,
+    beta: i32,
+    ply: usize
+  ) -> i32 {
+    self.nodes += 1;
+
+    let stand_pat = self.state.evaluate(board);
+    if stand_pat >= beta || ply >= MAX_PLY - 1 {
+      return stand_pat
+    }
+    alpha = alpha.max(stand_pat);
+
+    for mv in self.ordered_moves(board, ply, None, true) {
+      if !board.see_ge(mv, 0) {
+        continue
+      }
+
+      let color = board.turn;
+      let undo = self
+        .state
+        .make_move(board, mv)
+        .expect("Moves start on a piece");
+      if board.is_in_check(color) {
+        self.state.unmake_move(board, undo);
+        continue
+      }
+
+      let score = -self.quiescence(board, -beta, -alpha, ply + 1);
+      self.state.unmake_move(board, undo);
+
+      if self.should_stop() {
+        return 0
+      }
+
+      if score >= beta {
+        return score
+      }
+      alpha = alpha.max(score);
+    }
+
+    alpha
+  }
+
+  fn alpha_beta(
+    &mut self,
+    board: &mut Board,
+    mut depth: u8,
+    mut alpha: i32,
+    beta: i32,
+    ply: usize,
+    pv: &mut Vec<Move>
+  ) -> i32 {
+    pv.clear();
+
+    if ply > 0 && self.is_draw(board) {
+      return 0
+    }
+
+    let color = board.turn;
+    let in_check = board.is_in_check(color);
+
+    // Don't stop searching while in check
+    if in_check && ply < MAX_PLY - 1 {
+      depth += 1;
+    }
+
+    if depth == 0 || ply >= MAX_PLY - 1 {
+      return self.quiescence(board, alpha, beta, ply)
+    }
+
+    self.nodes += 1;
+
+    let mut best = -INFINITY;
+    let mut child_pv = Vec::new();
+
+    // Moves of the previous principal variation are tried first
+    let first = self.pv_hint.get(ply).copied();
+
+    for mv in self.ordered_moves(board, ply, first, false) {
+      if ply == 0 && self.excluded.contains(&mv) {
+        continue
+      }
+
+      let undo = self
+        .state
+        .make_move(board, mv)
+        .expect("Moves start on a piece");
+      if board.is_in_check(color) {
+        self.state.unmake_move(board, undo);
+        continue
+      }
+
+      self.history.push(board.key());
+      let score = -self.alpha_beta(board, depth - 1, -beta, -alpha, ply + 1, &mut child_pv);
+      self.history.pop();
+      let capture = undo.squares.iter().any(|(coord, piece)| {
+        *coord != mv.start && piece.is_some_and(|piece| piece.color != color)
+      });
+      self.state.unmake_move(board, undo);
+
+      if self.should_stop() {
+        return 0
+      }
+
+      if score > best {
+        best = score;
+
+        if score > alpha {
+          alpha = score;
+          pv.clear();
+          pv.push(mv);
+          pv.extend_from_slice(&child_pv);
+        }
+      }
+
+      if score >= beta {
+        if !capture && self.killers[ply][0] != Some(mv) {
+          self.killers[ply] = [Some(mv), self.killers[ply][0]];
+        }
+        break
+      }
+    }
+
+    if best == -INFINITY {
+      // No legal moves
+      return if in_check { -MATE + ply as i32 } else { 0 }
+    }
+
+    best
+  }
+}
+
+/// Searches the position, `history` holds the keys of the earlier positions
+/// of the game for repetition detection and `info` is called after every
+/// completed iteration
+pub fn search(
+  board: &Board,
+  history: &[u64],
+  evaluator: &Evaluator,
+  limits: &Limits,
+  stop: &AtomicBool,
+  mut info: impl FnMut(&Info)
+) -> SearchResult {
+  let start = Instant::now();
+  let mut board = board.clone();
+  let mut history = history.to_vec();
+  if history.last() != Some(&board.key()) {
+    history.push(board.key());
+  }
+
+  let mut searcher = Searcher {
+    state: EvalState::new(evaluator, &board),
+    stop,
+    limits,
+    deadline: limits.budget().map(|budget| start + budget),
+    nodes: 0,
+    stopped: false,
+    history,
+    killers: [[None; 2]; MAX_PLY],
+    pv_hint: Vec::new(),
+    excluded: Vec::new()
+  };
+
+  let legal = board.legal_moves();
+  let mut result = SearchResult {
+    best_move: legal.first().copied(),
+    score:     Score::Cp(0),
+    depth:     0,
+    nodes:     0,
+    pv:        Vec::new()
+  };
+
+  if legal.len() <= 1 && (limits.time.is_some() || limits.movetime.is_some()) {
+    // Nothing to think about
+    return result
+  }
+
+  let max_depth = limits
+    .depth
+    .unwrap_or(MAX_PLY as u8 - 1)
+    .clamp(1, MAX_PLY as u8 - 1);
+  let lines = limits.multi_pv.clamp(1, legal.len().max(1));
+  // Principal variations of the previous iteration, tried first again
+  let mut hints: Vec<Vec<Move>> = vec![Vec::new(); lines];
+  let mut pv = Vec::new();
+
+  'deepening: for depth in 1..=max_depth {
+    searcher.excluded.clear();
+
+    for (line, hint) in hints.iter_mut().enumerate() {
+      searcher.pv_hint = hint.clone();
+      let score = searcher.alpha_beta(&mut board, depth, -INFINITY, INFINITY, 0, &mut pv);
+
+      // An interrupted iteration is only trusted for its first move
+      if searcher.stopped {
+        if depth > 1 && line == 0 {
+          result.best_move = pv.first().copied().or(result.best_move);
+        }
+        break 'deepening
+      }
+
+      let first = match pv.first() {
+        Some(&first) => first,
+        None => break
+      };
+      searcher.excluded.push(first);
+      *hint = pv.clone();
+
+      if line == 0 {
+        result.best_move = Some(first);
+        result.score = Score::from_internal(score);
+        result.depth = depth;
+        result.pv = pv.clone();
+        result.nodes = searcher.nodes;
+      }
+
+      info(&Info {
+        depth,
+        multipv: line + 1,
+        score: Score::from_internal(score),
+        nodes: searcher.nodes,
+        time: start.elapsed(),
+        pv: pv.clone()
+      });
+    }
+
+    if lines == 1 && matches!(result.score, Score::Mate(_)) {
+      break
+    }
+
+    // Another iteration would hardly finish in the remaining time
+    if let Some(budget) = limits.budget() {
+      if start.elapsed() > budget / 2 {
+        break
+      }
+    }
+  }
+
+  result.nodes = searcher.nodes;
+  result
+}
@@ -0,0 +1,183 @@
+use std::collections::HashMap;
+
+use crate::attacks::{attackers, slides_along, step, DIRECTIONS};
+use crate::{Board, Color, Coordinate, Move, Piece, Pieces};
+
+// Static exchange evaluation: plays out every capture on the target square,
+// always recapturing with the least valuable piece, and lets each side stop
+// capturing once it would lose material. Sliders hidden behind a piece that
+// has already captured join in (x-rays), since attacks are recomputed on an
+// occupancy that loses a piece after every capture.
+
+const ORDER: [Pieces; 6] = [
+  Pieces::Pawn,
+  Pieces::Knight,
+  Pieces::Bishop,
+  Pieces::Rook,
+  Pieces::Queen,
+  Pieces::King
+];
+
+impl Board {
+  // Pieces pinned to their own king, with the direction from the king towards the pinner
+  fn pins(&self) -> HashMap<Coordinate, (i8, i8)> {
+    let mut pins = HashMap::new();
+
+    for color in [Color::White, Color::Black] {
+      let king = match self.get_king_coord(color) {
+        Some(king) => king,
+        None => continue
+      };
+
+      for delta in DIRECTIONS {
+        let mut pinned: Option<Coordinate> = None;
+        let mut current = step(king, delta);
+
+        while let Some(coord) = current {
+          if let Some(piece) = self.get_piece(&coord) {
+            if piece.color == color {
+              if pinned.is_some() {
+                break
+              }
+              pinned = Some(coord);
+            } else {
+              if let (true, Some(pinned)) = (slides_along(piece.breed, delta), pinned) {
+                pins.insert(pinned, delta);
+              }
+              break
+            }
+          }
+
+          current = step(coord, delta);
+        }
+      }
+    }
+
+    pins
+  }
+
+  // Least valuable piece of the color attacking the target on the given occupancy
+  fn least_valuable_attacker(
+    &self,
+    occupancy: &HashMap<Coordinate, Piece>,
+    target: Coordinate,
+    color: Color,
+    pins: &HashMap<Coordinate, (i8, i8)>
+  ) -> Option<(Coordinate, Piece)> {
+    attackers(occupancy, target, color)
+      .into_iter()
+      .filter(|(coord, _)| match pins.get(coord) {
+        // A pinned piece can only capture along the pin
+        Some(&delta) => self
+          .get_king_coord(color)
+          .is_some_and(|king| is_on_ray(king, delta, target)),
+        None => true
+      })
+      .min_by_key(|(_, piece)| ORDER.iter().position(|&breed| breed == piece.breed))
+  }
+
+  /// Material balance of the capture sequence started by the move, in centipawns
+  pub fn see(
+    &self,
+    mv: Move
+  ) -> i32 {
+    self.see_with(mv, false)
+  }
+
+  /// Whether the capture sequence started by the move gains at least `threshold`
+  pub fn see_ge(
+    &self,
+    mv: Move,
+    threshold: i32
+  ) -> bool {
+    self.see(mv) >= threshold
+  }
+
+  /// Same as `see`, with the option to forbid pinned pieces to leave their pin
+  pub fn see_with(
+    &self,
+    mv: Move,
+    respect_pins: bool
+  ) -> i32 {
+    let attacker = match self.get_piece(&mv.start) {
+      Some(piece) => piece,
+      None => return 0
+    };
+
+    let pins = if respect_pins {
+      self.pins()
+    } else {
+      HashMap::new()
+    };
+
+    let mut occupancy = self.pieces.clone();
+
+    // En passant captures a pawn that isn't on the target square
+    let captured = match self.get_piece(&mv.target) {
+      Some(piece) => piece.breed.value(),
+      None if attacker.breed == Pieces::Pawn && Some(mv.target) == self.en_passant_target_sq => {
+        occupancy.remove(&coord!(mv.start.row, mv.target.col));
+        Pieces::Pawn.value()
+      },
+      None => 0
+    };
+
+    let mut gains = vec![captured];
+    let mut on_square = attacker;
+    let mut color = attacker.color.opposite();
+
+    occupancy.remove(&mv.start);
+    occupancy.insert(mv.target, attacker);
+
+    while let Some((coord, piece)) =
+      self.least_valuable_attacker(&occupancy, mv.target, color, &pins)
+    {
+      // The king can't capture into a square that is still defended
+      if piece.breed == Pieces::King {
+        let mut after = occupancy.clone();
+        after.remove(&coord);
+        after.insert(mv.target, piece);
+
+        if self
+          .least_valuable_attacker(&after, mv.target, color.opposite(), &pins)
+          .is_some()
+        {
+          break
+        }
+      }
+
+      gains.push(on_square.breed.value() - gains.last().expect("Gains are never empty"));
+
+      occupancy.remove(&coord);
+      occupancy.insert(mv.target, piece);
+      on_square = piece;
+      color = color.opposite();
+    }
+
+    // Each side may stop capturing when continuing would lose material
+    while gains.len() > 1 {
+      let last = gains.pop().expect("Gains are never empty");
+      let previous = gains.last_mut().expect("Gains are never empty");
+      *previous = -(-*previous).max(last);
+    }
+
+    gains[0]
+  }
+}
+
+fn is_on_ray(
+  origin: Coordinate,
+  delta: (i8, i8),
+  target: Coordinate
+) -> bool {
+  let mut current = step(origin, delta);
+
+  while let Some(coord) = current {
+    if coord == target {
+      return true
+    }
+    current = step(coord, delta);
+  }
+
+  false
+}
@@ -0,0 +1,820 @@
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{mpsc, Arc};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::eval::{EvalParams, Evaluator};
+use crate::game::{Game, GameResult, Outcome};
+use crate::search::{self, Limits, Score};
+use crate::uci::UciEngine;
+use crate::{Board, Color, Move};
+
+// Plays two engine configurations against each other and decides with a
+// sequential probability ratio test whether the first one is stronger.
+
+// Splits `key=value,key=value` into pairs
+fn pairs(spec: &str) -> Result<Vec<(&str, &str)>, String> {
+  spec
+    .split(',')
+    .filter(|part| !part.trim().is_empty())
+    .map(|part| {
+      part
+        .split_once('=')
+        .map(|(key, value)| (key.trim(), value.trim()))
+        .ok_or_else(|| format!("Expected key=value, got `{}`", part))
+    })
+    .collect()
+}
+
+fn number<T: FromStr>(
+  key: &str,
+  value: &str
+) -> Result<T, String> {
+  value
+    .parse()
+    .map_err(|_| format!("Invalid value `{}` for {}", value, key))
+}
+
+#[derive(Clone)]
+pub enum EngineKind {
+  InProcess(Arc<Evaluator>),
+  Uci {
+    command: String,
+    args:    Vec<String>,
+    options: Vec<(String, String)>
+  }
+}
+
+#[derive(Clone)]
+pub struct EngineConfig {
+  pub name:  String,
+  pub kind:  EngineKind,
+  pub depth: Option<u8>,
+  pub nodes: Option<u64>
+}
+
+impl FromStr for EngineConfig {
+  type Err = String;
+
+  /// `name=new,params=new.cfg,depth=6` for our own search, or
+  /// `cmd=./engine,arg=--flag,option.Hash=64` for a UCI subprocess
+  fn from_str(spec: &str) -> Result<Self, Self::Err> {
+    let mut name = None;
+    let mut command = None;
+    let mut args = Vec::new();
+    let mut options = Vec::new();
+    let mut evaluator = None;
+    let mut depth = None;
+    let mut nodes = None;
+
+    for (key, value) in pairs(spec)? {
+      match key {
+        "name" => name = Some(value.to_string()),
+        "cmd" => command = Some(value.to_string()),
+        "arg" => args.push(value.to_string()),
+        "network" => {
+          evaluator = Some(
+            Evaluator::load(Some(value.as_ref())).map_err(|err| format!("{}: {}", value, err))?
+          )
+        },
+        "params" => {
+          evaluator = Some(Evaluator::Handcrafted(
+            EvalParams::load(value).map_err(|err| format!("{}: {}", value, err))?
+          ))
+        },
+        "depth" => depth = Some(number(key, value)?),
+        "nodes" => nodes = Some(number(key, value)?),
+        _ => match key.strip_prefix("option.") {
+          Some(option) => options.push((option.to_string(), value.to_string())),
+          None => return Err(format!("Unknown engine setting `{}`", key))
+        }
+      }
+    }
+
+    let kind = match (command, evaluator) {
+      (Some(_), Some(_)) => return Err(String::from("A UCI engine can't use our evaluation")),
+      (Some(command), None) => EngineKind::Uci {
+        command,
+        args,
+        options
+      },
+      (None, evaluator) => EngineKind::InProcess(Arc::new(evaluator.unwrap_or_default()))
+    };
+
+    Ok(EngineConfig {
+      name: name.unwrap_or_else(|| String::from("chyes")),
+      kind,
+      depth,
+      nodes
+    })
+  }
+}
+
+impl EngineConfig {
+  pub fn create(&self) -> Result<Box<dyn Player>, String> {
+    match &self.kind {
+      EngineKind::InProcess(evaluator) => Ok(Box::new(SearchPlayer {
+        name:      self.name.clone(),
+        evaluator: evaluator.clone(),
+        stop:      AtomicBool::new(false)
+      })),
+      EngineKind::Uci {
+        command,
+        args,
+        options
+      } => {
+        let mut engine = UciEngine::spawn(command, args)?;
+        for (name, value) in options {
+          engine.set_option(name, value)?;
+        }
+        engine.wait_ready()?;
+
+        Ok(Box::new(UciPlayer {
+          name: self.name.clone(),
+          engine
+        }))
+      }
+    }
+  }
+}
+
+/// One side of a game
+pub trait Player: Send {
+  fn name(&self) -> &str;
+
+  fn new_game(&mut self) -> Result<(), String>;
+
+  /// Chooses a move, with the score from the side to move's point of view
+  fn go(
+    &mut self,
+    game: &Game,
+    limits: &Limits,
+    timeout: Duration
+  ) -> Result<(Move, Option<Score>), String>;
+}
+
+struct SearchPlayer {
+  name:      String,
+  evaluator: Arc<Evaluator>,
+  stop:      AtomicBool
+}
+
+impl Player for SearchPlayer {
+  fn name(&self) -> &str { &self.name }
+
+  fn new_game(&mut self) -> Result<(), String> { Ok(()) }
+
+  fn go(
+    &mut self,
+    game: &Game,
+    limits: &Limits,
+    _timeout: Duration
+  ) -> Result<(Move, Option<Score>), String> {
+    let result = search::search(
+      game.board(),
+      game.history(),
+      &self.evaluator,
+      limits,
+      &self.stop,
+      |_| {}
+    );
+
+    match result.best_move {
+      Some(mv) => Ok((mv, Some(result.score))),
+      None => Err(String::from("No legal moves"))
+    }
+  }
+}
+
+struct UciPlayer {
+  name:   String,
+  engine: UciEngine
+}
+
+impl Player for UciPlayer {
+  fn name(&self) -> &str { &self.name }
+
+  fn new_game(&mut self) -> Result<(), String> { self.engine.new_game() }
+
+  fn go(
+    &mut self,
+    game: &Game,
+    limits: &Limits,
+    timeout: Duration
+  ) -> Result<(Move, Option<Score>), String> {
+    self.engine.go(game.start(), game.moves(), limits, timeout)
+  }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct TimeControl {
+  pub base:      Duration,
+  pub increment: Duration
+}
+
+impl FromStr for TimeControl {
+  type Err = String;
+
+  /// Seconds, `60` or `10+0.1`
+  fn from_str(text: &str) -> Result<Self, Self::Err> {
+    let (base, increment) = text.split_once('+').unwrap_or((text, "0"));
+    let seconds = |value: &str| {
+      value
+        .trim()
+        .parse::<f64>()
+        .ok()
+        .filter(|seconds| *seconds >= 0.0)
+        .map(Duration::from_secs_f64)
+        .ok_or_else(|| format!("Invalid time control `{}`", text))
+    };
+
+    Ok(TimeControl {
+      base:      seconds(base)?,
+      increment: seconds(increment)?
+    })
+  }
+}
+
+/// Draw once both engines have scored within `score` for `moves` moves in a
+/// row, from move `move_number` on
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct DrawAdjudication {
+  pub move_number: u16,
+  pub moves:       u16,
+  pub score:       i32
+}
+
+impl FromStr for DrawAdjudication {
+  type Err = String;
+
+  /// `movenumber=40,movecount=8,score=10`
+  fn from_str(spec: &str) -> Result<Self, Self::Err> {
+    let mut draw = DrawAdjudication {
+      move_number: 40,
+      moves:       8,
+      score:       10
+    };
+
+    for (key, value) in pairs(spec)? {
+      match key {
+        "movenumber" => draw.move_number = number(key, value)?,
+        "movecount" => draw.moves = number(key, value)?,
+        "score" => draw.score = number(key, value)?,
+        _ => return Err(format!("Unknown draw adjudication setting `{}`", key))
+      }
+    }
+
+    Ok(draw)
+  }
+}
+
+/// Loss once both engines have scored beyond `score` against the same side
+/// for `moves` moves in a row
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct ResignAdjudication {
+  pub moves: u16,
+  pub score: i32
+}
+
+impl FromStr for ResignAdjudication {
+  type Err = String;
+
+  /// `movecount=3,score=600`
+  fn from_str(spec: &str) -> Result<Self, Self::Err> {
+    let mut resign = ResignAdjudication {
+      moves: 3,
+      score: 600
+    };
+
+    for (key, value) in pairs(spec)? {
+      match key {
+        "movecount" => resign.moves = number(key, value)?,
+        "score" => resign.score = number(key, value)?,
+        _ => return Err(format!("Unknown resign adjudication setting `{}`", key))
+      }
+    }
+
+    Ok(resign)
+  }
+}
+
+/// Ends games early when the engines agree on the result
+#[derive(Clone, Default, Debug)]
+pub struct Adjudication {
+  pub draw:      Option<DrawAdjudication>,
+  pub resign:    Option<ResignAdjudication>,
+  // Draw after this many moves
+  pub max_moves: Option<u16>
+}
+
+impl Adjudication {
+  fn decide(
+    &self,
+    game: &Game,
+    // Scores after every ply from white's point of view
+    scores: &[Option<i32>]
+  ) -> Option<(GameResult, String)> {
+    let plies = scores.len();
+    let move_number = game.board().fullmove_number;
+
+    if let Some(draw) = self.draw {
+      let window = 2 * draw.moves as usize;
+
+      if move_number >= draw.move_number
+        && plies >= window
+        && scores[plies - window..]
+          .iter()
+          .all(|score| score.is_some_and(|score| score.abs() <= draw.score))
+      {
+        return Some((GameResult::Draw, String::from("Draw by adjudication")))
+      }
+    }
+
+    if let Some(resign) = self.resign {
+      let window = 2 * resign.moves as usize;
+
+      for (color, sign) in [(Color::White, 1), (Color::Black, -1)] {
+        if plies >= window
+          && scores[plies - window..]
+            .iter()
+            .all(|score| score.is_some_and(|score| sign * score <= -resign.score))
+        {
+          return Some((
+            GameResult::win(color.opposite()),
+            format!("{} resigns", color.name())
+          ))
+        }
+      }
+    }
+
+    match self.max_moves {
+      Some(max_moves) if plies >= 2 * max_moves as usize => {
+        Some((GameResult::Draw, String::from("Draw by move limit")))
+      },
+      _ => None
+    }
+  }
+}
+
+/// Starting positions, one FEN per line; EPD lines without clocks are accepted
+pub fn load_openings(path: impl AsRef<Path>) -> Result<Vec<Board>, String> {
+  let path = path.as_ref();
+  let text = fs::read_to_string(path).map_err(|err| format!("{}: {}", path.display(), err))?;
+  let mut openings = Vec::new();
+
+  for (number, line) in text.lines().enumerate() {
+    let line = line.split(';').next().unwrap_or("").trim();
+
+    if line.is_empty() || line.starts_with('#') {
+      continue
+    }
+
+    let mut fields: Vec<&str> = line.split_whitespace().take(6).collect();
+    if fields.len() == 4 {
+      fields.extend(["0", "1"]);
+    }
+
+    let mut board = Board::new();
+    board
+      .load_fen(&fields.join(" "))
+      .map_err(|err| format!("{} line {}: {}", path.display(), number + 1, err))?;
+    openings.push(board);
+  }
+
+  if openings.is_empty() {
+    return Err(format!("{}: no positions", path.display()))
+  }
+
+  Ok(openings)
+}
+
+/// Wins, draws and losses of the first engine
+#[derive(Clone, Copy, Default, PartialEq, Eq, Debug)]
+pub struct Stats {
+  pub wins:   u32,
+  pub draws:  u32,
+  pub losses: u32
+}
+
+// Expected score of the stronger side by the logistic Elo model
+fn expected_score(elo: f64) -> f64 { 1.0 / (1.0 + 10f64.powf(-elo / 400.0)) }
+
+fn elo_of_score(score: f64) -> f64 { 400.0 * (score / (1.0 - score)).log10() }
+
+impl Stats {
+  pub fn games(&self) -> u32 { self.wins + self.draws + self.losses }
+
+  pub fn score(&self) -> f64 {
+    if self.games() == 0 {
+      return 0.5
+    }
+
+    (self.wins as f64 + self.draws as f64 / 2.0) / self.games() as f64
+  }
+
+  // Variance of a single game result
+  fn variance(&self) -> f64 {
+    let games = self.games() as f64;
+    let score = self.score();
+
+    (self.wins as f64 * (1.0 - score).powi(2)
+      + self.draws as f64 * (0.5 - score).powi(2)
+      + self.losses as f64 * score.powi(2))
+      / games
+  }
+
+  /// Elo difference with the half width of its 95% confidence interval
+  pub fn elo(&self) -> Option<(f64, f64)> {
+    let score = self.score();
+
+    if self.games() == 0 || score <= 0.0 || score >= 1.0 {
+      return None
+    }
+
+    let deviation = (self.variance() / self.games() as f64).sqrt();
+    let low = elo_of_score((score - 1.96 * deviation).max(0.0001));
+    let high = elo_of_score((score + 1.96 * deviation).min(0.9999));
+
+    Some((elo_of_score(score), (high - low) / 2.0))
+  }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum SprtResult {
+  // The first engine is no stronger than elo0
+  AcceptH0,
+  // The first engine is at least elo1 stronger
+  AcceptH1
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Sprt {
+  pub elo0:  f64,
+  pub elo1:  f64,
+  pub alpha: f64,
+  pub beta:  f64
+}
+
+impl Default for Sprt {
+  fn default() -> Self {
+    Sprt {
+      elo0:  0.0,
+      elo1:  5.0,
+      alpha: 0.05,
+      beta:  0.05
+    }
+  }
+}
+
+impl FromStr for Sprt {
+  type Err = String;
+
+  /// `elo0=0,elo1=5,alpha=0.05,beta=0.05`, missing values keep their default
+  fn from_str(spec: &str) -> Result<Self, Self::Err> {
+    let mut sprt = Sprt::default();
+
+    for (key, value) in pairs(spec)? {
+      match key {
+        "elo0" => sprt.elo0 = number(key, value)?,
+        "elo1" => sprt.elo1 = number(key, value)?,
+        "alpha" => sprt.alpha = number(key, value)?,
+        "beta" => sprt.beta = number(key, value)?,
+        _ => return Err(format!("Unknown SPRT setting `{}`", key))
+      }
+    }
+
+    if sprt.elo0 >= sprt.elo1 {
+      return Err(String::from("elo0 has to be below elo1"))
+    }
+    if !(0.0..0.5).contains(&sprt.alpha)
+      || !(0.0..0.5).contains(&sprt.beta)
+      || sprt.alpha == 0.0
+      || sprt.beta == 0.0
+    {
+      return Err(String::from("alpha and beta have to be between 0 and 0.5"))
+    }
+
+    Ok(sprt)
+  }
+}
+
+impl Sprt {
+  /// Lower and upper log-likelihood ratio bounds
+  pub fn bounds(&self) -> (f64, f64) {
+    (
+      (self.beta / (1.0 - self.alpha)).ln(),
+      ((1.0 - self.beta) / self.alpha).ln()
+    )
+  }
+
+  /// Log-likelihood ratio of elo1 against elo0, with the trinomial
+  /// approximation of the game results
+  pub fn llr(
+    &self,
+    stats: &Stats
+  ) -> f64 {
+    let games = stats.games() as f64;
+    let variance = stats.variance();
+
+    if stats.games() == 0 || variance <= 0.0 {
+      return 0.0
+    }
+
+    let score0 = expected_score(self.elo0);
+    let score1 = expected_score(self.elo1);
+
+    (score1 - score0) * (2.0 * stats.score() - score0 - score1) / (2.0 * variance / games)
+  }
+
+  pub fn result(
+    &self,
+    stats: &Stats
+  ) -> Option<SprtResult> {
+    let (lower, upper) = self.bounds();
+    let llr = self.llr(stats);
+
+    if llr >= upper {
+      Some(SprtResult::AcceptH1)
+    } else if llr <= lower {
+      Some(SprtResult::AcceptH0)
+    } else {
+      None
+    }
+  }
+}
+
+pub struct MatchConfig {
+  pub engines:      [EngineConfig; 2],
+  // Every opening is played twice, with swapped colors
+  pub openings:     Vec<Board>,
+  pub games:        usize,
+  pub time_control: Option<TimeControl>,
+  pub adjudication: Adjudication,
+  pub concurrency:  usize,
+  pub pgn:          Option<PathBuf>,
+  pub sprt:         Option<Sprt>
+}
+
+impl Default for MatchConfig {
+  fn default() -> Self {
+    let engine = EngineConfig {
+      name:  String::from("chyes"),
+      kind:  EngineKind::InProcess(Arc::new(Evaluator::default())),
+      depth: None,
+      nodes: None
+    };
+
+    MatchConfig {
+      engines:      [engine.clone(), engine],
+      openings:     vec![Board::default()],
+      games:        100,
+      time_control: Some(TimeControl {
+        base:      Duration::from_secs(10),
+        increment: Duration::from_millis(100)
+      }),
+      adjudication: Adjudication::default(),
+      concurrency:  1,
+      pgn:          None,
+      sprt:         None
+    }
+  }
+}
+
+/// A finished game of the match
+pub struct GameRecord {
+  pub number:  usize,
+  // Index of the engine playing white
+  pub white:   usize,
+  pub game:    Game,
+  pub outcome: Outcome
+}
+
+impl GameRecord {
+  pub fn to_pgn(
+    &self,
+    config: &MatchConfig
+  ) -> String {
+    let time_control = match config.time_control {
+      Some(tc) => format!("{}+{}", tc.base.as_secs_f64(), tc.increment.as_secs_f64()),
+      None => String::from("-")
+    };
+
+    self.game.to_pgn(&[
+      ("Event", String::from("chyes match")),
+      ("Round", self.number.to_string()),
+      ("White", config.engines[self.white].name.clone()),
+      ("Black", config.engines[1 - self.white].name.clone()),
+      ("TimeControl", time_control),
+      ("Termination", self.outcome.to_string())
+    ])
+  }
+}
+
+fn play_game(
+  number: usize,
+  opening: &Board,
+  white: usize,
+  players: &mut [Box<dyn Player>; 2],
+  config: &MatchConfig
+) -> Result<GameRecord, String> {
+  let mut game = Game::new(opening.clone());
+  let mut scores: Vec<Option<i32>> = Vec::new();
+  let mut clocks = [config.time_control.map(|tc| tc.base); 2];
+
+  // A game that never started has no result to count
+  for player in players.iter_mut() {
+    player
+      .new_game()
+      .map_err(|err| format!("{} couldn't start game {}: {}", player.name(), number, err))?;
+  }
+
+  while game.outcome().is_none() {
+    let turn = game.board().turn;
+    // Index of the engine to move and of its clock
+    let side = if turn == Color::White {
+      white
+    } else {
+      1 - white
+    };
+    let clock = clocks[turn as usize];
+
+    let limits = Limits {
+      depth: config.engines[side].depth,
+      nodes: config.engines[side].nodes,
+      time: clock,
+      increment: config
+        .time_control
+        .map_or(Duration::ZERO, |tc| tc.increment),
+      ..Limits::default()
+    };
+    let timeout = clock.map_or(Duration::from_secs(3600), |clock| {
+      clock + Duration::from_secs(1)
+    });
+
+    let started = Instant::now();
+    let answer = players[side].go(&game, &limits, timeout);
+    let elapsed = started.elapsed();
+
+    let (mv, score) = match answer {
+      Ok(answer) => answer,
+      Err(err) => {
+        game.decide(
+          GameResult::win(turn.opposite()),
+          format!("{} forfeits: {}", turn.name(), err)
+        );
+        break
+      }
+    };
+
+    if let Some(clock) = clock {
+      // Overshooting the clock by a few milliseconds is forgiven
+      if elapsed > clock + Duration::from_millis(25) {
+        game.decide(
+          GameResult::win(turn.opposite()),
+          format!("{} loses on time", turn.name())
+        );
+        break
+      }
+
+      clocks[turn as usize] = Some(clock.saturating_sub(elapsed) + limits.increment);
+    }
+
+    if let Err(err) = game.play(mv) {
+      game.decide(
+        GameResult::win(turn.opposite()),
+        format!("{} forfeits: {}", turn.name(), err)
+      );
+      break
+    }
+
+    let seconds = elapsed.as_secs_f64();
+    game.comment(match score {
+      Some(score) => format!("{} {:.2}s", score, seconds),
+      None => format!("{:.2}s", seconds)
+    });
+    scores.push(score.map(|score| match turn {
+      Color::White => score.centipawns(),
+      Color::Black => -score.centipawns()
+    }));
+
+    // Engines claim every draw they can
+    if game.outcome().is_none() && game.claim_draw().is_err() {
+      if let Some((result, reason)) = config.adjudication.decide(&game, &scores) {
+        game.decide(result, reason);
+      }
+    }
+  }
+
+  let outcome = game.outcome().expect("The game is over");
+
+  Ok(GameRecord {
+    number,
+    white,
+    game,
+    outcome
+  })
+}
+
+/// Plays the match, calling `report` after every game with the statistics so
+/// far; stops early once the SPRT reaches a decision, and with an error when
+/// an engine can't start a game
+pub fn run(
+  config: &MatchConfig,
+  mut report: impl FnMut(&GameRecord, &Stats)
+) -> Result<Stats, String> {
+  if config.openings.is_empty() {
+    return Err(String::from("No openings"))
+  }
+
+  // Make sure the engines start before playing any games
+  for engine in config.engines.iter() {
+    engine.create()?;
+  }
+
+  let next = AtomicUsize::new(0);
+  let stop = AtomicBool::new(false);
+  let mut stats = Stats::default();
+  let mut pgn = match &config.pgn {
+    Some(path) => Some(
+      OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .map_err(|err| format!("{}: {}", path.display(), err))?
+    ),
+    None => None
+  };
+
+  let (sender, receiver) = mpsc::channel::<Result<GameRecord, String>>();
+
+  thread::scope(|scope| {
+    for _ in 0..config.concurrency.max(1) {
+      let sender = sender.clone();
+      let (next, stop) = (&next, &stop);
+
+      scope.spawn(move || {
+        let mut players = match (config.engines[0].create(), config.engines[1].create()) {
+          (Ok(first), Ok(second)) => [first, second],
+          (Err(err), _) | (_, Err(err)) => {
+            let _ = sender.send(Err(err));
+            return
+          }
+        };
+
+        while !stop.load(Ordering::Relaxed) {
+          let number = next.fetch_add(1, Ordering::Relaxed);
+          if number >= config.games {
+            break
+          }
+
+          // Both games of a pair share the opening
+          let opening = &config.openings[(number / 2) % config.openings.len()];
+          let record = play_game(number + 1, opening, number % 2, &mut players, config);
+          let failed = record.is_err();
+
+          if sender.send(record).is_err() || failed {
+            break
+          }
+        }
+      });
+    }
+    drop(sender);
+
+    for record in receiver {
+      let record = match record {
+        Ok(record) => record,
+        Err(err) => {
+          stop.store(true, Ordering::Relaxed);
+          return Err(err)
+        }
+      };
+
+      let first_is_white = record.white == 0;
+      match (record.outcome.result(), first_is_white) {
+        (GameResult::Draw, _) => stats.draws += 1,
+        (GameResult::WhiteWins, true) | (GameResult::BlackWins, false) => stats.wins += 1,
+        _ => stats.losses += 1
+      }
+
+      if let Some(file) = pgn.as_mut() {
+        writeln!(file, "{}", record.to_pgn(config))
+          .map_err(|err| format!("Couldn't write PGN: {}", err))?;
+      }
+
+      report(&record, &stats);
+
+      if config
+        .sprt
+        .is_some_and(|sprt| sprt.result(&stats).is_some())
+      {
+        stop.store(true, Ordering::Relaxed);
+      }
+    }
+
+    Ok(stats)
+  })
+}
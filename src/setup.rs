@@ -0,0 +1,142 @@
+// Setting up positions by hand, e.g. in the board editor. Boards built
+// square by square aren't checked until `validate` is called
+
+use crate::{Board, CastlingSides, Color, Coordinate, Pieces};
+
+impl Board {
+  pub fn set_turn(
+    &mut self,
+    color: Color
+  ) {
+    self.turn = color;
+  }
+
+  pub fn castling_right(
+    &self,
+    color: Color,
+    king_side: bool
+  ) -> bool {
+    self.castling[color as usize][side(king_side)]
+  }
+
+  pub fn set_castling_right(
+    &mut self,
+    color: Color,
+    king_side: bool,
+    allowed: bool
+  ) {
+    self.castling[color as usize][side(king_side)] = allowed;
+  }
+
+  /// Square a pawn passed on the last move
+  pub fn en_passant(&self) -> Option<Coordinate> { self.en_passant_target_sq }
+
+  pub fn set_en_passant(
+    &mut self,
+    coord: Option<Coordinate>
+  ) {
+    self.en_passant_target_sq = coord;
+  }
+
+  /// Checks the position could come up in a game: one king a side, no pawns
+  /// on the first or last rank, no more pieces than a side starts with, the
+  /// side that just moved not in check, and castling rights and the en
+  /// passant square matching the pieces
+  pub fn validate(&self) -> Result<(), String> {
+    for color in [Color::White, Color::Black] {
+      let name = color.name();
+      let count = |breed: Pieces| {
+        self
+          .pieces
+          .values()
+          .filter(|piece| piece.color == color && piece.breed == breed)
+          .count()
+      };
+
+      match count(Pieces::King) {
+        0 => return Err(format!("{} has no king", name)),
+        1 => {},
+        kings => return Err(format!("{} has {} kings", name, kings))
+      }
+      if count(Pieces::Pawn) > 8 {
+        return Err(format!("{} has more than 8 pawns", name))
+      }
+      let pieces = self
+        .pieces
+        .values()
+        .filter(|piece| piece.color == color)
+        .count();
+      if pieces > 16 {
+        return Err(format!("{} has more than 16 pieces", name))
+      }
+    }
+
+    if let Some((coord, _)) = self
+      .pieces
+      .iter()
+      .find(|(coord, piece)| piece.breed == Pieces::Pawn && (coord.row == 0 || coord.row == 7))
+    {
+      return Err(format!("Pawn on {}", coord))
+    }
+
+    if self.is_in_check(self.turn.opposite()) {
+      return Err(format!(
+        "{} is in check with {} to move",
+        self.turn.opposite().name(),
+        self.turn.name().to_lowercase()
+      ))
+    }
+
+    for color in [Color::White, Color::Black] {
+      let row = if color == Color::White { 7 } else { 0 };
+      let at = |col: u8, breed: Pieces| {
+        self
+          .get_piece(&coord!(row, col))
+          .is_some_and(|piece| piece.color == color && piece.breed == breed)
+      };
+
+      for (king_side, col, name) in [(true, 7, "king"), (false, 0, "queen")] {
+        if self.castling_right(color, king_side) && !(at(4, Pieces::King) && at(col, Pieces::Rook))
+        {
+          return Err(format!(
+            "{} can't castle {} side without the king and rook at home",
+            color.name(),
+            name
+          ))
+        }
+      }
+    }
+
+    if let Some(target) = self.en_passant_target_sq {
+      // The pawn that passed the square stands in front of it, with the
+      // square and the one it came from empty
+      let (row, forward): (u8, i8) = match self.turn {
+        Color::White => (2, 1),
+        Color::Black => (5, -1)
+      };
+      let pawn = coord!((row as i8 + forward) as u8, target.col);
+      let passed = target.row == row
+        && self.get_piece(&target).is_none()
+        && self
+          .get_piece(&coord!((row as i8 - forward) as u8, target.col))
+          .is_none()
+        && self
+          .get_piece(&pawn)
+          .is_some_and(|piece| piece.color != self.turn && piece.breed == Pieces::Pawn);
+
+      if !passed {
+        return Err(format!("No pawn could have just passed {}", target))
+      }
+    }
+
+    Ok(())
+  }
+}
+
+fn side(king_side: bool) -> usize {
+  if king_side {
+    CastlingSides::KingSide as usize
+  } else {
+    CastlingSides::QueenSide as usize
+  }
+}
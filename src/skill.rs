@@ -0,0 +1,174 @@
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+
+use crate::eval::Evaluator;
+use crate::search::{self, Limits, SearchResult, MATE};
+use crate::Board;
+
+// Skill of the full strength engine
+pub const MAX_SKILL: u8 = 20;
+
+/// How strong the computer plays: search limits, and a skill below
+/// `MAX_SKILL` that makes it pick worse moves on purpose
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Difficulty {
+  pub depth:    Option<u8>,
+  pub movetime: Option<Duration>,
+  pub nodes:    Option<u64>,
+  pub skill:    u8
+}
+
+impl Default for Difficulty {
+  fn default() -> Self { Difficulty::level(5) }
+}
+
+impl Difficulty {
+  pub const LEVELS: u8 = 8;
+
+  /// Preset from 1, the weakest, to `LEVELS`
+  pub fn level(level: u8) -> Self {
+    let (depth, millis, skill) = match level.clamp(1, Difficulty::LEVELS) {
+      1 => (Some(1), None, 0),
+      2 => (Some(1), None, 6),
+      3 => (Some(2), None, 10),
+      4 => (Some(3), None, 13),
+      5 => (Some(4), Some(1000), 16),
+      6 => (Some(5), Some(1000), 18),
+      7 => (None, Some(2000), MAX_SKILL),
+      _ => (None, Some(5000), MAX_SKILL)
+    };
+
+    Difficulty {
+      depth,
+      movetime: millis.map(Duration::from_millis),
+      nodes: None,
+      skill
+    }
+  }
+
+  pub fn limits(&self) -> Limits {
+    Limits {
+      depth: self.depth,
+      movetime: self.movetime,
+      nodes: self.nodes,
+      ..Limits::default()
+    }
+  }
+}
+
+impl FromStr for Difficulty {
+  type Err = String;
+
+  /// A level, `3`, or settings on top of the strongest level,
+  /// `level=4,time=2.5`, `depth=6,nodes=100000,skill=12`
+  fn from_str(text: &str) -> Result<Self, Self::Err> {
+    if let Ok(level) = text.trim().parse::<u8>() {
+      if (1..=Difficulty::LEVELS).contains(&level) {
+        return Ok(Difficulty::level(level))
+      }
+      return Err(format!("Levels go from 1 to {}", Difficulty::LEVELS))
+    }
+
+    let mut difficulty = Difficulty::level(Difficulty::LEVELS);
+    let invalid = || format!("Invalid difficulty `{}`", text);
+
+    for pair in text.split(',') {
+      let (key, value) = pair.split_once('=').ok_or_else(invalid)?;
+      let value = value.trim();
+
+      match key.trim() {
+        "level" => difficulty = Difficulty::level(value.parse().map_err(|_| invalid())?),
+        "depth" => difficulty.depth = Some(value.parse().map_err(|_| invalid())?),
+        "time" | "movetime" => {
+          let seconds: f64 = value.parse().map_err(|_| invalid())?;
+          difficulty.movetime = Some(Duration::from_secs_f64(seconds.max(0.0)));
+        },
+        "nodes" => difficulty.nodes = Some(value.parse().map_err(|_| invalid())?),
+        "skill" => {
+          let skill: u8 = value.parse().map_err(|_| invalid())?;
+          difficulty.skill = skill.min(MAX_SKILL);
+        },
+        _ => return Err(invalid())
+      }
+    }
+
+    Ok(difficulty)
+  }
+}
+
+/// Picks a move at the given difficulty. Below full skill every move is
+/// scored by a shallow search and the scores are blurred with random noise,
+/// more of it the lower the skill, so weak levels miss tactics but still
+/// take free material most of the time
+pub fn pick_move(
+  board: &Board,
+  history: &[u64],
+  evaluator: &Evaluator,
+  difficulty: &Difficulty,
+  stop: &AtomicBool,
+  seed: u64
+) -> SearchResult {
+  let mut result = search::search(
+    board,
+    history,
+    evaluator,
+    &difficulty.limits(),
+    stop,
+    |_| {}
+  );
+
+  if difficulty.skill >= MAX_SKILL || stop.load(Ordering::Relaxed) {
+    return result
+  }
+
+  // xorshift64*
+  let mut state = seed.max(1);
+  let mut noise = move |range: i32| -> i32 {
+    state ^= state >> 12;
+    state ^= state << 25;
+    state ^= state >> 27;
+    (state.wrapping_mul(0x2545_f491_4f6c_dd1d) >> 33) as i32 % (range + 1)
+  };
+
+  let weakness = (MAX_SKILL - difficulty.skill) as i32 * 25;
+  let mut history = history.to_vec();
+  history.push(board.key());
+  let shallow = Limits {
+    depth: Some(1),
+    ..Limits::default()
+  };
+
+  let mut best = None;
+  for mv in board.legal_moves() {
+    let mut child = board.clone();
+    if child.make_move(mv).is_err() {
+      continue
+    }
+
+    // Searches don't score positions without moves, mates and stalemates
+    let score = if child.legal_moves().is_empty() {
+      if child.is_in_check(child.turn) {
+        MATE - 1
+      } else {
+        0
+      }
+    } else {
+      let reply = search::search(&child, &history, evaluator, &shallow, stop, |_| {});
+      -reply.score.centipawns()
+    } + noise(weakness);
+
+    if best.is_none_or(|(_, best)| score > best) {
+      best = Some((mv, score));
+    }
+  }
+
+  if let Some((mv, _)) = best {
+    if result.best_move != Some(mv) {
+      result.best_move = Some(mv);
+      result.pv = vec![mv];
+    }
+  }
+
+  result
+}
@@ -0,0 +1,1029 @@
+use std::collections::HashMap;
+use std::path::Path;
+use std::str::FromStr;
+use std::{fmt, fs, io};
+
+use crate::{Board, Color, Pieces};
+
+// Tablebases are generated by retrograde analysis: every legal position of a
+// material signature is enumerated once, mates are found and distances are
+// propagated backwards by un-making moves until nothing changes anymore.
+//
+// Positions are indexed as `side * 64^n + sq[0] + sq[1] * 64 + ...`, where the
+// squares follow the canonical piece order of the material (white pieces
+// first, kings first, then Queen, Rook, Bishop, Knight, Pawn). Castling rights
+// and en passant are not part of the index.
+//
+// Every position is stored in one byte:
+//   0         - draw
+//   1..=253   - distance to mate in plies + 1 (odd plies - win, even - loss)
+//   255       - illegal position
+
+const DRAW: u8 = 0;
+const UNKNOWN: u8 = 254;
+const ILLEGAL: u8 = 255;
+const NONE: u8 = 255;
+const MAX_PLIES: usize = 252;
+const MAX_PIECES: usize = 4;
+
+const MAGIC: &[u8; 8] = b"CHYESTB1";
+
+const KING_DELTAS: [(i8, i8); 8] = [
+  (-1, -1),
+  (-1, 0),
+  (-1, 1),
+  (0, -1),
+  (0, 1),
+  (1, -1),
+  (1, 0),
+  (1, 1)
+];
+const KNIGHT_DELTAS: [(i8, i8); 8] = [
+  (-2, -1),
+  (-2, 1),
+  (-1, -2),
+  (-1, 2),
+  (1, -2),
+  (1, 2),
+  (2, -1),
+  (2, 1)
+];
+const ROOK_DELTAS: [(i8, i8); 4] = [(-1, 0), (1, 0), (0, -1), (0, 1)];
+const BISHOP_DELTAS: [(i8, i8); 4] = [(-1, -1), (-1, 1), (1, -1), (1, 1)];
+
+/// Distance to mate of a position, in plies, from the side to move's point of view
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Dtm {
+  Win(u8),
+  Loss(u8),
+  Draw
+}
+
+impl Dtm {
+  fn decode(byte: u8) -> Option<Self> {
+    match byte {
+      DRAW => Some(Dtm::Draw),
+      ILLEGAL | UNKNOWN => None,
+      _ if (byte - 1) % 2 == 1 => Some(Dtm::Win(byte - 1)),
+      _ => Some(Dtm::Loss(byte - 1))
+    }
+  }
+}
+
+/// Set of pieces on the board, e.g. "KQK" or "KBNK"
+///
+/// The first K starts the white pieces, the second K starts the black pieces
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
+pub struct Material {
+  pieces: Vec<(Color, Pieces)>
+}
+
+fn breed_order(breed: Pieces) -> u8 {
+  use Pieces::*;
+  match breed {
+    King => 0,
+    Queen => 1,
+    Rook => 2,
+    Bishop => 3,
+    Knight => 4,
+    Pawn => 5
+  }
+}
+
+fn canonical_order(
+  color: Color,
+  breed: Pieces
+) -> u8 {
+  let side = if color == Color::White { 0 } else { 8 };
+
+  side + breed_order(breed)
+}
+
+impl Material {
+  fn from_pieces(mut pieces: Vec<(Color, Pieces)>) -> Self {
+    pieces.sort_by_key(|&(color, breed)| canonical_order(color, breed));
+
+    Material {
+      pieces
+    }
+  }
+
+  /// Material of the pieces currently on the board
+  pub fn of(board: &Board) -> Self {
+    Self::from_pieces(
+      board
+        .pieces
+        .values()
+        .map(|piece| (piece.color, piece.breed))
+        .collect()
+    )
+  }
+
+  pub fn len(&self) -> usize { self.pieces.len() }
+
+  pub fn is_empty(&self) -> bool { self.pieces.is_empty() }
+
+  fn positions(&self) -> usize { 2 * 64usize.pow(self.pieces.len() as u32) }
+
+  // Materials reachable by a single capture or promotion
+  fn successors(&self) -> Vec<Material> {
+    let mut result: Vec<Material> = Vec::new();
+
+    for (i, &(color, breed)) in self.pieces.iter().enumerate() {
+      if breed == Pieces::King {
+        continue
+      }
+
+      let mut captured = self.pieces.clone();
+      captured.remove(i);
+      result.push(Material::from_pieces(captured));
+
+      if breed == Pieces::Pawn {
+        for promotion in [Pieces::Queen, Pieces::Rook, Pieces::Bishop, Pieces::Knight] {
+          let mut promoted = self.pieces.clone();
+          promoted[i] = (color, promotion);
+          result.push(Material::from_pieces(promoted));
+        }
+      }
+    }
+
+    result.sort_by_key(|material| material.to_string());
+    result.dedup();
+    result
+  }
+}
+
+impl FromStr for Material {
+  type Err = &'static str;
+
+  fn from_str(notation: &str) -> Result<Self, Self::Err> {
+    // KQK -> white: KQ, black: K
+    let second_king = match notation.char_indices().skip(1).find(|&(_, c)| c == 'K') {
+      Some((i, _)) => i,
+      None => return Err("Material must contain a king for both sides")
+    };
+
+    if !notation.starts_with('K') {
+      return Err("Material must start with the white king")
+    }
+
+    let mut pieces = Vec::new();
+
+    for (i, c) in notation.chars().enumerate() {
+      let color = if i < second_king {
+        Color::White
+      } else {
+        Color::Black
+      };
+
+      use Pieces::*;
+      let breed = match c {
+        'K' if i == 0 || i == second_king => King,
+        'Q' => Queen,
+        'R' => Rook,
+        'B' => Bishop,
+        'N' => Knight,
+        'P' => Pawn,
+        _ => return Err("Invalid piece in material notation (K/Q/R/B/N/P are available)")
+      };
+
+      pieces.push((color, breed));
+    }
+
+    if pieces.len() > MAX_PIECES {
+      return Err("Tablebases support at most 4 pieces")
+    }
+
+    Ok(Material::from_pieces(pieces))
+  }
+}
+
+impl fmt::Display for Material {
+  fn fmt(
+    &self,
+    f: &mut fmt::Formatter<'_>
+  ) -> fmt::Result {
+    use Pieces::*;
+    for &(_, breed) in self.pieces.iter() {
+      let c = match breed {
+        King => 'K',
+        Queen => 'Q',
+        Rook => 'R',
+        Bishop => 'B',
+        Knight => 'N',
+        Pawn => 'P'
+      };
+      write!(f, "{}", c)?;
+    }
+
+    Ok(())
+  }
+}
+
+// Compact board used while solving, squares are numbered like `Coordinate`
+type Cells = [Option<(Color, Pieces)>; 64];
+
+fn step(
+  square: u8,
+  (d_row, d_col): (i8, i8)
+) -> Option<u8> {
+  let row = (square / 8) as i8 + d_row;
+  let col = (square % 8) as i8 + d_col;
+
+  if (0..8).contains(&row) && (0..8).contains(&col) {
+    Some((row * 8 + col) as u8)
+  } else {
+    None
+  }
+}
+
+// White pawns move towards row 0, black pawns towards row 7
+fn pawn_direction(color: Color) -> i8 {
+  if color == Color::White {
+    -1
+  } else {
+    1
+  }
+}
+
+fn is_attacked(
+  cells: &Cells,
+  square: u8,
+  by: Color
+) -> bool {
+  let attacker = |sq: Option<u8>, breeds: &[Pieces]| match sq.and_then(|sq| cells[sq as usize]) {
+    Some((color, breed)) => color == by && breeds.contains(&breed),
+    None => false
+  };
+
+  if KING_DELTAS
+    .iter()
+    .any(|&d| attacker(step(square, d), &[Pieces::King]))
+  {
+    return true
+  }
+
+  if KNIGHT_DELTAS
+    .iter()
+    .any(|&d| attacker(step(square, d), &[Pieces::Knight]))
+  {
+    return true
+  }
+
+  // A pawn attacks the square from behind, relative to its direction
+  let behind = -pawn_direction(by);
+  if attacker(step(square, (behind, -1)), &[Pieces::Pawn])
+    || attacker(step(square, (behind, 1)), &[Pieces::Pawn])
+  {
+    return true
+  }
+
+  for (deltas, breeds) in [
+    (&ROOK_DELTAS, [Pieces::Rook, Pieces::Queen]),
+    (&BISHOP_DELTAS, [Pieces::Bishop, Pieces::Queen])
+  ] {
+    for &delta in deltas.iter() {
+      let mut current = step(square, delta);
+
+      while let Some(sq) = current {
+        if cells[sq as usize].is_some() {
+          if attacker(Some(sq), &breeds) {
+            return true
+          }
+          break
+        }
+        current = step(sq, delta);
+      }
+    }
+  }
+
+  false
+}
+
+// Fixed capacity list of squares, keeps the solver's inner loops free of allocations
+#[derive(Clone, Copy)]
+struct Squares {
+  items: [u8; 32],
+  len:   usize
+}
+
+impl Squares {
+  fn new() -> Self {
+    Squares {
+      items: [0; 32],
+      len:   0
+    }
+  }
+
+  fn push(
+    &mut self,
+    square: u8
+  ) {
+    self.items[self.len] = square;
+    self.len += 1;
+  }
+
+  fn as_slice(&self) -> &[u8] { &self.items[..self.len] }
+}
+
+// Destination squares of a non-pawn piece, `captures` allows landing on enemy pieces
+fn piece_targets(
+  cells: &Cells,
+  square: u8,
+  color: Color,
+  breed: Pieces,
+  captures: bool
+) -> Squares {
+  let mut targets = Squares::new();
+  let mut push = |sq: u8| -> bool {
+    match cells[sq as usize] {
+      None => {
+        targets.push(sq);
+        true
+      },
+      Some((other, _)) => {
+        if captures && other != color {
+          targets.push(sq);
+        }
+        false
+      }
+    }
+  };
+
+  use Pieces::*;
+  match breed {
+    King | Knight => {
+      let deltas = if breed == King {
+        &KING_DELTAS
+      } else {
+        &KNIGHT_DELTAS
+      };
+
+      for &delta in deltas.iter() {
+        if let Some(sq) = step(square, delta) {
+          push(sq);
+        }
+      }
+    },
+    Queen | Rook | Bishop => {
+      let deltas: &[(i8, i8)] = match breed {
+        Queen => &KING_DELTAS,
+        Rook => &ROOK_DELTAS,
+        _ => &BISHOP_DELTAS
+      };
+
+      for &delta in deltas.iter() {
+        let mut current = step(square, delta);
+
+        while let Some(sq) = current {
+          if !push(sq) {
+            break
+          }
+          current = step(sq, delta);
+        }
+      }
+    },
+    Pawn => unreachable!("Pawn moves are generated separately")
+  }
+
+  targets
+}
+
+#[derive(Clone, Copy)]
+struct Position {
+  turn:    Color,
+  squares: [u8; MAX_PIECES]
+}
+
+// Table reached by a capture and/or a promotion, with the slots of the
+// remaining pieces in the order of the other table
+struct Exit<'a> {
+  removed:  Option<usize>,
+  promoted: Option<(usize, Pieces)>,
+  table:    &'a Tablebase,
+  slots:    Vec<usize>
+}
+
+struct Solver<'a> {
+  material: &'a Material,
+  exits:    Vec<Exit<'a>>
+}
+
+fn index_of(
+  squares: &[u8],
+  turn: Color
+) -> usize {
+  let mut index = 0;
+
+  for &square in squares.iter().rev() {
+    index = index * 64 + square as usize;
+  }
+
+  if turn == Color::Black {
+    index += 64usize.pow(squares.len() as u32);
+  }
+
+  index
+}
+
+impl<'a> Solver<'a> {
+  fn new(
+    material: &'a Material,
+    solved: &'a HashMap<Material, Tablebase>
+  ) -> Self {
+    let n = material.len();
+    let mut exits = Vec::new();
+
+    let mut removals: Vec<Option<usize>> = vec![None];
+    removals.extend(
+      (0..n)
+        .filter(|&i| material.pieces[i].1 != Pieces::King)
+        .map(Some)
+    );
+
+    let mut promotions: Vec<Option<(usize, Pieces)>> = vec![None];
+    for (i, &(_, breed)) in material.pieces.iter().enumerate() {
+      if breed == Pieces::Pawn {
+        for promotion in [Pieces::Queen, Pieces::Rook, Pieces::Bishop, Pieces::Knight] {
+          promotions.push(Some((i, promotion)));
+        }
+      }
+    }
+
+    for &removed in removals.iter() {
+      for &promoted in promotions.iter() {
+        if (removed.is_none() && promoted.is_none())
+          || (removed.is_some() && promoted.is_some_and(|(i, _)| Some(i) == removed))
+        {
+          continue
+        }
+
+        let mut slots: Vec<usize> = (0..n).filter(|&i| Some(i) != removed).collect();
+        let breed_at = |i: usize| match promoted {
+          Some((slot, breed)) if slot == i => (material.pieces[i].0, breed),
+          _ => material.pieces[i]
+        };
+        slots.sort_by_key(|&i| {
+          let (color, breed) = breed_at(i);
+          canonical_order(color, breed)
+        });
+
+        let other = Material::from_pieces(slots.iter().map(|&i| breed_at(i)).collect());
+        if let Some(table) = solved.get(&other) {
+          exits.push(Exit {
+            removed,
+            promoted,
+            table,
+            slots
+          });
+        }
+      }
+    }
+
+    Solver {
+      material,
+      exits
+    }
+  }
+
+  fn index(
+    &self,
+    position: &Position
+  ) -> usize {
+    index_of(&position.squares[..self.material.len()], position.turn)
+  }
+
+  fn position(
+    &self,
+    mut index: usize
+  ) -> Position {
+    let n = self.material.len();
+    let turn = if index >= 64usize.pow(n as u32) {
+      index -= 64usize.pow(n as u32);
+      Color::Black
+    } else {
+      Color::White
+    };
+
+    let mut squares = [0; MAX_PIECES];
+    for square in squares.iter_mut().take(n) {
+      *square = (index % 64) as u8;
+      index /= 64;
+    }
+
+    Position {
+      turn,
+      squares
+    }
+  }
+
+  fn cells(
+    &self,
+    position: &Position
+  ) -> Option<Cells> {
+    let mut cells: Cells = [None; 64];
+
+    for (&square, &piece) in position.squares.iter().zip(self.material.pieces.iter()) {
+      if cells[square as usize].is_some() {
+        return None
+      }
+      cells[square as usize] = Some(piece);
+    }
+
+    Some(cells)
+  }
+
+  fn king(
+    &self,
+    position: &Position,
+    color: Color
+  ) -> u8 {
+    let slot = self
+      .material
+      .pieces
+      .iter()
+      .position(|&piece| piece == (color, Pieces::King))
+      .expect("Material without a king");
+
+    position.squares[slot]
+  }
+
+  fn is_legal(
+    &self,
+    position: &Position,
+    cells: &Cells
+  ) -> bool {
+    // Pawns can't stand on the first or last rank
+    for (&square, &(_, breed)) in position.squares.iter().zip(self.material.pieces.iter()) {
+      if breed == Pieces::Pawn && (square / 8 == 0 || square / 8 == 7) {
+        return false
+      }
+    }
+
+    // The side that has just moved can't be in check
+    let waiting = position.turn.opposite();
+    !is_attacked(cells, self.king(position, waiting), position.turn)
+  }
+
+  // Value of a position in another table, reached by a capture or a promotion
+  fn probe_exit(
+    &self,
+    child: &Position,
+    removed: Option<usize>,
+    promoted: Option<(usize, Pieces)>
+  ) -> u8 {
+    let exit = self
+      .exits
+      .iter()
+      .find(|exit| exit.removed == removed && exit.promoted == promoted)
+      .expect("Successor tablebase must be generated first");
+
+    let mut squares = [0; MAX_PIECES];
+    for (square, &slot) in squares.iter_mut().zip(exit.slots.iter()) {
+      *square = child.squares[slot];
+    }
+
+    exit.table.table[index_of(&squares[..exit.slots.len()], child.turn)]
+  }
+
+  // Visits every legal move, passing either an index in this table or the
+  // value of the position in another table
+  fn for_each_move(
+    &self,
+    position: &Position,
+    cells: &Cells,
+    mut visit: impl FnMut(Result<usize, u8>)
+  ) {
+    let turn = position.turn;
+    let king = self.king(position, turn);
+
+    for (slot, &(color, breed)) in self.material.pieces.iter().enumerate() {
+      if color != turn {
+        continue
+      }
+
+      let from = position.squares[slot];
+      let targets = if breed == Pieces::Pawn {
+        let mut targets = Squares::new();
+        let direction = pawn_direction(color);
+        let start_row = if color == Color::White { 6 } else { 1 };
+
+        if let Some(sq) = step(from, (direction, 0)) {
+          if cells[sq as usize].is_none() {
+            targets.push(sq);
+
+            if from / 8 == start_row {
+              if let Some(sq) = step(sq, (direction, 0)) {
+                if cells[sq as usize].is_none() {
+                  targets.push(sq);
+                }
+              }
+            }
+          }
+        }
+
+        for side in [-1, 1] {
+          if let Some(sq) = step(from, (direction, side)) {
+            if matches!(cells[sq as usize], Some((other, _)) if other != color) {
+              targets.push(sq);
+            }
+          }
+        }
+
+        targets
+      } else {
+        piece_targets(cells, from, color, breed, true)
+      };
+
+      for &to in targets.as_slice() {
+        let mut next = *cells;
+        next[from as usize] = None;
+        next[to as usize] = Some((color, breed));
+
+        let own_king = if breed == Pieces::King { to } else { king };
+        if is_attacked(&next, own_king, turn.opposite()) {
+          continue
+        }
+
+        let mut child = *position;
+        child.squares[slot] = to;
+        child.turn = turn.opposite();
+
+        let removed = if cells[to as usize].is_some() {
+          (0..self.material.len()).find(|&i| i != slot && position.squares[i] == to)
+        } else {
+          None
+        };
+
+        let last_row = if color == Color::White { 0 } else { 7 };
+        if breed == Pieces::Pawn && to / 8 == last_row {
+          for promotion in [Pieces::Queen, Pieces::Rook, Pieces::Bishop, Pieces::Knight] {
+            visit(Err(self.probe_exit(
+              &child,
+              removed,
+              Some((slot, promotion))
+            )));
+          }
+        } else if removed.is_some() {
+          visit(Err(self.probe_exit(&child, removed, None)));
+        } else {
+          visit(Ok(self.index(&child)));
+        }
+      }
+    }
+  }
+
+  // Positions from which the side that isn't to move could have reached this one,
+  // without a capture or a promotion
+  fn for_each_predecessor(
+    &self,
+    position: &Position,
+    cells: &Cells,
+    mut visit: impl FnMut(usize)
+  ) {
+    let mover = position.turn.opposite();
+
+    for (slot, &(color, breed)) in self.material.pieces.iter().enumerate() {
+      if color != mover {
+        continue
+      }
+
+      let to = position.squares[slot];
+      let origins = if breed == Pieces::Pawn {
+        let mut origins = Squares::new();
+        let back = -pawn_direction(color);
+        let start_row = if color == Color::White { 6 } else { 1 };
+
+        if let Some(sq) = step(to, (back, 0)) {
+          if cells[sq as usize].is_none() && sq / 8 != 0 && sq / 8 != 7 {
+            origins.push(sq);
+
+            if let Some(sq) = step(sq, (back, 0)) {
+              if cells[sq as usize].is_none() && sq / 8 == start_row {
+                origins.push(sq);
+              }
+            }
+          }
+        }
+
+        origins
+      } else {
+        piece_targets(cells, to, color, breed, false)
+      };
+
+      for &from in origins.as_slice() {
+        let mut previous = *position;
+        previous.squares[slot] = from;
+        previous.turn = mover;
+
+        let mut previous_cells = *cells;
+        previous_cells[to as usize] = None;
+        previous_cells[from as usize] = Some((color, breed));
+
+        // The side that is waiting in the previous position can't be in check
+        if !is_attacked(&previous_cells, self.king(&previous, position.turn), mover) {
+          visit(self.index(&previous));
+        }
+      }
+    }
+  }
+
+  fn solve(&self) -> Vec<u8> {
+    let size = self.material.positions();
+
+    let mut values = vec![UNKNOWN; size];
+    let mut counters = vec![0u8; size];
+    let mut win_at = vec![NONE; size];
+    let mut loss_floor = vec![0u8; size];
+    let mut draw_exit = vec![false; size];
+    let mut buckets: Vec<Vec<usize>> = vec![Vec::new(); MAX_PLIES + 1];
+
+    // Forward pass: count moves inside this table and evaluate the rest
+    for index in 0..size {
+      let position = self.position(index);
+      let cells = match self.cells(&position) {
+        Some(cells) if self.is_legal(&position, &cells) => cells,
+        _ => {
+          values[index] = ILLEGAL;
+          continue
+        }
+      };
+
+      let mut has_moves = false;
+      self.for_each_move(&position, &cells, |child| {
+        has_moves = true;
+
+        match child {
+          Ok(_) => counters[index] += 1,
+          Err(value) => match Dtm::decode(value) {
+            Some(Dtm::Loss(plies)) => win_at[index] = win_at[index].min(plies + 1),
+            Some(Dtm::Win(plies)) => loss_floor[index] = loss_floor[index].max(plies + 1),
+            _ => draw_exit[index] = true
+          }
+        }
+      });
+
+      if !has_moves {
+        let king = self.king(&position, position.turn);
+
+        if is_attacked(&cells, king, position.turn.opposite()) {
+          buckets[0].push(index);
+        } else {
+          values[index] = DRAW;
+        }
+      } else if win_at[index] != NONE {
+        buckets[win_at[index] as usize].push(index);
+      } else if counters[index] == 0 {
+        if draw_exit[index] {
+          values[index] = DRAW;
+        } else {
+          buckets[loss_floor[index] as usize].push(index);
+        }
+      }
+    }
+
+    // Backward pass: resolve positions in order of increasing distance to mate
+    for plies in 0..=MAX_PLIES {
+      let bucket = std::mem::take(&mut buckets[plies]);
+
+      for index in bucket {
+        let is_win = plies % 2 == 1;
+
+        if values[index] != UNKNOWN || (is_win && win_at[index] != plies as u8) {
+          continue
+        }
+
+        values[index] = plies as u8 + 1;
+
+        let position = self.position(index);
+        let cells = self
+          .cells(&position)
+          .expect("Resolved position must be valid");
+
+        self.for_each_predecessor(&position, &cells, |previous| {
+          if values[previous] != UNKNOWN {
+            return
+          }
+
+          if is_win {
+            counters[previous] -= 1;
+
+            if counters[previous] == 0 && win_at[previous] == NONE && !draw_exit[previous] {
+              let floor = (plies + 1).max(loss_floor[previous] as usize);
+              assert!(
+                floor <= MAX_PLIES,
+                "Distance to mate is too long to be stored"
+              );
+              buckets[floor].push(previous);
+            }
+          } else if win_at[previous] as usize > plies + 1 {
+            assert!(
+              plies < MAX_PLIES,
+              "Distance to mate is too long to be stored"
+            );
+            win_at[previous] = plies as u8 + 1;
+            buckets[plies + 1].push(previous);
+          }
+        });
+      }
+    }
+
+    // Everything that couldn't be resolved is a draw
+    for value in values.iter_mut() {
+      if *value == UNKNOWN {
+        *value = DRAW;
+      }
+    }
+
+    values
+  }
+}
+
+/// Distance to mate for every position of a single material signature
+#[derive(Clone)]
+pub struct Tablebase {
+  material: Material,
+  table:    Vec<u8>
+}
+
+impl Tablebase {
+  pub fn material(&self) -> &Material { &self.material }
+
+  pub fn probe_dtm(
+    &self,
+    board: &Board
+  ) -> Option<Dtm> {
+    // Tables are generated without castling rights or en passant captures
+    if Material::of(board) != self.material
+      || board.castling != [[false; 2]; 2]
+      || board.en_passant().is_some()
+    {
+      return None
+    }
+
+    let mut pieces: Vec<(Color, Pieces, u8)> = Vec::new();
+    for (coord, piece) in board.pieces.iter() {
+      let square: u8 = (*coord).try_into().ok()?;
+      pieces.push((piece.color, piece.breed, square));
+    }
+    pieces.sort_by_key(|&(color, breed, _)| canonical_order(color, breed));
+
+    let squares: Vec<u8> = pieces.iter().map(|&(_, _, sq)| sq).collect();
+
+    Dtm::decode(self.table[index_of(&squares, board.turn)])
+  }
+
+  /// Longest distance to mate stored in the table, in plies
+  pub fn longest_mate(&self) -> u8 {
+    self
+      .table
+      .iter()
+      .filter_map(|&byte| match Dtm::decode(byte) {
+        Some(Dtm::Win(plies)) | Some(Dtm::Loss(plies)) => Some(plies),
+        _ => None
+      })
+      .max()
+      .unwrap_or(0)
+  }
+
+  pub fn save(
+    &self,
+    path: impl AsRef<Path>
+  ) -> io::Result<()> {
+    // Layout: magic, material length, material, then runs of (value, count)
+    let material = self.material.to_string();
+    let mut bytes = Vec::with_capacity(self.table.len() / 4);
+
+    bytes.extend_from_slice(MAGIC);
+    bytes.push(material.len() as u8);
+    bytes.extend_from_slice(material.as_bytes());
+
+    let mut i = 0;
+    while i < self.table.len() {
+      let value = self.table[i];
+      let mut count = 1;
+
+      while count < 255 && i + count < self.table.len() && self.table[i + count] == value {
+        count += 1;
+      }
+
+      bytes.push(value);
+      bytes.push(count as u8);
+      i += count;
+    }
+
+    fs::write(path, bytes)
+  }
+
+  pub fn load(path: impl AsRef<Path>) -> io::Result<Self> {
+    let invalid = |message: &str| io::Error::new(io::ErrorKind::InvalidData, message.to_string());
+    let bytes = fs::read(path)?;
+
+    if bytes.len() < MAGIC.len() + 1 || &bytes[..MAGIC.len()] != MAGIC {
+      return Err(invalid("Not a chyes tablebase file"))
+    }
+
+    let length = bytes[MAGIC.len()] as usize;
+    let start = MAGIC.len() + 1;
+    let material = bytes
+      .get(start..start + length)
+      .and_then(|name| std::str::from_utf8(name).ok())
+      .ok_or_else(|| invalid("Couldn't read tablebase material"))?;
+    let material = Material::from_str(material).map_err(invalid)?;
+
+    let mut table = Vec::with_capacity(material.positions());
+    for run in bytes[start + length..].chunks(2) {
+      match run {
+        [value, count] => table.extend(std::iter::repeat_n(*value, *count as usize)),
+        _ => return Err(invalid("Truncated tablebase file"))
+      }
+    }
+
+    if table.len() != material.positions() {
+      return Err(invalid("Tablebase size doesn't match its material"))
+    }
+
+    Ok(Tablebase {
+      material,
+      table
+    })
+  }
+}
+
+/// Collection of tablebases, generated locally or loaded from disk
+#[derive(Default)]
+pub struct Tablebases {
+  tables: HashMap<Material, Tablebase>
+}
+
+impl Tablebases {
+  pub fn new() -> Self { Self::default() }
+
+  pub fn get(
+    &self,
+    material: &Material
+  ) -> Option<&Tablebase> {
+    self.tables.get(material)
+  }
+
+  /// Generates the tablebase for the material and every table it depends on
+  pub fn generate(
+    &mut self,
+    material: &Material
+  ) -> &Tablebase {
+    if !self.tables.contains_key(material) {
+      for successor in material.successors() {
+        self.generate(&successor);
+      }
+
+      let table = Solver::new(material, &self.tables).solve();
+
+      self.tables.insert(
+        material.clone(),
+        Tablebase {
+          material: material.clone(),
+          table
+        }
+      );
+    }
+
+    &self.tables[material]
+  }
+
+  pub fn probe_dtm(
+    &self,
+    board: &Board
+  ) -> Option<Dtm> {
+    self.tables.get(&Material::of(board))?.probe_dtm(board)
+  }
+
+  /// Saves every table as `<material>.chtb` in the directory
+  pub fn save_dir(
+    &self,
+    dir: impl AsRef<Path>
+  ) -> io::Result<()> {
+    fs::create_dir_all(&dir)?;
+
+    for (material, table) in self.tables.iter() {
+      table.save(dir.as_ref().join(format!("{}.chtb", material)))?;
+    }
+
+    Ok(())
+  }
+
+  /// Loads every `.chtb` file from the directory
+  pub fn load_dir(dir: impl AsRef<Path>) -> io::Result<Self> {
+    let mut tablebases = Tablebases::new();
+
+    for entry in fs::read_dir(dir)? {
+      let path = entry?.path();
+
+      if path.extension().is_some_and(|ext| ext == "chtb") {
+        let table = Tablebase::load(&path)?;
+        tablebases.tables.insert(table.material.clone(), table);
+      }
+    }
+
+    Ok(tablebases)
+  }
+}
@@ -0,0 +1,98 @@
+// The terminal taken over by the app: raw mode, the alternate screen and
+// mouse capture. Leaving the shell in that state is worse than any bug, so
+// it's undone however the app ends, returning, failing or panicking
+
+use std::io::{self, Stdout};
+use std::panic;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Once};
+
+use crossterm::cursor::Show;
+use crossterm::event::{DisableMouseCapture, EnableMouseCapture};
+use crossterm::execute;
+use crossterm::terminal::{
+  disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen
+};
+use signal_hook::consts::{SIGINT, SIGTERM};
+use tui::backend::CrosstermBackend;
+use tui::Terminal;
+
+/// Puts the terminal back the way the shell expects it. Safe to call more
+/// than once
+pub fn restore() -> io::Result<()> {
+  disable_raw_mode()?;
+  execute!(
+    io::stdout(),
+    LeaveAlternateScreen,
+    DisableMouseCapture,
+    Show
+  )
+}
+
+static PANICKED: AtomicBool = AtomicBool::new(false);
+
+/// Whether a thread panicked, e.g. the computer's. The terminal is restored
+/// by then, so the app should stop drawing
+pub fn panicked() -> bool { PANICKED.load(Ordering::Relaxed) }
+
+// Restores the terminal before the panic message is printed, so the
+// message ends up on the normal screen instead of being wiped with the
+// alternate one
+fn install_panic_hook() {
+  static INSTALLED: Once = Once::new();
+
+  INSTALLED.call_once(|| {
+    let previous = panic::take_hook();
+    panic::set_hook(Box::new(move |info| {
+      PANICKED.store(true, Ordering::Relaxed);
+      let _ = restore();
+      previous(info);
+    }));
+  });
+}
+
+/// Flag raised by SIGINT and SIGTERM, for the main loop to quit on. In raw
+/// mode ctrl-c is a key press, so these come from `kill` and the like
+pub fn quit_signals() -> io::Result<Arc<AtomicBool>> {
+  let flag = Arc::new(AtomicBool::new(false));
+
+  for signal in [SIGINT, SIGTERM] {
+    signal_hook::flag::register(signal, Arc::clone(&flag))?;
+  }
+
+  Ok(flag)
+}
+
+/// The terminal in raw mode on the alternate screen, restored when dropped
+pub struct Session {
+  terminal: Terminal<CrosstermBackend<Stdout>>
+}
+
+impl Session {
+  pub fn start() -> io::Result<Self> {
+    install_panic_hook();
+
+    enable_raw_mode()?;
+    // From here on a failure has to undo what was done so far
+    let start = || {
+      execute!(io::stdout(), EnterAlternateScreen, EnableMouseCapture)?;
+      Terminal::new(CrosstermBackend::new(io::stdout()))
+    };
+
+    match start() {
+      Ok(terminal) => Ok(Session {
+        terminal
+      }),
+      Err(err) => {
+        let _ = restore();
+        Err(err)
+      }
+    }
+  }
+
+  pub fn terminal(&mut self) -> &mut Terminal<CrosstermBackend<Stdout>> { &mut self.terminal }
+}
+
+impl Drop for Session {
+  fn drop(&mut self) { let _ = restore(); }
+}
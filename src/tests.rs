@@ -1123,3 +1123,2688 @@ fn get_king_coord() {
     Some(coord!(6, 3))
   );
 }
+
+#[test]
+fn tablebase_material() {
+  use crate::tablebase::Material;
+
+  assert_eq!(
+    Material::from_str("KQK")
+      .expect("Couldn't parse material")
+      .to_string(),
+    "KQK"
+  );
+  assert_eq!(
+    Material::from_str("KNBK").expect("Couldn't parse material"),
+    Material::from_str("KBNK").expect("Couldn't parse material")
+  );
+  assert_eq!(
+    Material::of(&Board::from_fen("8/8/8/4k3/8/8/1P6/K7 w - - 0 1").expect("Couldn't load FEN")),
+    Material::from_str("KPK").expect("Couldn't parse material")
+  );
+  assert!(Material::from_str("QKK").is_err());
+  assert!(Material::from_str("KQ").is_err());
+  assert!(Material::from_str("KQRKR").is_err());
+}
+
+#[test]
+fn tablebase_kqk_krk() {
+  use crate::tablebase::{Dtm, Material, Tablebases};
+
+  let mut tablebases = Tablebases::new();
+
+  macro_rules! probe {
+    ($fen:expr) => {
+      tablebases.probe_dtm(&Board::from_fen($fen).expect("Couldn't load FEN"))
+    };
+  }
+
+  // Longest mates, counted for the losing side: KQK is mate in 10, KRK is mate in 16
+  let kqk = tablebases.generate(&Material::from_str("KQK").expect("Couldn't parse material"));
+  assert_eq!(kqk.longest_mate(), 20);
+
+  let krk = tablebases.generate(&Material::from_str("KRK").expect("Couldn't parse material"));
+  assert_eq!(krk.longest_mate(), 32);
+
+  // Already mated
+  assert_eq!(probe!("k7/1Q6/1K6/8/8/8/8/8 b - - 0 1"), Some(Dtm::Loss(0)));
+
+  // Stalemate
+  assert_eq!(probe!("k7/2Q5/1K6/8/8/8/8/8 b - - 0 1"), Some(Dtm::Draw));
+
+  // Mate in one
+  assert_eq!(probe!("k7/8/1K6/8/8/8/8/7R w - - 0 1"), Some(Dtm::Win(1)));
+
+  // Black to move can capture the undefended queen
+  assert_eq!(probe!("8/8/8/8/8/8/1Q6/1k5K b - - 0 1"), Some(Dtm::Draw));
+
+  // Material that wasn't generated
+  assert_eq!(probe!("k7/8/1K6/8/8/8/8/7B w - - 0 1"), None);
+
+  // Board assembled by hand
+  let mut board = Board::new();
+  board.place_piece(piece!(King, White), coord!(2, 1));
+  board.place_piece(piece!(King, Black), coord!(0, 0));
+  board.place_piece(piece!(Rook, White), coord!(7, 7));
+  assert_eq!(tablebases.probe_dtm(&board), Some(Dtm::Win(1)));
+}
+
+#[test]
+#[ignore = "generating KBNK takes over a minute, run with --ignored"]
+fn tablebase_kbnk() {
+  use crate::tablebase::{Dtm, Material, Tablebases};
+
+  let mut tablebases = Tablebases::new();
+
+  macro_rules! probe {
+    ($fen:expr) => {
+      tablebases.probe_dtm(&Board::from_fen($fen).expect("Couldn't load FEN"))
+    };
+  }
+
+  // The longest mate with bishop and knight takes 33 moves
+  let kbnk = tablebases.generate(&Material::from_str("KBNK").expect("Couldn't parse material"));
+  assert_eq!(kbnk.longest_mate(), 66);
+
+  // Mate in the corner of the bishop's color
+  assert_eq!(
+    probe!("7k/4N3/6K1/4B3/8/8/8/8 b - - 0 1"),
+    Some(Dtm::Loss(0))
+  );
+  assert_eq!(
+    probe!("7k/4N3/6K1/8/5B2/8/8/8 w - - 0 1"),
+    Some(Dtm::Win(1))
+  );
+
+  // Black to move takes the undefended knight
+  assert_eq!(probe!("B7/8/8/8/8/8/1N6/1k5K b - - 0 1"), Some(Dtm::Draw));
+}
+
+#[test]
+fn tablebase_kpk() {
+  use crate::tablebase::{Dtm, Material, Tablebase, Tablebases};
+
+  let mut tablebases = Tablebases::new();
+  tablebases.generate(&Material::from_str("KPK").expect("Couldn't parse material"));
+
+  macro_rules! probe {
+    ($fen:expr) => {
+      tablebases.probe_dtm(&Board::from_fen($fen).expect("Couldn't load FEN"))
+    };
+  }
+
+  // King on the sixth rank in front of the pawn wins
+  assert!(matches!(
+    probe!("4k3/8/4K3/4P3/8/8/8/8 w - - 0 1"),
+    Some(Dtm::Win(_))
+  ));
+
+  // Black has the opposition
+  assert_eq!(probe!("8/8/8/8/8/4k3/4P3/4K3 w - - 0 1"), Some(Dtm::Draw));
+
+  // Rook pawn with the defending king in the corner
+  assert_eq!(probe!("7k/8/8/6KP/8/8/8/8 w - - 0 1"), Some(Dtm::Draw));
+
+  // The pawn queens with mate following
+  assert!(matches!(
+    probe!("8/4P3/8/8/8/8/k7/2K5 w - - 0 1"),
+    Some(Dtm::Win(_))
+  ));
+
+  // En passant captures aren't in the tables, positions with them aren't probed
+  assert!(probe!("4k3/8/8/8/4P3/8/8/4K3 b - - 0 1").is_some());
+  assert_eq!(probe!("4k3/8/8/8/4P3/8/8/4K3 b - e3 0 1"), None);
+
+  // Saving and loading keeps the table intact
+  let dir = std::env::temp_dir().join(format!("chyes-tablebases-{}", std::process::id()));
+  tablebases.save_dir(&dir).expect("Couldn't save tablebases");
+
+  let loaded = Tablebase::load(dir.join("KPK.chtb")).expect("Couldn't load tablebase");
+  let board = Board::from_fen("4k3/8/4K3/4P3/8/8/8/8 w - - 0 1").expect("Couldn't load FEN");
+  assert_eq!(
+    loaded.probe_dtm(&board),
+    probe!("4k3/8/4K3/4P3/8/8/8/8 w - - 0 1")
+  );
+  assert!(Tablebases::load_dir(&dir)
+    .expect("Couldn't load tablebases")
+    .probe_dtm(&Board::from_fen("k7/8/1K6/8/8/8/8/6Q1 w - - 0 1").expect("Couldn't load FEN"))
+    .is_some());
+
+  std::fs::remove_dir_all(&dir).expect("Couldn't remove temporary directory");
+}
+
+#[test]
+fn static_exchange_evaluation() {
+  macro_rules! see {
+    ($fen:expr, $start:expr, $target:expr) => {
+      Board::from_fen($fen)
+        .expect("Couldn't load FEN")
+        .see(Move::new(
+          Coordinate::from_notation($start).expect("Invalid coordinate"),
+          Coordinate::from_notation($target).expect("Invalid coordinate")
+        ))
+    };
+  }
+
+  // Case 1: Undefended pawn
+  assert_eq!(
+    see!(
+      "1k1r4/1pp4p/p7/4p3/8/P5P1/1PP4P/2K1R3 w - - 0 1",
+      "e1",
+      "e5"
+    ),
+    100
+  );
+
+  // Case 2: Knight takes a pawn defended by a knight, x-rays behind both sides
+  assert_eq!(
+    see!(
+      "1k1r3q/1ppn3p/p4b2/4p3/8/P2N2P1/1PP1R1BP/2K1Q3 w - - 0 1",
+      "d3",
+      "e5"
+    ),
+    100 - 320
+  );
+
+  // Case 3: Even pawn trade
+  assert_eq!(
+    see!(
+      "4R3/2r3p1/5bk1/1p1r3p/p2PR1P1/P1BK1P2/1P6/8 b - - 0 1",
+      "h5",
+      "g4"
+    ),
+    0
+  );
+
+  // Case 4: Pawn takes a pawn that can't be recaptured with profit
+  assert_eq!(
+    see!(
+      "2r1r1k1/pp1bppbp/3p1np1/q3P3/2P2P2/1P2B3/P1N1B1PP/2RQ1RK1 b - - 0 1",
+      "d6",
+      "e5"
+    ),
+    100
+  );
+
+  // Case 5: Rook takes a rook defended by a rook, the recapturing queen is behind
+  assert_eq!(
+    see!(
+      "6rr/6pk/p1Qp1b1p/2n5/1B3p2/5p2/P1P2P2/4RK1R w - - 0 1",
+      "e1",
+      "e8"
+    ),
+    -500
+  );
+
+  // Case 6: Quiet move to a square attacked by a pawn
+  assert_eq!(see!("4k3/8/3p4/8/3R4/8/8/4K3 w - - 0 1", "d4", "e5"), -500);
+
+  // Case 7: En passant
+  assert_eq!(see!("4k3/8/8/3pP3/8/8/8/4K3 w - d6 0 1", "e5", "d6"), 100);
+
+  // Case 8: The king recaptures, unless the square is still defended
+  assert_eq!(
+    see!("4k3/4r3/8/8/8/8/4Q3/4K3 w - - 0 1", "e2", "e7"),
+    500 - 900
+  );
+  assert_eq!(see!("4k3/4r3/8/8/8/8/4R3/4R1K1 w - - 0 1", "e2", "e7"), 500);
+
+  // Thresholds
+  let board = Board::from_fen("1k1r3q/1ppn3p/p4b2/4p3/8/P2N2P1/1PP1R1BP/2K1Q3 w - - 0 1")
+    .expect("Couldn't load FEN");
+  let mv = Move::new(
+    Coordinate::from_notation("d3").expect("Invalid coordinate"),
+    Coordinate::from_notation("e5").expect("Invalid coordinate")
+  );
+  assert!(board.see_ge(mv, -220));
+  assert!(!board.see_ge(mv, 0));
+
+  // Pins: the knight on f6 is pinned to its king by the bishop
+  let board = Board::from_fen("7k/8/5n2/3p4/4P3/2B5/8/4K3 w - - 0 1").expect("Couldn't load FEN");
+  let mv = Move::new(
+    Coordinate::from_notation("e4").expect("Invalid coordinate"),
+    Coordinate::from_notation("d5").expect("Invalid coordinate")
+  );
+  assert_eq!(board.see(mv), 0);
+  assert_eq!(board.see_with(mv, true), 100);
+}
+
+#[test]
+fn make_and_unmake_move() {
+  let mut board =
+    Board::from_fen("r1bqkbnr/pppp1ppp/2n5/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R w KQkq - 2 3")
+      .expect("Couldn't load FEN");
+  let fen = board.get_fen();
+
+  // Nxe5 captures a pawn
+  let undo = board
+    .make_move(Move::new(
+      Coordinate::from_notation("f3").expect("Invalid coordinate"),
+      Coordinate::from_notation("e5").expect("Invalid coordinate")
+    ))
+    .expect("Couldn't make move");
+
+  assert_eq!(board.get_turn(), Color::Black);
+  assert_eq!(undo.changes(&board).len(), 2);
+
+  board.unmake_move(undo);
+  assert_eq!(board.get_fen(), fen);
+
+  // Empty start square
+  assert!(board
+    .make_move(Move::new(
+      Coordinate::from_notation("e3").expect("Invalid coordinate"),
+      Coordinate::from_notation("e4").expect("Invalid coordinate")
+    ))
+    .is_err());
+}
+
+#[test]
+fn handcrafted_evaluation() {
+  use crate::eval::{EvalParams, Evaluator};
+
+  let params = EvalParams::default();
+  let eval = |fen: &str| params.evaluate(&Board::from_fen(fen).expect("Couldn't load FEN"));
+
+  // Symmetrical positions are equal
+  assert_eq!(
+    eval("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1"),
+    0
+  );
+
+  // Mirrored positions have the same value for the side to move
+  assert_eq!(
+    eval("r1bqkbnr/pppp1ppp/2n5/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R w KQkq - 2 3"),
+    eval("rnbqkb1r/pppp1ppp/5n2/4p3/4P3/2N5/PPPP1PPP/R1BQKBNR b KQkq - 2 3")
+  );
+
+  // Extra queen
+  assert!(eval("3qk3/8/8/8/8/8/8/4K3 b - - 0 1") > 800);
+  assert!(eval("3qk3/8/8/8/8/8/8/4K3 w - - 0 1") < -800);
+
+  // Passed pawns are worth more than blocked ones
+  assert!(
+    eval("4k3/8/8/3P4/8/8/8/4K3 w - - 0 1") > eval("4k3/8/3p4/3P4/8/8/8/4K3 w - - 0 1") + 100
+  );
+
+  assert_eq!(params.get("queen"), Some(900));
+  assert_eq!(params.get("unknown"), None);
+
+  // Without a network the handcrafted evaluation is used
+  let evaluator = Evaluator::load(None).expect("Couldn't create evaluator");
+  assert!(matches!(evaluator, Evaluator::Handcrafted(_)));
+  assert_eq!(
+    evaluator.evaluate(&Board::default()),
+    params.evaluate(&Board::default())
+  );
+}
+
+#[test]
+fn nnue_incremental_updates() {
+  use crate::eval::{EvalState, Evaluator};
+  use crate::nnue::{Accumulator, Network};
+
+  let network = Network::random(40, 8, 7);
+  let mut board = Board::from_fen("r3k2r/ppp2ppp/2n1bn2/3pp3/3PP3/2N1BN2/PPP2PPP/R3K2R w - - 0 1")
+    .expect("Couldn't load FEN");
+  let start = board.clone();
+
+  let evaluator = Evaluator::Nnue(network);
+  let network = match &evaluator {
+    Evaluator::Nnue(network) => network,
+    _ => unreachable!()
+  };
+  let mut state = EvalState::new(&evaluator, &board);
+  let mut undos = Vec::new();
+
+  // Quiet move, captures, king moves and pawn pushes for both sides
+  for (start, target) in [
+    ("e3", "g5"),
+    ("d5", "e4"),
+    ("c3", "e4"),
+    ("e8", "d7"),
+    ("e1", "e2"),
+    ("f6", "e4"),
+    ("h2", "h3"),
+    ("a7", "a5"),
+    ("e2", "d3")
+  ] {
+    let mv = Move::new(
+      Coordinate::from_notation(start).expect("Invalid coordinate"),
+      Coordinate::from_notation(target).expect("Invalid coordinate")
+    );
+    undos.push(state.make_move(&mut board, mv).expect("Couldn't make move"));
+
+    // Incremental updates match a full refresh
+    assert_eq!(state.evaluate(&board), evaluator.evaluate(&board));
+  }
+
+  // After unmaking everything the evaluation is back to the start
+  while let Some(undo) = undos.pop() {
+    state.unmake_move(&mut board, undo);
+  }
+  assert_eq!(board.get_fen(), start.get_fen());
+  assert_eq!(state.evaluate(&board), evaluator.evaluate(&start));
+
+  // Saving and loading keeps the network intact
+  let path = std::env::temp_dir().join(format!("chyes-network-{}.nnue", std::process::id()));
+  network.save(&path).expect("Couldn't save network");
+
+  let loaded = Evaluator::load(Some(&path)).expect("Couldn't load network");
+  assert_eq!(loaded.evaluate(&start), evaluator.evaluate(&start));
+  std::fs::remove_file(&path).expect("Couldn't remove temporary file");
+
+  // Incremental accumulator equals a freshly computed one
+  let mut accumulator = Accumulator::new(network, &board);
+  let undo = board
+    .make_move(Move::new(
+      Coordinate::from_notation("f3").expect("Invalid coordinate"),
+      Coordinate::from_notation("e5").expect("Invalid coordinate")
+    ))
+    .expect("Couldn't make move");
+  accumulator.update(network, &board, &undo.changes(&board));
+  assert_eq!(accumulator, Accumulator::new(network, &board));
+
+  // Garbage isn't a network
+  assert!(Network::read(&mut &b"not a network"[..]).is_err());
+}
+
+#[test]
+fn evaluation_config() {
+  use crate::eval::EvalParams;
+
+  let mut params = EvalParams::default();
+  params.weights[1] = 333;
+
+  assert_eq!(EvalParams::from_config(&params.to_config()), Ok(params));
+  assert_eq!(
+    EvalParams::from_config("# comment\nknight = 300 # trailing\n\n")
+      .expect("Couldn't parse config")
+      .get("knight"),
+    Some(300)
+  );
+  assert!(EvalParams::from_config("knight 300").is_err());
+  assert!(EvalParams::from_config("horse = 300").is_err());
+  assert!(EvalParams::from_config("knight = many").is_err());
+}
+
+#[test]
+fn texel_tuning() {
+  use crate::eval::EvalParams;
+  use crate::tuning::{sigmoid, Dataset, Method, Tuner};
+
+  let dataset = Dataset::parse(
+    "4k3/8/8/8/8/8/8/1N2K3 w - - 0 1 [0.5]
+     4k3/8/8/8/8/8/8/1N2K3 b - - 0 1; 1/2-1/2
+     4k3/8/8/8/8/8/P7/1N2K3 w - - 0 1 \"1-0\"
+     1n2k3/8/8/8/8/8/8/4K3 w - - 0 1 [0.5]
+     1n2k3/p7/8/8/8/8/8/4K3 b - - 0 1 0-1
+     4k3/8/8/8/8/8/PPP5/4K3 w - - 0 1 [1.0]
+     4k3/ppp5/8/8/8/8/8/4K3 w - - 0 1 [0.0]"
+  )
+  .expect("Couldn't parse dataset");
+  assert_eq!(dataset.len(), 7);
+
+  assert!(Dataset::parse("4k3/8/8/8/8/8/8/4K3 w - - 0 1").is_err());
+  assert!(Dataset::parse("4k3/8/8/8/8/8/8/4K3 w - - 0 1 [win]").is_err());
+
+  // EPD lines without the clocks
+  let epd = Dataset::parse(
+    "4k3/8/8/8/8/8/P7/1N2K3 w - - c9 \"1-0\";
+     1n2k3/p7/8/8/8/8/8/4K3 b - - c9 \"0-1\";
+     4k3/8/8/8/8/8/8/1N2K3 b - - 0.5"
+  )
+  .expect("Couldn't parse EPD");
+  assert_eq!(epd.len(), 3);
+  assert!(Dataset::parse("4k3/8/8/8/8/8/8/4K3 w - - c9").is_err());
+
+  assert_eq!(sigmoid(0.0, 1.0), 0.5);
+  assert!(sigmoid(400.0, 1.0) > 0.9);
+
+  let params = EvalParams::default();
+  let tuner = Tuner::new(&dataset, &params);
+  let error = tuner.mean_squared_error(&params);
+
+  let mut passes = 0;
+  let tuned = tuner.tune(
+    &params,
+    Method::LocalSearch {
+      step: 10
+    },
+    20,
+    |_, _| passes += 1
+  );
+  assert!(passes > 0);
+  assert!(tuner.mean_squared_error(&tuned) < error);
+
+  // Lone knights are drawn, so the tuner makes them worth less
+  assert!(tuned.get("knight") < params.get("knight"));
+
+  let tuned = tuner.tune(
+    &params,
+    Method::GradientDescent {
+      learning_rate: 10_000.0
+    },
+    50,
+    |_, _| {}
+  );
+  assert!(tuner.mean_squared_error(&tuned) < error);
+}
+
+#[test]
+fn castling_en_passant_and_clocks() {
+  let mut board = Board::from_fen("r3k2r/pppq1ppp/8/8/8/8/PPPQ1PPP/R3K2R w KQkq - 4 10")
+    .expect("Couldn't load FEN");
+  let king = Coordinate::from_str("e1").unwrap();
+
+  let targets = board.generate_moves(king).unwrap();
+  assert!(targets.contains(&Coordinate::from_str("g1").unwrap()));
+  assert!(targets.contains(&Coordinate::from_str("c1").unwrap()));
+
+  let undo = board
+    .make_move(Move::new(king, Coordinate::from_str("g1").unwrap()))
+    .unwrap();
+  assert_eq!(
+    board.get_fen(),
+    "r3k2r/pppq1ppp/8/8/8/8/PPPQ1PPP/R4RK1 b kq - 5 10"
+  );
+  board.unmake_move(undo);
+  assert_eq!(
+    board.get_fen(),
+    "r3k2r/pppq1ppp/8/8/8/8/PPPQ1PPP/R3K2R w KQkq - 4 10"
+  );
+
+  // Can't castle through an attacked square
+  let board = Board::from_fen("4k3/8/8/8/8/8/5r2/R3K2R w KQ - 0 1").expect("Couldn't load FEN");
+  let targets = board.generate_moves(coord!(7, 4)).unwrap();
+  assert!(!targets.contains(&coord!(7, 6)));
+  assert!(targets.contains(&coord!(7, 2)));
+
+  // Capturing en passant removes the pawn and resets the halfmove clock
+  let mut board = Board::from_fen("4k3/8/8/3pP3/8/8/8/4K3 w - d6 7 30").expect("Couldn't load FEN");
+  board
+    .move_piece(
+      Coordinate::from_str("e5").unwrap(),
+      Coordinate::from_str("d6").unwrap()
+    )
+    .unwrap();
+  assert_eq!(board.get_fen(), "4k3/8/3P4/8/8/8/8/4K3 b - - 0 30");
+
+  // A double step played on the board leaves the square it passed, which
+  // the pawn next to it can take on
+  let mut board = Board::from_fen("4k3/3p4/8/4P3/8/8/8/4K3 b - - 0 1").expect("Couldn't load FEN");
+  let undo = board
+    .make_move(Move::new(coord!(1, 3), coord!(3, 3)))
+    .unwrap();
+  assert_eq!(board.get_fen(), "4k3/8/8/3pP3/8/8/8/4K3 w - d6 0 2");
+  let capture = Move::new(coord!(3, 4), coord!(2, 3));
+  assert!(board.legal_moves().contains(&capture));
+  let after_capture = board.make_move(capture).unwrap();
+  assert_eq!(board.get_fen(), "4k3/8/3P4/8/8/8/8/4K3 b - - 0 2");
+  board.unmake_move(after_capture);
+  board.unmake_move(undo);
+  assert_eq!(board.get_fen(), "4k3/3p4/8/4P3/8/8/8/4K3 b - - 0 1");
+
+  // The same for black after a white double step
+  let mut board = Board::from_fen("4k3/8/8/8/5p2/8/4P3/4K3 w - - 0 1").expect("Couldn't load FEN");
+  board
+    .make_move(Move::new(coord!(6, 4), coord!(4, 4)))
+    .unwrap();
+  assert_eq!(board.get_fen(), "4k3/8/8/8/4Pp2/8/8/4K3 b - e3 0 1");
+  assert!(board
+    .legal_moves()
+    .contains(&Move::new(coord!(4, 5), coord!(5, 4))));
+
+  // A king is in check from nothing but it may not walk next to the other king
+  let board = Board::from_fen("8/8/8/3k4/8/3K4/8/8 w - - 0 1").expect("Couldn't load FEN");
+  assert!(!board
+    .generate_moves(coord!(5, 3))
+    .unwrap()
+    .contains(&coord!(4, 3)));
+}
+
+#[test]
+fn perft() {
+  // Leaf positions after every sequence of legal moves
+  fn perft(
+    board: &mut Board,
+    depth: u32
+  ) -> usize {
+    let moves = board.legal_moves();
+    if depth == 1 {
+      return moves.len()
+    }
+
+    moves
+      .into_iter()
+      .map(|mv| {
+        let undo = board.make_move(mv).expect("Couldn't make move");
+        let nodes = perft(board, depth - 1);
+        board.unmake_move(undo);
+        nodes
+      })
+      .sum()
+  }
+
+  // Positions from the chess programming wiki, with castling, en passant,
+  // promotions and pins between them
+  for (fen, counts) in [
+    (
+      "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+      &[20, 400, 8902][..]
+    ),
+    (
+      "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+      &[48, 2039, 97862]
+    ),
+    (
+      "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+      &[14, 191, 2812]
+    ),
+    (
+      "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1",
+      &[6, 264, 9467]
+    ),
+    (
+      "rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8",
+      &[44, 1486, 62379]
+    )
+  ] {
+    let mut board = Board::from_fen(fen).expect("Couldn't load FEN");
+    for (depth, &count) in counts.iter().enumerate() {
+      assert_eq!(
+        perft(&mut board, depth as u32 + 1),
+        count,
+        "{} at depth {}",
+        fen,
+        depth + 1
+      );
+    }
+    assert_eq!(board.get_fen(), fen);
+  }
+}
+
+#[test]
+fn standard_algebraic_notation() {
+  let board =
+    Board::from_fen("r3k2r/8/8/8/8/2N3N1/8/R3K2R w KQkq - 0 1").expect("Couldn't load FEN");
+
+  let mv = board.parse_san("Nce4").unwrap();
+  assert_eq!(mv.to_string(), "c3e4");
+  assert_eq!(board.to_san(mv), "Nce4");
+  assert!(board.parse_san("Ne4").is_err());
+  assert_eq!(board.to_san(board.parse_san("O-O-O").unwrap()), "O-O-O");
+  assert_eq!(board.to_san(board.parse_uci("a1a8").unwrap()), "Rxa8+");
+
+  let board = Board::from_fen("7k/4P1pp/8/8/8/8/8/6K1 w - - 0 1").expect("Couldn't load FEN");
+  assert_eq!(board.to_san(board.parse_san("e8=Q").unwrap()), "e8=Q#");
+  assert_eq!(board.to_uci(board.parse_san("e8Q+").unwrap()), "e7e8q");
+}
+
+#[test]
+fn game_outcomes_and_pgn() {
+  use crate::game::{Game, GameResult, Outcome};
+
+  // Fool's mate
+  let mut game = Game::default();
+  for san in ["f3", "e5", "g4", "Qh4#"] {
+    let mv = game.board().parse_san(san).unwrap();
+    game.play(mv).unwrap();
+  }
+  assert_eq!(game.outcome(), Some(Outcome::Checkmate(Color::Black)));
+  assert!(game.play(Move::new(coord!(6, 0), coord!(5, 0))).is_err());
+  assert_eq!(
+    game.to_pgn(&[("White", String::from("A"))]),
+    "[Event \"?\"]\n[Site \"?\"]\n[Date \"????.??.??\"]\n[Round \"?\"]\n[White \"A\"]\n[Black \
+     \"?\"]\n[Result \"0-1\"]\n\n1. f3 e5 2. g4 Qh4# 0-1\n"
+  );
+
+  // Knights shuffling back and forth
+  let mut game = Game::default();
+  for _ in 0..2 {
+    for san in ["Nf3", "Nf6", "Ng1", "Ng8"] {
+      assert_eq!(game.outcome(), None);
+      let mv = game.board().parse_san(san).unwrap();
+      game.play(mv).unwrap();
+    }
+  }
+  // Threefold repetition has to be claimed, fivefold ends the game
+  assert_eq!(game.outcome(), None);
+  assert_eq!(game.claimable_draw(), Some(Outcome::Repetition));
+  let mut claimed = game.clone();
+  assert_eq!(claimed.claim_draw(), Ok(Outcome::Repetition));
+  assert_eq!(claimed.outcome(), Some(Outcome::Repetition));
+  for _ in 0..2 {
+    for san in ["Nf3", "Nf6", "Ng1", "Ng8"] {
+      assert_eq!(game.outcome(), None);
+      let mv = game.board().parse_san(san).unwrap();
+      game.play(mv).unwrap();
+    }
+  }
+  assert_eq!(game.outcome(), Some(Outcome::Repetition));
+  assert_eq!(game.claimable_draw(), None);
+  game.undo();
+  assert_eq!(game.outcome(), None);
+  assert_eq!(game.claimable_draw(), Some(Outcome::Repetition));
+  let mut game = Game::default();
+  assert_eq!(game.claimable_draw(), None);
+  assert!(game.claim_draw().is_err());
+
+  // Fifty moves have to be claimed, 75 end the game
+  let mut game = Game::from_fen("8/8/4k3/8/8/3RK3/8/8 w - - 99 80").unwrap();
+  game.play(game.board().parse_san("Rd1").unwrap()).unwrap();
+  assert_eq!(game.outcome(), None);
+  assert_eq!(game.claimable_draw(), Some(Outcome::FiftyMoves));
+  let mut game = Game::from_fen("8/8/4k3/8/8/3RK3/8/8 w - - 149 100").unwrap();
+  game.play(game.board().parse_san("Rd1").unwrap()).unwrap();
+  assert_eq!(game.outcome(), Some(Outcome::FiftyMoves));
+
+  let game = Game::from_fen("8/8/4k3/8/8/3BK3/8/8 b - - 0 1").unwrap();
+  assert_eq!(game.outcome(), Some(Outcome::InsufficientMaterial));
+  assert_eq!(game.outcome().unwrap().result(), GameResult::Draw);
+}
+
+#[test]
+fn search_finds_mates() {
+  use std::sync::atomic::AtomicBool;
+
+  use crate::eval::Evaluator;
+  use crate::search::{search, Limits, Score};
+
+  let evaluator = Evaluator::default();
+  let stop = AtomicBool::new(false);
+  let limits = Limits {
+    depth: Some(4),
+    ..Limits::default()
+  };
+
+  // Back rank mate
+  let board = Board::from_fen("6k1/5ppp/8/8/8/8/5PPP/R5K1 w - - 0 1").expect("Couldn't load FEN");
+  let result = search(&board, &[], &evaluator, &limits, &stop, |_| {});
+  assert_eq!(result.best_move.unwrap().to_string(), "a1a8");
+  assert_eq!(result.score, Score::Mate(1));
+
+  // Mate in two with a queen sacrifice
+  let board = Board::from_fen("3r3k/6pp/7N/8/2Q5/8/8/6K1 w - - 0 1").expect("Couldn't load FEN");
+  let result = search(&board, &[], &evaluator, &limits, &stop, |_| {});
+  assert_eq!(result.score, Score::Mate(2));
+
+  // Winning a hanging queen
+  let board = Board::from_fen("4k3/8/8/3q4/8/8/3R4/4K3 w - - 0 1").expect("Couldn't load FEN");
+  let result = search(&board, &[], &evaluator, &limits, &stop, |_| {});
+  assert_eq!(result.best_move.unwrap().to_string(), "d2d5");
+}
+
+#[test]
+fn uci_session() {
+  use std::io::{Cursor, Write};
+  use std::sync::{Arc, Mutex};
+
+  use crate::eval::Evaluator;
+
+  #[derive(Clone, Default)]
+  struct Output(Arc<Mutex<Vec<u8>>>);
+
+  impl Write for Output {
+    fn write(
+      &mut self,
+      buf: &[u8]
+    ) -> std::io::Result<usize> {
+      self.0.lock().unwrap().write(buf)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> { Ok(()) }
+  }
+
+  let output = Output::default();
+  let input = Cursor::new("uci\nisready\nposition startpos moves e2e4 e7e5\ngo depth 2\nquit\n");
+  uci::serve(Evaluator::default(), input, output.clone());
+
+  let text = String::from_utf8(output.0.lock().unwrap().clone()).unwrap();
+  assert!(text.contains("uciok"));
+  assert!(text.contains("readyok"));
+
+  // The reply is always a legal move, even when quitting cuts the search short
+  let best = text
+    .lines()
+    .find_map(|line| line.strip_prefix("bestmove "))
+    .expect("No best move");
+  let board =
+    Board::from_fen("rnbqkbnr/pppp1ppp/8/4p3/4P3/8/PPPP1PPP/RNBQKBNR w KQkq - 0 2").unwrap();
+  assert!(board.parse_uci(best).is_ok());
+}
+
+#[test]
+fn match_statistics() {
+  use crate::selfplay::{self, MatchConfig, Sprt, SprtResult, Stats, TimeControl};
+
+  let stats = Stats {
+    wins:   180,
+    draws:  60,
+    losses: 60
+  };
+  assert_eq!(stats.score(), 0.7);
+  let (elo, margin) = stats.elo().unwrap();
+  assert!((elo - 147.2).abs() < 0.1);
+  assert!(margin > 30.0 && margin < 45.0);
+
+  let sprt: Sprt = "elo0=0,elo1=10".parse().unwrap();
+  let (lower, upper) = sprt.bounds();
+  assert!((lower + 2.944).abs() < 0.001 && (upper - 2.944).abs() < 0.001);
+  assert_eq!(sprt.result(&stats), Some(SprtResult::AcceptH1));
+  assert_eq!(
+    sprt.result(&Stats {
+      wins:   60,
+      draws:  60,
+      losses: 180
+    }),
+    Some(SprtResult::AcceptH0)
+  );
+  assert_eq!(sprt.result(&Stats::default()), None);
+  assert!("elo0=5,elo1=0".parse::<Sprt>().is_err());
+
+  assert_eq!(
+    "10+0.1".parse::<TimeControl>(),
+    Ok(TimeControl {
+      base:      std::time::Duration::from_secs(10),
+      increment: std::time::Duration::from_millis(100)
+    })
+  );
+
+  // A quick in-process match
+  let mut config = MatchConfig {
+    games: 2,
+    time_control: None,
+    ..MatchConfig::default()
+  };
+  config.engines[0].depth = Some(1);
+  config.engines[1].depth = Some(1);
+  config.adjudication.max_moves = Some(5);
+
+  let mut games = 0;
+  let stats = selfplay::run(&config, |record, _| {
+    games += 1;
+    assert!(record
+      .to_pgn(&config)
+      .contains("[Termination \"Draw by move limit\"]"));
+  })
+  .unwrap();
+  assert_eq!((games, stats.draws), (2, 2));
+
+  // An engine that quits after the handshake can't start a game, which ends
+  // the match instead of counting as a draw
+  config.engines[1] = "cmd=sh,arg=-c,arg=read line; echo uciok; read line; echo readyok"
+    .parse()
+    .unwrap();
+  let result = selfplay::run(&config, |_, _| panic!("No game was played"));
+  assert!(result.unwrap_err().contains("couldn't start game 1"));
+}
+
+#[test]
+fn app_move_entry() {
+  use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+
+  use crate::app::App;
+
+  let mut app = App::default();
+  let press =
+    |app: &mut App, code: KeyCode| app.handle_key(KeyEvent::new(code, KeyModifiers::NONE));
+
+  // The cursor starts on e2
+  press(&mut app, KeyCode::Enter);
+  assert_eq!(app.selected(), Some(coord!(6, 4)));
+
+  // Three squares up is out of reach
+  for _ in 0..3 {
+    press(&mut app, KeyCode::Char('k'));
+  }
+  press(&mut app, KeyCode::Char(' '));
+  assert_eq!(app.message(), Some("Illegal move e2e5"));
+  assert!(app.game().moves().is_empty());
+
+  press(&mut app, KeyCode::Down);
+  press(&mut app, KeyCode::Enter);
+  assert_eq!(app.game().moves().len(), 1);
+  assert_eq!(app.selected(), None);
+  assert_eq!(app.status(), "Black to move");
+
+  // White pieces can't move on black's turn
+  for _ in 0..4 {
+    press(&mut app, KeyCode::Char('j'));
+  }
+  press(&mut app, KeyCode::Enter);
+  assert_eq!(app.cursor(), coord!(7, 4));
+  assert_eq!(app.selected(), None);
+  assert_eq!(app.message(), Some("It's black to move"));
+}
+
+#[test]
+fn app_mouse_input() {
+  use crossterm::event::{KeyModifiers, MouseButton, MouseEvent, MouseEventKind};
+  use tui::backend::TestBackend;
+  use tui::Terminal;
+
+  use crate::app::App;
+  use crate::widget::BoardWidget;
+
+  // An 8x8 area only fits single cell squares without labels
+  let board = Board::default();
+  let widget = BoardWidget::new(&board);
+  let area = tui::layout::Rect::new(3, 2, 8, 8);
+  assert_eq!(widget.square_at(area, 3, 2), Some(coord!(0, 0)));
+  assert_eq!(widget.square_at(area, 7, 8), Some(coord!(6, 4)));
+  assert_eq!(widget.square_at(area, 11, 2), None);
+  assert_eq!(
+    BoardWidget::new(&board).flipped(true).square_at(area, 3, 2),
+    Some(coord!(7, 7))
+  );
+
+  let mut terminal = Terminal::new(TestBackend::new(60, 20)).unwrap();
+  let mut app = App::default();
+  terminal.draw(|f| app.draw(f)).unwrap();
+
+  // Presses the mouse on the middle of a square
+  let mouse = |app: &mut App, kind: MouseEventKind, notation: &'static str| {
+    let square = BoardWidget::new(&board).square_area(
+      app.board_area(),
+      Coordinate::from_notation(notation).unwrap()
+    );
+    app.handle_mouse(MouseEvent {
+      kind,
+      column: square.x + square.width / 2,
+      row: square.y + square.height / 2,
+      modifiers: KeyModifiers::NONE
+    })
+  };
+  let down = MouseEventKind::Down(MouseButton::Left);
+  let up = MouseEventKind::Up(MouseButton::Left);
+  let drag = MouseEventKind::Drag(MouseButton::Left);
+
+  // Click e2, click e4
+  mouse(&mut app, down, "e2");
+  mouse(&mut app, up, "e2");
+  assert_eq!(app.selected(), Some(coord!(6, 4)));
+  mouse(&mut app, down, "e4");
+  mouse(&mut app, up, "e4");
+  assert_eq!(app.game().moves().len(), 1);
+
+  // Drag g8 to f6
+  mouse(&mut app, down, "g8");
+  mouse(&mut app, drag, "f7");
+  mouse(&mut app, drag, "f6");
+  mouse(&mut app, up, "f6");
+  assert_eq!(app.game().moves().len(), 2);
+  assert_eq!(app.game().moves()[1].to_string(), "g8f6");
+
+  // Clicking the selected piece again deselects it
+  mouse(&mut app, down, "b1");
+  mouse(&mut app, up, "b1");
+  assert_eq!(app.selected(), Some(coord!(7, 1)));
+  mouse(&mut app, down, "b1");
+  mouse(&mut app, up, "b1");
+  assert_eq!(app.selected(), None);
+}
+
+#[test]
+fn board_widget_scaling() {
+  use tui::buffer::Buffer;
+  use tui::layout::Rect;
+  use tui::widgets::Widget;
+
+  use crate::widget::BoardWidget;
+
+  let board = Board::default();
+  let text = |buf: &Buffer, y: u16| -> String {
+    (0..buf.area.width)
+      .map(|x| buf.get(x, y).symbol.clone())
+      .collect()
+  };
+
+  // 3x1 squares with labels
+  let area = Rect::new(0, 0, 26, 9);
+  let mut buf = Buffer::empty(area);
+  BoardWidget::new(&board).render(area, &mut buf);
+  assert_eq!(text(&buf, 0), "8  ♖  ♘  ♗  ♕  ♔  ♗  ♘  ♖ ");
+  assert_eq!(text(&buf, 8), "   a  b  c  d  e  f  g  h ");
+  assert_eq!(
+    BoardWidget::new(&board).square_area(area, coord!(7, 7)),
+    Rect::new(23, 7, 3, 1)
+  );
+
+  // 7x3 squares with ASCII art, flipped
+  let area = Rect::new(0, 0, 58, 25);
+  let mut buf = Buffer::empty(area);
+  BoardWidget::new(&board)
+    .flipped(true)
+    .ascii_art(true)
+    .render(area, &mut buf);
+  assert_eq!(&text(&buf, 0)[..16], "    n_n     >)  ");
+  assert_eq!(&text(&buf, 1)[..16], "1   (R)    (N)  ");
+  assert_eq!(
+    text(&buf, 24).trim_end(),
+    "     h      g      f      e      d      c      b      a"
+  );
+  assert_eq!(
+    BoardWidget::new(&board)
+      .flipped(true)
+      .square_at(area, 3, 23),
+    Some(coord!(0, 7))
+  );
+
+  // Too small areas are clipped
+  let area = Rect::new(0, 0, 5, 3);
+  let mut buf = Buffer::empty(area);
+  BoardWidget::new(&board).render(area, &mut buf);
+  assert_eq!(text(&buf, 0), "♖♘♗♕♔");
+  assert_eq!(BoardWidget::new(&board).square_at(area, 4, 3), None);
+}
+
+#[test]
+fn board_view_orientation() {
+  use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+
+  use crate::app::App;
+  use crate::game::Game;
+  use crate::view::{BoardView, Orientation};
+
+  let mut board = Board::new();
+  board.load_fen("4k3/8/8/8/8/8/8/R3K3 w - - 0 1").unwrap();
+
+  assert_eq!(board.to_string(), BoardView::new(&board).to_string());
+  assert!(BoardView::new(&board)
+    .to_string()
+    .ends_with("........\n♜...♚...\n"));
+  assert!(BoardView::new(&board)
+    .orientation(Orientation::Black)
+    .to_string()
+    .starts_with("...♚...♜\n"));
+
+  // Side to move stays put, auto flip follows the turn
+  board.move_piece(coord!(7, 0), coord!(6, 0)).unwrap();
+  let view = BoardView::new(&board).first_to_move(Color::White);
+  assert!(!view.orientation(Orientation::SideToMove).is_flipped());
+  assert!(view.orientation(Orientation::AutoFlip).is_flipped());
+  assert_eq!("auto".parse(), Ok(Orientation::AutoFlip));
+
+  // Flipping the TUI board also flips the cursor keys
+  let key = |code| KeyEvent::new(code, KeyModifiers::NONE);
+  let mut app = App::new(Game::default());
+  app.handle_key(key(KeyCode::Char('f')));
+  assert_eq!(app.orientation(), Orientation::Black);
+  app.handle_key(key(KeyCode::Up));
+  assert_eq!(app.cursor(), coord!(7, 4));
+  app.handle_key(key(KeyCode::Char('o')));
+  assert_eq!(app.orientation(), Orientation::SideToMove);
+  assert!(!app.view().is_flipped());
+}
+
+#[test]
+fn themes_and_glyphs() {
+  use tui::style::Color as TermColor;
+
+  use crate::config::Config;
+  use crate::theme::{ColorDepth, GlyphSet, Theme};
+  use crate::view::BoardView;
+
+  let board = Board::default();
+  let view = BoardView::new(&board).glyphs(GlyphSet::ASCII).to_string();
+  assert!(view.starts_with("rnbqkbnr\npppppppp\n"));
+  assert!(view.ends_with("RNBQKBNR\n"));
+
+  let custom: GlyphSet = "KDTLSBkdtlsb".parse().unwrap();
+  assert_eq!(custom.glyph(board.get_piece(&coord!(7, 1)).unwrap()), 'S');
+  assert!("abc".parse::<GlyphSet>().is_err());
+  assert_eq!("outline".parse(), Ok(GlyphSet::OUTLINE));
+
+  // Theme files start from a built in theme
+  let theme =
+    Theme::parse("# Mine\nbase = \"wood\"\nlight = \"#ffffff\" # white\ndark = 94\ncheck = red\n")
+      .unwrap();
+  assert_eq!(theme.light, TermColor::Rgb(255, 255, 255));
+  assert_eq!(theme.dark, TermColor::Indexed(94));
+  assert_eq!(theme.check, TermColor::Red);
+  assert_eq!(theme.white_piece, Theme::named("wood").unwrap().white_piece);
+  assert!(Theme::parse("light = nope").is_err());
+  assert!(Theme::parse("border = red").is_err());
+
+  // Fallbacks for terminals without true color
+  let theme = Theme::named("wood").unwrap();
+  assert_eq!(theme.with_depth(ColorDepth::TrueColor), theme);
+  assert_eq!(
+    theme.with_depth(ColorDepth::Ansi256).light,
+    TermColor::Indexed(223)
+  );
+  assert_eq!(
+    theme.with_depth(ColorDepth::Ansi256).white_piece,
+    TermColor::Indexed(231)
+  );
+  assert_eq!(theme.with_depth(ColorDepth::Ansi16).light, TermColor::Gray);
+  assert_eq!(
+    theme.with_depth(ColorDepth::Ansi16).black_piece,
+    TermColor::Black
+  );
+
+  let config = Config::parse("[board]\ntheme = \"green\"\npieces = ascii\n").unwrap();
+  assert_eq!(config.theme.as_deref(), Some("green"));
+  assert_eq!(config.pieces.as_deref(), Some("ascii"));
+  assert!(Config::parse("speed = 3").is_err());
+}
+
+#[test]
+fn app_layout_and_browsing() {
+  use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+  use tui::backend::TestBackend;
+  use tui::Terminal;
+
+  use crate::app::{captured, App};
+  use crate::game::Game;
+
+  let mut game = Game::default();
+  for san in ["e4", "d5", "exd5", "Qxd5", "Nc3"] {
+    let mv = game.board().parse_san(san).unwrap();
+    game.play(mv).unwrap();
+  }
+
+  let mut app = App::new(game);
+  let mut terminal = Terminal::new(TestBackend::new(80, 24)).unwrap();
+  let screen = |terminal: &mut Terminal<TestBackend>, app: &mut App| -> String {
+    terminal.draw(|f| app.draw(f)).unwrap();
+    let buffer = terminal.backend().buffer();
+    (0..buffer.area.height)
+      .map(|y| {
+        (0..buffer.area.width)
+          .map(|x| buffer.get(x, y).symbol.clone())
+          .collect::<String>()
+      })
+      .collect::<Vec<_>>()
+      .join("\n")
+  };
+
+  let text = screen(&mut terminal, &mut app);
+  assert!(text.contains("1. e4      d5"));
+  assert!(text.contains("2. exd5    Qxd5"));
+  assert!(text.contains("3. Nc3"));
+  assert!(text.contains("W ♙ "));
+  assert!(text.contains("B ♟ "));
+  assert!(text.contains("Black to move"));
+  assert!(text.contains("FEN rnb1kbnr/ppp1pppp/8/3q4/8/2N5/PPPP1PPP/R1BQKBNR b KQkq - 1 3"));
+
+  // Stepping back through the moves
+  let key = |code| KeyEvent::new(code, KeyModifiers::NONE);
+  app.handle_key(key(KeyCode::Char('[')));
+  app.handle_key(key(KeyCode::Char('[')));
+  assert_eq!(app.ply(), 3);
+  let text = screen(&mut terminal, &mut app);
+  assert!(text.contains("Black to move (move 3 of 5)"));
+  assert!(text.contains("W ♙ +1"));
+  app.activate(coord!(0, 3));
+  assert_eq!(app.message(), Some("Press End to go back to the game"));
+
+  app.handle_key(key(KeyCode::Home));
+  assert_eq!(app.view().board().get_fen(), Board::default().get_fen());
+  app.handle_key(key(KeyCode::End));
+  assert_eq!(app.ply(), 5);
+  app.handle_key(key(KeyCode::Char('y')));
+  assert_eq!(app.take_clipboard(), Some(app.game().board().get_fen()));
+
+  // The move list follows moves played and taken back
+  app.run_command("undo").unwrap();
+  app.run_command("undo").unwrap();
+  app.run_command("Nf6").unwrap();
+  let text = screen(&mut terminal, &mut app);
+  assert!(text.contains("2. exd5    Nf6"), "{}", text);
+  assert!(!text.contains("Qxd5"));
+  assert!(!text.contains("3. Nc3"));
+
+  // A bishop for a knight leaves nobody a pawn ahead
+  app
+    .run_command("fen rnbqkb1r/pppppppp/8/8/8/8/PPPPPPPP/RN1QKBNR w KQkq - 0 1")
+    .unwrap();
+  let text = screen(&mut terminal, &mut app);
+  assert!(text.contains("W ♘ "), "{}", text);
+  assert!(text.contains("B ♝ "));
+  assert!(!text.contains("+0"));
+
+  // Promoted pieces stand in for the pawns they came from
+  let mut board = Board::new();
+  board
+    .load_fen("QQ2k3/8/8/8/8/8/PPPPPP2/RNB1KBNR w - - 0 1")
+    .unwrap();
+  assert_eq!(captured(&board, Color::White), vec![Pieces::Pawn]);
+  assert_eq!(captured(&board, Color::Black).len(), 15);
+}
+
+#[test]
+fn pgn_reading() {
+  use crate::game::GameResult;
+  use crate::pgn;
+
+  let text = "[Event \"Test \\\"quoted\\\"\"]\n[White \"A\"]\n[Result \"0-1\"]\n\n1. e4 {Best by \
+              test} e5 (1... c5 2. Nf3 $1) 2. Nf3!? Nc6 $2 3.Bb5 a6 ; comment to the end\n4. O-O \
+              0-1\n\n[Event \"Second\"]\n[SetUp \"1\"]\n[FEN \"4k3/8/8/8/8/8/4P3/4K3 w - - 0 \
+              1\"]\n\n1. e4 Kd7 *\n";
+
+  let games = pgn::parse(text).unwrap();
+  assert_eq!(games.len(), 2);
+  assert_eq!(games[0].tag("Event"), Some("Test \"quoted\""));
+  assert_eq!(games[0].game.moves().len(), 7);
+  assert_eq!(games[0].game.comments()[0].as_deref(), Some("Best by test"));
+  assert_eq!(
+    games[0].game.outcome().map(|outcome| outcome.result()),
+    Some(GameResult::BlackWins)
+  );
+  assert_eq!(games[1].game.san_moves(), vec!["e4", "Kd7"]);
+  assert!(games[1].game.outcome().is_none());
+
+  // Saved games read back the same
+  let pgn = games[0].game.to_pgn(&[("White", String::from("A"))]);
+  let again = pgn::parse(&pgn).unwrap();
+  assert_eq!(again[0].game.moves(), games[0].game.moves());
+  assert_eq!(again[0].game.to_pgn(&[("White", String::from("A"))]), pgn);
+
+  assert!(pgn::parse("1. e4 e5 2. Ke3").is_err());
+
+  // A brace in a comment would end it early
+  let mut game = crate::game::Game::default();
+  game.play(game.board().parse_san("e4").unwrap()).unwrap();
+  game.comment(String::from("Best} e5 by test"));
+  let pgn = game.to_annotated_pgn(&[], &[], &[Some(String::from("{a} b}"))]);
+  assert!(pgn.contains("1. e4 {{a b} *"), "{}", pgn);
+  let again = pgn::parse(&game.to_pgn(&[])).unwrap();
+  assert_eq!(again[0].game.moves(), game.moves());
+  assert_eq!(
+    again[0].game.comments()[0].as_deref(),
+    Some("Best e5 by test")
+  );
+
+  // Games go on after a threefold repetition nobody claimed
+  let games = pgn::parse("1. Nf3 Nf6 2. Ng1 Ng8 3. Nf3 Nf6 4. Ng1 Ng8 5. e4 e5 1-0").unwrap();
+  assert_eq!(games[0].game.moves().len(), 10);
+}
+
+#[test]
+fn app_command_line() {
+  use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+
+  use crate::app::App;
+
+  let mut app = App::default();
+  let key = |app: &mut App, code| app.handle_key(KeyEvent::new(code, KeyModifiers::NONE));
+  let type_line = |app: &mut App, text: &str| {
+    key(app, KeyCode::Char(':'));
+    for c in text.chars() {
+      key(app, KeyCode::Char(c));
+    }
+    key(app, KeyCode::Enter);
+  };
+
+  // SAN and UCI moves
+  type_line(&mut app, "e4");
+  type_line(&mut app, "e7e5");
+  assert_eq!(app.game().san_moves(), vec!["e4", "e5"]);
+  type_line(&mut app, "Ke3");
+  assert_eq!(app.message(), Some("Illegal move Ke3"));
+  assert_eq!(app.command(), None);
+
+  // Tab completes a unique move, and cycles through several
+  key(&mut app, KeyCode::Char(':'));
+  key(&mut app, KeyCode::Char('N'));
+  key(&mut app, KeyCode::Char('f'));
+  key(&mut app, KeyCode::Tab);
+  assert_eq!(app.command(), Some("Nf3"));
+  key(&mut app, KeyCode::Esc);
+  key(&mut app, KeyCode::Char(':'));
+  key(&mut app, KeyCode::Char('N'));
+  key(&mut app, KeyCode::Tab);
+  assert_eq!(app.command(), Some("Na3"));
+  key(&mut app, KeyCode::Tab);
+  assert_eq!(app.command(), Some("Nc3"));
+  key(&mut app, KeyCode::Enter);
+  assert_eq!(app.game().moves().len(), 3);
+
+  // History
+  key(&mut app, KeyCode::Char(':'));
+  key(&mut app, KeyCode::Up);
+  assert_eq!(app.command(), Some("Nc3"));
+  key(&mut app, KeyCode::Up);
+  assert_eq!(app.command(), Some("Ke3"));
+  key(&mut app, KeyCode::Down);
+  key(&mut app, KeyCode::Down);
+  assert_eq!(app.command(), Some(""));
+  key(&mut app, KeyCode::Esc);
+
+  type_line(&mut app, "undo");
+  assert_eq!(app.game().moves().len(), 2);
+  type_line(&mut app, "flip");
+  assert!(app.view().is_flipped());
+
+  // Saving and loading
+  let path = std::env::temp_dir().join(format!("chyes-{}.pgn", std::process::id()));
+  let path = path.to_str().unwrap();
+  type_line(&mut app, &format!("save {}", path));
+  type_line(&mut app, "new");
+  assert!(app.game().moves().is_empty());
+  type_line(&mut app, &format!("load {}", path));
+  assert_eq!(app.game().san_moves(), vec!["e4", "e5"]);
+  std::fs::remove_file(path).unwrap();
+
+  type_line(&mut app, "fen 4k3/8/8/8/8/8/8/4K2R w K - 0 1");
+  assert_eq!(
+    app.game().board().get_fen(),
+    "4k3/8/8/8/8/8/8/4K2R w K - 0 1"
+  );
+  type_line(&mut app, "fen nonsense");
+  assert!(app.message().is_some());
+
+  // The computer answers once it's on
+  type_line(&mut app, "engine on");
+  type_line(&mut app, "O-O");
+  app.wait_for_engine();
+  assert_eq!(app.game().moves().len(), 2);
+  assert!(app.message().unwrap().starts_with("Computer played"));
+  type_line(&mut app, "undo");
+  assert!(app.game().moves().is_empty());
+}
+
+#[test]
+fn playing_the_computer() {
+  use std::sync::atomic::AtomicBool;
+
+  use crate::app::App;
+  use crate::eval::Evaluator;
+  use crate::game::{Game, Outcome};
+  use crate::skill::{self, Difficulty};
+
+  assert_eq!("3".parse(), Ok(Difficulty::level(3)));
+  assert!("9".parse::<Difficulty>().is_err());
+  let custom: Difficulty = "level=2,depth=3,skill=40".parse().unwrap();
+  assert_eq!((custom.depth, custom.skill), (Some(3), 20));
+
+  // Even the weakest level takes a hanging queen most of the time
+  let board = Board::from_fen("4k3/8/8/3q4/8/8/3R4/4K3 w - - 0 1").unwrap();
+  let evaluator = Evaluator::default();
+  let stop = AtomicBool::new(false);
+  let captures = (1..=20)
+    .filter(|&seed| {
+      let result = skill::pick_move(&board, &[], &evaluator, &Difficulty::level(1), &stop, seed);
+      result.best_move.map(|mv| mv.to_string()).as_deref() == Some("d2d5")
+    })
+    .count();
+  assert!(captures >= 12, "{} captures", captures);
+
+  // Nor does it miss a mate in one, which the search scores like a stalemate
+  let board = Board::from_fen("6k1/5ppp/8/8/8/8/5PPP/3Q2K1 w - - 0 1").unwrap();
+  for level in [1, 5] {
+    for seed in 1..=5 {
+      let difficulty = Difficulty::level(level);
+      let result = skill::pick_move(&board, &[], &evaluator, &difficulty, &stop, seed);
+      assert_eq!(result.best_move.unwrap().to_string(), "d1d8");
+    }
+  }
+
+  // The computer answers on its own thread
+  let mut app = App::new(Game::default());
+  app.set_difficulty(Difficulty::level(2));
+  app.run_command("play black").unwrap();
+  assert!(app.view().is_flipped());
+  app.wait_for_engine();
+  assert_eq!(app.game().moves().len(), 1);
+  assert!(app.run_command("e2e4").is_err());
+
+  app.run_command("e5").unwrap();
+  assert!(app.thinking_time().is_some());
+  assert!(app.status().contains("computer is thinking"));
+  app.wait_for_engine();
+  assert_eq!(app.game().moves().len(), 3);
+  assert_eq!(app.thinking_time(), None);
+
+  // Taking back removes the computer's answer too, but not its first move
+  app.take_back().unwrap();
+  assert_eq!(app.game().moves().len(), 1);
+  app.take_back().unwrap();
+  app.wait_for_engine();
+  assert_eq!(app.game().moves().len(), 1);
+
+  // No draw before the computer has an opinion, resigning ends the game
+  assert!(app.offer_draw().is_err());
+  assert_eq!(app.resign(), Ok(String::from("Black resigns")));
+  assert_eq!(
+    app.game().outcome(),
+    Some(Outcome::Decision(
+      crate::game::GameResult::WhiteWins,
+      String::from("Black resigns")
+    ))
+  );
+
+  // Over the board draws are agreed
+  let mut app = App::default();
+  assert_eq!(app.offer_draw(), Ok(String::from("Draw agreed")));
+}
+
+#[test]
+fn multi_pv_analysis() {
+  use std::sync::atomic::AtomicBool;
+  use std::time::{Duration, Instant};
+
+  use tui::backend::TestBackend;
+  use tui::Terminal;
+
+  use crate::app::App;
+  use crate::eval::Evaluator;
+  use crate::game::Game;
+  use crate::search::{self, Info, Limits, Score};
+
+  // Three different first moves, the best line first
+  let board = Board::from_fen("4k3/8/8/3q4/8/8/3R4/4K3 w - - 0 1").unwrap();
+  let limits = Limits {
+    depth: Some(3),
+    multi_pv: 3,
+    ..Limits::default()
+  };
+  let mut lines: Vec<Info> = Vec::new();
+  let result = search::search(
+    &board,
+    &[],
+    &Evaluator::default(),
+    &limits,
+    &AtomicBool::new(false),
+    |info| {
+      if info.depth == 3 {
+        lines.push(info.clone())
+      }
+    }
+  );
+  assert_eq!(lines.len(), 3);
+  assert_eq!(lines[0].multipv, 1);
+  assert_eq!(lines[0].pv[0].to_string(), "d2d5");
+  assert_eq!(result.pv, lines[0].pv);
+  assert_ne!(lines[1].pv[0], lines[2].pv[0]);
+  assert!(lines[0].score.centipawns() >= lines[1].score.centipawns());
+  assert!(lines[1].score.centipawns() >= lines[2].score.centipawns());
+  assert!(Score::Mate(2).centipawns() > Score::Mate(3).centipawns());
+  assert!(Score::Mate(-2).centipawns() < Score::Cp(-2000).centipawns());
+
+  assert_eq!(
+    crate::app::san_line(
+      &Board::default(),
+      &[
+        Move::new(coord!(6, 4), coord!(4, 4)),
+        Move::new(coord!(1, 4), coord!(3, 4))
+      ]
+    ),
+    "1. e4 e5"
+  );
+
+  // The pane follows the position on screen
+  let mut game = Game::default();
+  game.play(game.board().parse_san("e4").unwrap()).unwrap();
+  let mut app = App::new(game);
+  app.set_analysis_lines(2);
+  app.run_command("analysis on").unwrap();
+
+  let wait_for_lines = |app: &mut App| {
+    let start = Instant::now();
+    while app.analysis_lines().len() < 2 && start.elapsed() < Duration::from_secs(10) {
+      std::thread::sleep(Duration::from_millis(10));
+      app.tick();
+    }
+    assert_eq!(app.analysis_lines().len(), 2);
+  };
+  wait_for_lines(&mut app);
+
+  let mut terminal = Terminal::new(TestBackend::new(80, 30)).unwrap();
+  terminal.draw(|f| app.draw(f)).unwrap();
+  let buffer = terminal.backend().buffer();
+  let text: String = buffer
+    .content
+    .iter()
+    .map(|cell| cell.symbol.as_str())
+    .collect();
+  assert!(text.contains("Analysis"));
+  assert!(text.contains("depth"));
+  assert!(text.contains("1..."));
+
+  app.go_to_ply(0);
+  app.tick();
+  assert!(app.analysis_lines().is_empty());
+  wait_for_lines(&mut app);
+  assert!(crate::app::san_line(app.view().board(), &app.analysis_lines()[0].pv).starts_with("1. "));
+
+  app.run_command("analysis off").unwrap();
+  assert!(!app.is_analysing());
+}
+
+#[test]
+fn chess_clocks() {
+  use std::time::{Duration, Instant};
+
+  use crate::app::App;
+  use crate::clock::{self, Bonus, Clock, TimeControl};
+  use crate::game::GameResult;
+
+  let secs = Duration::from_secs;
+
+  // Parsing and PGN tags
+  let blitz: TimeControl = "5+3".parse().unwrap();
+  assert_eq!(blitz.stages[0].time, secs(300));
+  assert_eq!(blitz.stages[0].bonus, Some(Bonus::Fischer(secs(3))));
+  assert_eq!(
+    "15d10".parse::<TimeControl>().unwrap().stages[0].bonus,
+    Some(Bonus::SimpleDelay(secs(10)))
+  );
+  let classical: TimeControl = "40/90+30,30+30".parse().unwrap();
+  assert_eq!(classical.stages.len(), 2);
+  assert_eq!(classical.to_pgn_tag(), "40/5400+30:1800+30");
+  assert_eq!(classical.to_string(), "40/90+30,30+30");
+  assert_eq!(
+    "30s".parse::<TimeControl>(),
+    Ok(TimeControl::sudden_death(secs(30)))
+  );
+  assert!("0/5".parse::<TimeControl>().is_err());
+  assert!("five".parse::<TimeControl>().is_err());
+
+  assert_eq!(clock::format_time(secs(5400)), "1:30:00");
+  assert_eq!(clock::format_time(secs(299)), "4:59");
+  assert_eq!(clock::format_time(Duration::from_millis(9800)), "9.8");
+  // Each branch starts where the one before ends
+  assert_eq!(clock::format_time(secs(3600)), "1:00:00");
+  assert_eq!(
+    clock::format_time(Duration::from_millis(3_599_900)),
+    "59:59"
+  );
+  assert_eq!(clock::format_time(secs(10)), "0:10");
+  assert_eq!(clock::format_time(Duration::from_millis(9_999)), "9.9");
+
+  assert_eq!(clock::clk_comment(secs(303)), "[%clk 0:05:03]");
+
+  // Fischer increment after the move
+  let start = Instant::now();
+  let mut clock = Clock::new(blitz);
+  clock.start(Color::White, start);
+  assert_eq!(clock.remaining(Color::White, start + secs(10)), secs(290));
+  assert_eq!(clock.press(start + secs(10)), Some(secs(293)));
+  assert_eq!(clock.running(), Some(Color::Black));
+  assert_eq!(clock.remaining(Color::White, start + secs(60)), secs(293));
+
+  // A simple delay runs before the clock does
+  let mut clock = Clock::new("1d5".parse().unwrap());
+  clock.start(Color::White, start);
+  assert_eq!(clock.remaining(Color::White, start + secs(4)), secs(60));
+  assert_eq!(clock.press(start + secs(8)), Some(secs(57)));
+
+  // Bronstein gives back the time used, up to the delay
+  let mut clock = Clock::new("1b5".parse().unwrap());
+  clock.start(Color::White, start);
+  assert_eq!(clock.press(start + secs(3)), Some(secs(60)));
+  assert_eq!(clock.press(start + secs(4)), Some(secs(60)));
+  assert_eq!(clock.press(start + secs(14)), Some(secs(55)));
+
+  // The second stage is added after the 40th move
+  let mut clock = Clock::new(classical);
+  let mut now = start;
+  clock.start(Color::White, now);
+  for _ in 0..39 {
+    now += secs(60);
+    clock.press(now);
+    clock.press(now);
+  }
+  now += secs(60);
+  assert_eq!(
+    clock.press(now),
+    Some(secs(5400 - 40 * 60 + 40 * 30 + 1800))
+  );
+
+  // Flag fall
+  let mut clock = Clock::new(TimeControl::sudden_death(secs(60)));
+  clock.start(Color::White, start);
+  assert_eq!(clock.flagged(start + secs(59)), None);
+  assert_eq!(clock.flagged(start + secs(61)), Some(Color::White));
+  assert_eq!(clock.press(start + secs(61)), Some(Duration::ZERO));
+
+  // Insufficient mating material turns a loss on time into a draw
+  let rook = Board::from_fen("4k3/8/8/8/8/8/8/4K2R w - - 0 1").unwrap();
+  assert!(rook.has_mating_material(Color::White));
+  assert!(!rook.has_mating_material(Color::Black));
+  assert_eq!(
+    clock::flag_result(&rook, Color::Black).0,
+    GameResult::WhiteWins
+  );
+  assert_eq!(
+    clock::flag_result(&rook, Color::White),
+    (
+      GameResult::Draw,
+      String::from("Draw, white ran out of time but black can't mate")
+    )
+  );
+  let knight = Board::from_fen("4k3/8/8/8/8/8/8/4KN2 w - - 0 1").unwrap();
+  assert!(!knight.has_mating_material(Color::White));
+  let knights = Board::from_fen("4k3/8/8/8/8/8/p7/4KN2 w - - 0 1").unwrap();
+  assert!(knights.has_mating_material(Color::White));
+  let bishops = Board::from_fen("4k3/8/8/8/8/8/8/2B1KB2 w - - 0 1").unwrap();
+  assert!(bishops.has_mating_material(Color::White));
+  let same_color = Board::from_fen("4k3/8/8/8/8/8/1B6/2B1K3 w - - 0 1").unwrap();
+  assert!(!same_color.has_mating_material(Color::White));
+
+  // Clock times end up in the saved game
+  let mut app = App::default();
+  assert!(app.run_command("clock nonsense").is_err());
+  app.run_command("clock 5+3").unwrap();
+  assert_eq!(
+    app.clock().and_then(|clock| clock.running()),
+    Some(Color::White)
+  );
+  app.run_command("e4").unwrap();
+  app.run_command("e5").unwrap();
+  assert_eq!(
+    app.clock().and_then(|clock| clock.running()),
+    Some(Color::White)
+  );
+
+  let path = std::env::temp_dir().join(format!("chyes-clock-{}.pgn", std::process::id()));
+  app
+    .run_command(&format!("save {}", path.display()))
+    .unwrap();
+  let pgn = std::fs::read_to_string(&path).unwrap();
+  std::fs::remove_file(&path).unwrap();
+  assert!(pgn.contains("[TimeControl \"300+3\"]"));
+  assert!(pgn.contains("1. e4 {[%clk 0:05:0"));
+
+  // Taking back runs the clock of the side to move again
+  app.run_command("undo").unwrap();
+  assert_eq!(
+    app.clock().and_then(|clock| clock.running()),
+    Some(Color::Black)
+  );
+
+  app.run_command("clock off").unwrap();
+  assert!(app.clock().is_none());
+
+  // A move after the flag fell is refused, even before the next tick
+  let short = TimeControl::sudden_death(Duration::from_millis(20));
+  let mut app = App::default();
+  app.set_clock(Some(short.clone()));
+  std::thread::sleep(Duration::from_millis(40));
+  assert_eq!(
+    app.run_command("e4"),
+    Err(String::from("Black wins on time"))
+  );
+  assert!(app.game().moves().is_empty());
+
+  // Resigning or agreeing a draw stops the clock, it doesn't flag later
+  for command in ["resign", "draw"] {
+    let mut app = App::default();
+    app.set_clock(Some(short.clone()));
+    app.run_command(command).unwrap();
+    assert_eq!(app.clock().and_then(|clock| clock.running()), None);
+    std::thread::sleep(Duration::from_millis(40));
+    app.tick();
+    assert!(!app.game().outcome().unwrap().to_string().contains("time"));
+  }
+}
+
+#[test]
+fn underpromotion() {
+  use std::sync::atomic::AtomicBool;
+
+  use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+  use tui::backend::TestBackend;
+  use tui::Terminal;
+
+  use crate::app::App;
+  use crate::eval::Evaluator;
+  use crate::game::Game;
+  use crate::search::{self, Limits};
+
+  // Promoting to a knight gives check
+  let board = Board::from_fen("8/3P1k2/8/8/8/8/8/K7 w - - 0 1").unwrap();
+  let promotions: Vec<String> = board
+    .legal_moves()
+    .into_iter()
+    .filter(|mv| mv.start == coord!(1, 3))
+    .map(|mv| mv.to_string())
+    .collect();
+  assert_eq!(promotions, vec!["d7d8q", "d7d8r", "d7d8b", "d7d8n"]);
+
+  let knight = board.parse_san("d8=N+").unwrap();
+  assert_eq!(
+    knight,
+    Move::promoting(coord!(1, 3), coord!(0, 3), Pieces::Knight)
+  );
+  assert_eq!(board.to_san(knight), "d8=N+");
+  assert_eq!(board.to_uci(knight), "d7d8n");
+  assert_eq!(board.parse_uci("d7d8n"), Ok(knight));
+  assert_eq!(board.to_san(board.parse_san("d8").unwrap()), "d8=Q");
+  assert_eq!(board.to_san(board.parse_uci("d7d8").unwrap()), "d8=Q");
+  assert!(board.parse_uci("d7d8k").is_err());
+
+  let mut game = Game::new(board.clone());
+  game.play(knight).unwrap();
+  assert_eq!(
+    game.board().get_piece(&coord!(0, 3)),
+    Some(Piece {
+      color: Color::White,
+      breed: Pieces::Knight
+    })
+  );
+  assert!(game.board().is_in_check(Color::Black));
+  assert_eq!(game.san_moves(), vec!["d8=N+"]);
+  assert_eq!(game.board_at(1).get_fen(), game.board().get_fen());
+
+  // A promotion without a piece named is a queen, as everywhere else
+  let plain = Move::new(coord!(1, 3), coord!(0, 3));
+  assert_eq!(
+    board.normalize(plain),
+    Move::promoting(coord!(1, 3), coord!(0, 3), Pieces::Queen)
+  );
+  assert_eq!(board.to_uci(plain), "d7d8q");
+  let mut game = Game::new(board.clone());
+  game.play(plain).unwrap();
+  assert_eq!(game.san_moves(), vec!["d8=Q"]);
+  assert_eq!(game.moves()[0].promotion, Some(Pieces::Queen));
+  assert_eq!(
+    board
+      .clone()
+      .apply_move(Move::promoting(coord!(1, 3), coord!(0, 3), Pieces::King)),
+    Err(String::from("A pawn can't become a king"))
+  );
+
+  // Queening stalemates, a rook wins
+  let board = Board::from_fen("8/k1P5/2K5/8/8/8/8/8 w - - 0 1").unwrap();
+  let mut queen = board.clone();
+  queen.apply_move(board.parse_san("c8=Q").unwrap()).unwrap();
+  assert!(queen.is_in_stalemate(Color::Black));
+  let mut rook = board.clone();
+  rook.apply_move(board.parse_san("c8=R").unwrap()).unwrap();
+  assert!(!rook.is_in_stalemate(Color::Black));
+
+  let limits = Limits {
+    depth: Some(3),
+    ..Limits::default()
+  };
+  let result = search::search(
+    &board,
+    &[],
+    &Evaluator::default(),
+    &limits,
+    &AtomicBool::new(false),
+    |_| {}
+  );
+  assert_eq!(
+    result.best_move.map(|mv| board.to_san(mv)).as_deref(),
+    Some("c8=R")
+  );
+
+  // The picker in the app
+  let mut terminal = Terminal::new(TestBackend::new(80, 30)).unwrap();
+  let mut app = App::new(Game::new(board));
+  let key = |app: &mut App, code| app.handle_key(KeyEvent::new(code, KeyModifiers::NONE));
+  app.activate(coord!(1, 2));
+  app.activate(coord!(0, 2));
+  assert_eq!(app.promotion(), Some(Move::new(coord!(1, 2), coord!(0, 2))));
+  assert!(app.game().moves().is_empty());
+
+  terminal.draw(|f| app.draw(f)).unwrap();
+  let text: String = terminal
+    .backend()
+    .buffer()
+    .content()
+    .iter()
+    .map(|cell| cell.symbol.as_str())
+    .collect();
+  assert!(text.contains("Promote to"));
+  assert!(text.contains(" ♛ q  ♜ r  ♝ b  ♞ n "));
+
+  // Escape cancels, arrows and enter pick a piece
+  key(&mut app, KeyCode::Esc);
+  assert_eq!(app.promotion(), None);
+  app.activate(coord!(1, 2));
+  app.activate(coord!(0, 2));
+  key(&mut app, KeyCode::Right);
+  key(&mut app, KeyCode::Enter);
+  assert_eq!(app.game().san_moves(), vec!["c8=R"]);
+
+  // So do the letters, and the command line takes SAN
+  app.run_command("undo").unwrap();
+  app.activate(coord!(1, 2));
+  app.activate(coord!(0, 2));
+  key(&mut app, KeyCode::Char('b'));
+  assert_eq!(app.game().san_moves(), vec!["c8=B"]);
+  app.run_command("undo").unwrap();
+  app.run_command("c8=N").unwrap();
+  assert_eq!(app.game().san_moves(), vec!["c8=N+"]);
+}
+
+#[test]
+fn board_editor() {
+  use crossterm::event::{
+    KeyCode, KeyEvent, KeyModifiers, MouseButton, MouseEvent, MouseEventKind
+  };
+  use tui::backend::TestBackend;
+  use tui::Terminal;
+
+  use crate::app::App;
+
+  // Validation
+  let mut board = Board::default();
+  assert_eq!(board.validate(), Ok(()));
+  assert_eq!(
+    board.remove_piece(coord!(7, 7)),
+    Some(Piece::new(Color::White, Pieces::Rook))
+  );
+  assert_eq!(
+    board.validate(),
+    Err(String::from(
+      "White can't castle king side without the king and rook at home"
+    ))
+  );
+  board.set_castling_right(Color::White, true, false);
+  assert_eq!(board.validate(), Ok(()));
+  assert!(board.get_fen().contains(" w Qkq "));
+
+  board.remove_piece(coord!(1, 7));
+  board.place_piece(Piece::new(Color::Black, Pieces::Pawn), coord!(7, 7));
+  assert_eq!(board.validate(), Err(String::from("Pawn on h1")));
+
+  board.clear();
+  assert_eq!(board.get_fen(), "8/8/8/8/8/8/8/8 w - - 0 1");
+  assert_eq!(board.validate(), Err(String::from("White has no king")));
+  board.place_piece(Piece::new(Color::White, Pieces::King), coord!(7, 4));
+  board.place_piece(Piece::new(Color::Black, Pieces::King), coord!(0, 4));
+  board.place_piece(Piece::new(Color::White, Pieces::Rook), coord!(4, 4));
+  assert_eq!(
+    board.validate(),
+    Err(String::from("Black is in check with white to move"))
+  );
+  board.set_turn(Color::Black);
+  assert_eq!(board.validate(), Ok(()));
+
+  let after_e4 =
+    Board::from_fen("rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 1").unwrap();
+  assert_eq!(after_e4.en_passant(), Some(coord!(5, 4)));
+  assert_eq!(after_e4.validate(), Ok(()));
+  let mut wrong = after_e4.clone();
+  wrong.set_en_passant(Some(coord!(5, 3)));
+  assert_eq!(
+    wrong.validate(),
+    Err(String::from("No pawn could have just passed d3"))
+  );
+
+  // The editor in the app
+  let mut terminal = Terminal::new(TestBackend::new(80, 30)).unwrap();
+  let mut app = App::default();
+  let key = |app: &mut App, code| app.handle_key(KeyEvent::new(code, KeyModifiers::NONE));
+  app.run_command("e4").unwrap();
+  app.run_command("edit").unwrap();
+  assert!(app.is_editing());
+  assert_eq!(app.view().board().get_fen(), app.game().board().get_fen());
+
+  key(&mut app, KeyCode::Char('c'));
+  assert!(app.status().contains("White has no king"));
+  terminal.draw(|f| app.draw(f)).unwrap();
+  key(&mut app, KeyCode::Enter);
+  assert_eq!(app.message(), Some("White has no king"));
+  assert!(app.is_editing());
+
+  // Letters put pieces down at the cursor, e2 to start with
+  key(&mut app, KeyCode::Char('K'));
+  key(&mut app, KeyCode::Up);
+  key(&mut app, KeyCode::Char('P'));
+  for _ in 0..5 {
+    key(&mut app, KeyCode::Up);
+  }
+  key(&mut app, KeyCode::Char('P'));
+  key(&mut app, KeyCode::Right);
+  key(&mut app, KeyCode::Char('k'));
+  assert_eq!(app.status(), "Setting up: Pawn on e8");
+  terminal.draw(|f| app.draw(f)).unwrap();
+
+  // Painting the same piece again takes it off
+  key(&mut app, KeyCode::Left);
+  key(&mut app, KeyCode::Char(' '));
+  assert_eq!(app.status(), "Setting up: Black has 2 kings");
+  key(&mut app, KeyCode::Char(' '));
+  assert_eq!(
+    app.status(),
+    "Setting up, black to move, the position is legal"
+  );
+  key(&mut app, KeyCode::Char('t'));
+  for _ in 0..5 {
+    key(&mut app, KeyCode::Down);
+  }
+  key(&mut app, KeyCode::Char('x'));
+  assert_eq!(app.view().board().get_piece(&coord!(5, 4)), None);
+  key(&mut app, KeyCode::Char('P'));
+  terminal.draw(|f| app.draw(f)).unwrap();
+
+  // Right clicks remove, left clicks paint with the last piece
+  let area = app.board_area();
+  let square = crate::widget::BoardWidget::new(app.view().board()).square_area(area, coord!(5, 4));
+  let click = |app: &mut App, button| {
+    app.handle_mouse(MouseEvent {
+      kind:      MouseEventKind::Down(button),
+      column:    square.x,
+      row:       square.y,
+      modifiers: KeyModifiers::NONE
+    })
+  };
+  click(&mut app, MouseButton::Right);
+  assert_eq!(app.view().board().get_piece(&coord!(5, 4)), None);
+  click(&mut app, MouseButton::Left);
+  assert_eq!(
+    app.view().board().get_fen(),
+    "5k2/8/8/8/8/4P3/4K3/8 w - - 0 1"
+  );
+
+  key(&mut app, KeyCode::Char('y'));
+  assert_eq!(
+    app.take_clipboard().as_deref(),
+    Some("5k2/8/8/8/8/4P3/4K3/8 w - - 0 1")
+  );
+
+  // Enter plays the position, esc leaves the game as it was
+  key(&mut app, KeyCode::Enter);
+  assert!(!app.is_editing());
+  assert_eq!(
+    app.game().start().get_fen(),
+    "5k2/8/8/8/8/4P3/4K3/8 w - - 0 1"
+  );
+  assert!(app.game().moves().is_empty());
+
+  app.run_command("edit").unwrap();
+  key(&mut app, KeyCode::Char('s'));
+  key(&mut app, KeyCode::Esc);
+  assert!(!app.is_editing());
+  assert_eq!(
+    app.view().board().get_fen(),
+    "5k2/8/8/8/8/4P3/4K3/8 w - - 0 1"
+  );
+}
+
+#[test]
+fn config_file_settings() {
+  use crate::config::{Config, Mode};
+
+  let config = Config::parse(
+    "# Defaults\nmode = \"engine\"\nside = black\ntime = \"5+3\"\nlevel = 4\nlines = \
+     2\norientation = auto\n"
+  )
+  .unwrap();
+  assert_eq!(
+    config.mode.as_deref().map(str::parse),
+    Some(Ok(Mode::Engine))
+  );
+  assert_eq!(config.side.as_deref(), Some("black"));
+  assert_eq!(config.time.as_deref(), Some("5+3"));
+  assert_eq!(config.level.as_deref(), Some("4"));
+  assert_eq!(config.lines.as_deref(), Some("2"));
+  assert_eq!(config.orientation.as_deref(), Some("auto"));
+
+  assert_eq!("hvh".parse(), Ok(Mode::HumanVsHuman));
+  assert_eq!("Replay".parse(), Ok(Mode::Replay));
+  assert_eq!(Mode::Analysis.to_string(), "analysis");
+  assert_eq!("w".parse(), Ok(Color::White));
+  assert!("green".parse::<Color>().is_err());
+
+  // Mistakes are reported with their line
+  assert_eq!(
+    Config::parse("theme = wood\nmode = fast"),
+    Err(String::from(
+      "Line 2: Unknown mode `fast`, expected hvh, engine, analysis or replay"
+    ))
+  );
+  assert_eq!(
+    Config::parse("lines = 0"),
+    Err(String::from("Line 1: Analysis lines go from 1 to 10"))
+  );
+  assert_eq!(
+    Config::parse("\n\ntime = soon"),
+    Err(String::from("Line 3: Invalid time control `soon`"))
+  );
+  assert!(Config::parse("level = 12").is_err());
+  assert!(Config::parse("side = red").is_err());
+}
+
+#[test]
+fn quit_signals() {
+  use std::sync::atomic::Ordering;
+
+  use signal_hook::consts::SIGTERM;
+  use signal_hook::low_level::raise;
+
+  // The signal only raises the flag instead of killing the process
+  let quit = crate::terminal::quit_signals().unwrap();
+  assert!(!quit.load(Ordering::Relaxed));
+  raise(SIGTERM).unwrap();
+  assert!(quit.load(Ordering::Relaxed));
+  assert!(!crate::terminal::panicked());
+}
+
+#[test]
+fn pgn_replay() {
+  use std::time::Duration;
+
+  use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+  use tui::backend::TestBackend;
+  use tui::Terminal;
+
+  use crate::app::App;
+  use crate::pgn;
+
+  // Variations, nested ones and their comments
+  let text = "[White \"Anand\"]\n[Black \"Carlsen\"]\n[Event \"Match\"]\n\n1. e4 e5 (1... c5 \
+              {Sicilian} 2. Nf3 (2. Nc3 Nc6) d6) (1... Ke7 Nf3) 2. Nf3 {Develops} Nc6 *\n\n[White \
+              \"Kasparov\"]\n[Black \"Karpov\"]\n[Event \"Match\"]\n\n1. d4 d5 *\n";
+  let games = pgn::parse(text).unwrap();
+  assert_eq!(games[0].game.san_moves(), vec!["e4", "e5", "Nf3", "Nc6"]);
+  assert_eq!(games[0].header(), "Anand - Carlsen, Match, ?, ?");
+
+  let variations = &games[0].variations;
+  assert_eq!(variations.len(), 2);
+  assert_eq!(variations[0].ply, 1);
+  assert_eq!(variations[0].moves.len(), 3);
+  assert_eq!(variations[0].variations[0].ply, 1);
+  assert_eq!(
+    variations[0].to_pgn(&games[0].game.board_at(1)),
+    "1... c5 {Sicilian} 2. Nf3 (2. Nc3 Nc6) 2... d6"
+  );
+  // Lines stop at the first move that isn't legal
+  assert!(variations[1].moves.is_empty());
+
+  // Several games are picked from a list searched by their headers
+  let mut terminal = Terminal::new(TestBackend::new(80, 30)).unwrap();
+  let mut app = App::default();
+  let key = |app: &mut App, code| app.handle_key(KeyEvent::new(code, KeyModifiers::NONE));
+  app.open_pgn(games).unwrap();
+  assert!(app.is_picking_game());
+  assert_eq!(app.picker_games().len(), 2);
+  terminal.draw(|f| app.draw(f)).unwrap();
+  for c in "karp".chars() {
+    key(&mut app, KeyCode::Char(c));
+  }
+  assert_eq!(app.picker_games().len(), 1);
+  key(&mut app, KeyCode::Backspace);
+  key(&mut app, KeyCode::Char('x'));
+  assert!(app.picker_games().is_empty());
+  for _ in 0..5 {
+    key(&mut app, KeyCode::Backspace);
+  }
+  key(&mut app, KeyCode::Enter);
+  assert!(!app.is_picking_game());
+  assert!(app.is_replaying());
+  assert_eq!(app.ply(), 0);
+
+  // Stepping, jumping and the moves being off limits
+  key(&mut app, KeyCode::Right);
+  key(&mut app, KeyCode::Right);
+  assert_eq!(app.ply(), 2);
+  terminal.draw(|f| app.draw(f)).unwrap();
+  key(&mut app, KeyCode::Left);
+  assert_eq!(app.ply(), 1);
+  key(&mut app, KeyCode::End);
+  assert_eq!(app.ply(), 4);
+  key(&mut app, KeyCode::Char('g'));
+  key(&mut app, KeyCode::Char('3'));
+  key(&mut app, KeyCode::Enter);
+  assert_eq!(app.ply(), 3);
+  assert!(app.run_command("Nc6").is_err());
+  key(&mut app, KeyCode::Home);
+  assert_eq!(app.ply(), 0);
+
+  // Autoplay runs to the end of the game
+  app.set_replay_speed(Duration::ZERO);
+  key(&mut app, KeyCode::Char('p'));
+  assert!(app.is_autoplaying());
+  for _ in 0..10 {
+    app.tick();
+  }
+  assert_eq!(app.ply(), 4);
+  assert!(!app.is_autoplaying());
+
+  // Leaving the replay plays on from the position shown
+  key(&mut app, KeyCode::Char('g'));
+  key(&mut app, KeyCode::Char('2'));
+  key(&mut app, KeyCode::Enter);
+  key(&mut app, KeyCode::Esc);
+  assert!(!app.is_replaying());
+  assert_eq!(app.game().san_moves(), vec!["e4", "e5"]);
+  app.run_command("Nc3").unwrap();
+  assert_eq!(app.game().san_moves(), vec!["e4", "e5", "Nc3"]);
+}
+
+#[test]
+fn text_rendering() {
+  use crate::render::{TextBoard, TextStyle};
+  use crate::theme::GlyphSet;
+  use crate::view::{BoardView, Orientation};
+
+  let mut board = Board::from_fen("4k3/8/8/8/8/8/4P3/4K3 w - - 0 1").unwrap();
+  let mv = board.parse_san("e4").unwrap();
+  board.make_move(mv).unwrap();
+
+  let ascii = TextBoard::new(&board).last_move(Some(mv)).render();
+  let lines: Vec<&str> = ascii.lines().collect();
+  assert_eq!(lines.len(), 18);
+  assert_eq!(lines[0], "  +---+---+---+---+---+---+---+---+");
+  assert_eq!(lines[1], "8 |   |   |   |   | k |   |   |   |");
+  assert_eq!(lines[9], "4 |   |   |   |   |(P)|   |   |   |");
+  assert_eq!(lines[13], "2 |   |   |   |   |( )|   |   |   |");
+  assert_eq!(lines[17], "    a   b   c   d   e   f   g   h");
+
+  // Black at the bottom, highlights win over the last move
+  let highlights = [coord!(4, 4)];
+  let unicode = BoardView::new(&board)
+    .orientation(Orientation::Black)
+    .text()
+    .style(TextStyle::Unicode)
+    .glyphs(GlyphSet::OUTLINE)
+    .last_move(Some(mv))
+    .highlights(&highlights)
+    .render();
+  let lines: Vec<&str> = unicode.lines().collect();
+  assert_eq!(lines[0], "  ┌───┬───┬───┬───┬───┬───┬───┬───┐");
+  assert_eq!(lines[1], "1 │   │   │   │ ♔ │   │   │   │   │");
+  assert_eq!(lines[7], "4 │   │   │   │[♙]│   │   │   │   │");
+  assert_eq!(lines[16], "  └───┴───┴───┴───┴───┴───┴───┴───┘");
+  assert_eq!(lines[17], "    h   g   f   e   d   c   b   a");
+
+  let ansi = TextBoard::new(&board)
+    .style(TextStyle::Ansi)
+    .glyphs(GlyphSet::ASCII)
+    .last_move(Some(mv))
+    .render();
+  let lines: Vec<&str> = ansi.lines().collect();
+  assert!(lines[4].starts_with("4 \x1b[48;2;161;189;203;30m   \x1b[48;2;254;255;255;30m   "));
+  assert!(lines[4].contains("\x1b[48;2;205;210;140;30m P "));
+  assert!(lines[4].ends_with("\x1b[0m"));
+  assert_eq!(lines[8], "   a  b  c  d  e  f  g  h");
+
+  let compact = TextBoard::new(&board)
+    .style(TextStyle::Compact)
+    .glyphs(GlyphSet::ASCII)
+    .last_move(Some(mv));
+  assert_eq!(
+    compact.to_string(),
+    "....k...\n........\n........\n........\n....P...\n........\n....*...\n....K...\n"
+  );
+  assert_eq!("Compact".parse::<TextStyle>(), Ok(TextStyle::Compact));
+  assert!("fancy".parse::<TextStyle>().is_err());
+}
+
+// Text form of a buffer for snapshots: the symbols row by row, then a
+// letter per cell naming its style in the legend below
+fn buffer_snapshot(buffer: &tui::buffer::Buffer) -> String {
+  const LETTERS: &str = "abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ0123456789";
+
+  let area = buffer.area;
+  let mut styles: Vec<String> = Vec::new();
+  let mut symbols = String::new();
+  let mut map = String::new();
+
+  for y in area.top()..area.bottom() {
+    symbols.push('|');
+    map.push('|');
+    for x in area.left()..area.right() {
+      let cell = buffer.get(x, y);
+      symbols.push_str(&cell.symbol);
+
+      let style = format!(
+        "fg={:?} bg={:?} modifier={:?}",
+        cell.fg, cell.bg, cell.modifier
+      );
+      let index = match styles.iter().position(|known| *known == style) {
+        Some(index) => index,
+        None => {
+          styles.push(style);
+          styles.len() - 1
+        }
+      };
+      map.push(
+        LETTERS
+          .chars()
+          .nth(index)
+          .expect("Too many styles for a snapshot")
+      );
+    }
+    symbols.push_str("|\n");
+    map.push_str("|\n");
+  }
+
+  let legend: String = styles
+    .iter()
+    .zip(LETTERS.chars())
+    .map(|(style, letter)| format!("{} {}\n", letter, style))
+    .collect();
+
+  format!("{}\n{}\n{}", symbols, map, legend)
+}
+
+// Compares the buffer with `src/snapshots/<name>.snap`. Running the tests
+// with UPDATE_SNAPSHOTS=1 writes the snapshots instead, review them with
+// `git diff` before committing
+fn assert_snapshot(
+  name: &str,
+  buffer: &tui::buffer::Buffer
+) {
+  use std::fs;
+  use std::path::Path;
+
+  let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+    .join("src/snapshots")
+    .join(format!("{}.snap", name));
+  let actual = buffer_snapshot(buffer);
+
+  if std::env::var_os("UPDATE_SNAPSHOTS").is_some() {
+    fs::create_dir_all(path.parent().unwrap()).unwrap();
+    fs::write(&path, actual).unwrap();
+    return
+  }
+
+  let expected = fs::read_to_string(&path).unwrap_or_else(|err| {
+    panic!(
+      "Couldn't read {}: {}, run the tests with UPDATE_SNAPSHOTS=1 to create it",
+      path.display(),
+      err
+    )
+  });
+  assert!(
+    actual == expected,
+    "{} doesn't match the snapshot, run the tests with UPDATE_SNAPSHOTS=1 if the change is \
+     intended\n\nExpected:\n{}\nActual:\n{}",
+    name,
+    expected,
+    actual
+  );
+}
+
+#[test]
+fn board_widget_snapshots() {
+  use tui::buffer::Buffer;
+  use tui::layout::Rect;
+  use tui::widgets::Widget;
+
+  use crate::theme::{GlyphSet, Theme};
+  use crate::widget::BoardWidget;
+
+  let mut board = Board::default();
+  let mv = board.parse_san("e4").unwrap();
+  board.make_move(mv).unwrap();
+  let targets = board.generate_moves(coord!(0, 6)).unwrap();
+
+  let render = |widget: BoardWidget, width, height| {
+    let area = Rect::new(0, 0, width, height);
+    let mut buffer = Buffer::empty(area);
+    widget.render(area, &mut buffer);
+    buffer
+  };
+
+  // Labels, the last move, a selection with its targets and the cursor
+  let widget = BoardWidget::new(&board)
+    .last_move(Some(mv))
+    .selected(Some(coord!(0, 6)))
+    .targets(&targets)
+    .cursor(Some(coord!(1, 3)));
+  assert_snapshot("board_widget", &render(widget, 44, 26));
+
+  // From black's side in ASCII art, in another theme
+  let widget = BoardWidget::new(&board)
+    .flipped(true)
+    .ascii_art(true)
+    .theme(Theme::named("wood").unwrap())
+    .glyphs(GlyphSet::ASCII)
+    .last_move(Some(mv));
+  assert_snapshot("board_widget_ascii_art", &render(widget, 58, 25));
+
+  // The smallest squares
+  assert_snapshot(
+    "board_widget_small",
+    &render(BoardWidget::new(&board), 8, 8)
+  );
+}
+
+#[test]
+fn app_layout_snapshots() {
+  use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+  use tui::backend::TestBackend;
+  use tui::Terminal;
+
+  use crate::app::App;
+
+  let mut terminal = Terminal::new(TestBackend::new(80, 30)).unwrap();
+  let mut app = App::default();
+  let key = |app: &mut App, code| app.handle_key(KeyEvent::new(code, KeyModifiers::NONE));
+
+  app.run_command("e4").unwrap();
+  app.run_command("c5").unwrap();
+  app.run_command("Nf3").unwrap();
+  // Black's e pawn selected, with its moves
+  for _ in 0..5 {
+    key(&mut app, KeyCode::Char('k'));
+  }
+  key(&mut app, KeyCode::Enter);
+  assert_eq!(app.selected(), Some(coord!(1, 4)));
+  terminal.draw(|f| app.draw(f)).unwrap();
+  assert_snapshot("app_layout", terminal.backend().buffer());
+
+  // Browsing with the command line open
+  key(&mut app, KeyCode::Esc);
+  key(&mut app, KeyCode::Char('['));
+  key(&mut app, KeyCode::Char(':'));
+  for c in "fen".chars() {
+    key(&mut app, KeyCode::Char(c));
+  }
+  // The FEN line of the snapshot, checked on its own so a wrong one can't
+  // be recorded
+  assert_eq!(
+    app.view().board().get_fen(),
+    "rnbqkbnr/pp1ppppp/8/2p5/4P3/8/PPPP1PPP/RNBQKBNR w KQkq c6 0 2"
+  );
+  terminal.draw(|f| app.draw(f)).unwrap();
+  assert_snapshot("app_layout_browsing", terminal.backend().buffer());
+}
+
+#[test]
+fn accessible_descriptions() {
+  use crate::app::App;
+  use crate::config::Config;
+
+  let board =
+    Board::from_fen("r3k2r/ppp2ppp/2n5/1B1pP3/8/5N2/PPP2PPP/R3K2R w KQkq d6 0 8").unwrap();
+  let describe = |san: &str| board.describe_move(board.parse_san(san).unwrap());
+  assert_eq!(describe("Ng5"), "White knight from f3 to g5");
+  assert_eq!(
+    describe("Bxc6+"),
+    "White bishop from b5 takes knight on c6, check"
+  );
+  assert_eq!(
+    describe("exd6"),
+    "White pawn from e5 takes pawn en passant on d6"
+  );
+  assert_eq!(describe("O-O-O"), "White castles queen side");
+
+  let board = Board::from_fen("6k1/1P3ppp/8/8/8/8/8/R5K1 w - - 0 1").unwrap();
+  assert_eq!(
+    board.describe_move(board.parse_san("Ra8#").unwrap()),
+    "White rook from a1 to a8, checkmate"
+  );
+  assert_eq!(
+    board.describe_move(board.parse_san("b8=N").unwrap()),
+    "White pawn from b7 to b8, promotes to knight"
+  );
+
+  assert_eq!(board.describe_square(coord!(1, 1)), "b7, white pawn");
+  assert_eq!(board.describe_square(coord!(4, 4)), "e4, empty");
+  assert_eq!(
+    board.describe_rank(7),
+    Ok(String::from(
+      "Rank 7: b7 white pawn, f7 black pawn, g7 black pawn and h7 black pawn"
+    ))
+  );
+  assert_eq!(board.describe_rank(4), Ok(String::from("Rank 4 is empty")));
+  assert!(board.describe_rank(9).is_err());
+  assert_eq!(
+    board.describe_file('a'),
+    Ok(String::from("File a: a1 white rook"))
+  );
+  assert_eq!(
+    board.describe_pieces(Color::Black),
+    "Black: king on g8, pawns on f7, g7 and h7"
+  );
+  assert_eq!(
+    board.describe_position(),
+    "White to move. White: king on g1, rook on a1, pawn on b7. Black: king on g8, pawns on f7, g7 \
+     and h7"
+  );
+
+  let mut board = Board::default();
+  for san in ["e4", "a6", "e5", "d5"] {
+    board.make_move(board.parse_san(san).unwrap()).unwrap();
+  }
+  assert!(board.describe_position().ends_with("En passant square d6"));
+
+  // The app announces the moves of both sides
+  let mut app = App::default();
+  app.run_command("accessible on").unwrap();
+  assert_eq!(
+    app.run_command("Nf3"),
+    Ok(Some(String::from("White knight from g1 to f3")))
+  );
+  app.run_command("e5").unwrap();
+  assert_eq!(
+    app.take_announcements(),
+    vec!["White knight from g1 to f3", "Black pawn from e7 to e5"]
+  );
+  assert!(app.take_announcements().is_empty());
+  assert_eq!(
+    app.run_command("square e5"),
+    Ok(Some(String::from("e5, black pawn")))
+  );
+  assert_eq!(
+    app.run_command("file g"),
+    Ok(Some(String::from(
+      "File g: g2 white pawn, g7 black pawn and g8 black knight"
+    )))
+  );
+  assert!(app.run_command("rank 0").is_err());
+  assert!(app
+    .run_command("describe")
+    .unwrap()
+    .unwrap()
+    .starts_with("White to move. "));
+
+  assert_eq!(
+    Config::parse("accessible = true")
+      .unwrap()
+      .accessible
+      .as_deref(),
+    Some("true")
+  );
+  assert!(Config::parse("accessible = yes").is_err());
+}
+
+#[test]
+fn coaching() {
+  use std::sync::atomic::AtomicBool;
+  use std::time::{Duration, Instant};
+
+  use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+  use tui::buffer::Buffer;
+  use tui::layout::Rect;
+  use tui::widgets::Widget;
+
+  use crate::app::App;
+  use crate::coach;
+  use crate::eval::Evaluator;
+  use crate::search::Limits;
+  use crate::theme::Theme;
+  use crate::widget::BoardWidget;
+
+  let evaluator = Evaluator::default();
+  let limits = Limits {
+    depth: Some(3),
+    ..Limits::default()
+  };
+  let stop = AtomicBool::new(false);
+
+  // Knights attacking each other, the black one defended by a pawn
+  let board = Board::from_fen("4k3/8/4p3/3n4/8/2N5/8/4K3 w - - 0 1").unwrap();
+  assert_eq!(board.hanging_pieces(Color::White), vec![coord!(5, 2)]);
+  assert!(board.hanging_pieces(Color::Black).is_empty());
+
+  // Black to move, but white would mate on f7
+  let scholar =
+    Board::from_fen("r1bqkbnr/pppp1ppp/2n5/4p2Q/2B1P3/8/PPPP1PPP/RNB1K1NR b KQkq - 3 3").unwrap();
+  let mate = Move::new(coord!(3, 7), coord!(1, 5));
+  assert_eq!(
+    coach::threat(&scholar, &evaluator, &limits, &stop),
+    Some(mate)
+  );
+  let checked = Board::from_fen("4k3/8/8/8/8/8/8/4K2r w - - 0 1").unwrap();
+  assert_eq!(coach::threat(&checked, &evaluator, &limits, &stop), None);
+
+  let board = Board::from_fen("6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1").unwrap();
+  assert_eq!(
+    coach::hint(&board, &[], &evaluator, &limits, &stop),
+    board.parse_san("Ra8#").ok()
+  );
+
+  // The queen takes the rook attacking it, or stays on the file and is lost
+  let board = Board::from_fen("4k3/8/8/3r4/8/8/8/3QK3 w - - 0 1").unwrap();
+  let take = board.parse_san("Qxd5").unwrap();
+  let blunder = board.parse_san("Qd2").unwrap();
+  assert_eq!(
+    coach::move_loss(&board, &[], &evaluator, &limits, &stop, take),
+    Ok((0, Some(take)))
+  );
+  let (loss, best) = coach::move_loss(&board, &[], &evaluator, &limits, &stop, blunder).unwrap();
+  assert!(loss > 500, "{}", loss);
+  assert_eq!(best, Some(take));
+
+  // The flag ends searches without limits, as when the app drops a lookup
+  let stop = AtomicBool::new(true);
+  let started = Instant::now();
+  coach::hint(&board, &[], &evaluator, &Limits::default(), &stop);
+  coach::threat(&scholar, &evaluator, &Limits::default(), &stop);
+  coach::move_loss(&board, &[], &evaluator, &Limits::default(), &stop, blunder).unwrap();
+  assert!(started.elapsed() < Duration::from_secs(1));
+
+  // Overlays are worked out in the background, between frames
+  let wait = |app: &mut App, done: &dyn Fn(&App) -> bool| {
+    let started = Instant::now();
+    while !done(app) && started.elapsed() < Duration::from_secs(10) {
+      app.tick();
+      std::thread::sleep(Duration::from_millis(10));
+    }
+  };
+
+  let mut app = App::default();
+  app
+    .run_command(&format!("fen {}", scholar.get_fen()))
+    .unwrap();
+  app.set_hanging(true);
+  app.set_threats(true);
+  wait(&mut app, &|app| app.threat().is_some());
+  assert_eq!(app.threat(), Some(mate));
+  // The king can't take back on f7 while the queen covers it
+  assert_eq!(app.hanging(), &[coord!(1, 5)]);
+  assert!(
+    app.status().ends_with("white threatens Qxf7#"),
+    "{}",
+    app.status()
+  );
+
+  assert!(app.ask_hint().is_ok());
+  wait(&mut app, &|app| app.hint().is_some());
+  assert!(app
+    .message()
+    .is_some_and(|message| message.starts_with("Hint: ")));
+  assert!(app
+    .hint()
+    .is_some_and(|hint| scholar.legal_moves().contains(&hint)));
+
+  app.set_threats(false);
+  app.tick();
+  assert_eq!(app.threat(), None);
+  assert!(!app.status().contains("threatens"));
+
+  // A move dropping the queen has to be confirmed, by playing it again or
+  // with enter. Moves are checked in the background meanwhile
+  let key = |code| KeyEvent::new(code, KeyModifiers::NONE);
+  app.set_hanging(false);
+  app.set_warnings(Some(150));
+  app
+    .run_command("fen 4k3/8/8/3r4/8/8/8/3QK3 w - - 0 1")
+    .unwrap();
+  assert_eq!(
+    app.run_command("Qd2"),
+    Ok(Some(String::from("Checking Qd2")))
+  );
+  assert!(app.game().moves().is_empty());
+  wait(&mut app, &|app| app.pending_move().is_some());
+  let warning = app.message().unwrap_or_default();
+  assert!(
+    warning.starts_with("Qd2 loses about") && warning.contains("best is Qxd5"),
+    "{}",
+    warning
+  );
+  assert_eq!(app.pending_move(), Some(blunder));
+  assert!(app.game().moves().is_empty());
+
+  app.handle_key(key(KeyCode::Esc));
+  assert_eq!(app.pending_move(), None);
+  assert_eq!(app.message(), Some("Move cancelled"));
+
+  assert!(app.run_command("Qxd5").is_ok());
+  wait(&mut app, &|app| !app.game().moves().is_empty());
+  assert_eq!(app.game().moves(), &[take]);
+  assert_eq!(app.message(), None);
+  app.run_command("undo").unwrap();
+
+  app.run_command("Qd2").unwrap();
+  wait(&mut app, &|app| app.pending_move().is_some());
+  assert!(app.run_command("Qd2").is_ok());
+  assert_eq!(app.game().moves(), &[blunder]);
+  app.run_command("undo").unwrap();
+
+  app.run_command("Qd2").unwrap();
+  wait(&mut app, &|app| app.pending_move().is_some());
+  app.handle_key(key(KeyCode::Enter));
+  assert_eq!(app.game().moves(), &[blunder]);
+  app.run_command("undo").unwrap();
+
+  // Enter doesn't wait for the check, esc drops the move being checked
+  app.run_command("Qd2").unwrap();
+  app.handle_key(key(KeyCode::Enter));
+  assert_eq!(app.game().moves(), &[blunder]);
+  app.run_command("undo").unwrap();
+  app.run_command("Qd2").unwrap();
+  app.handle_key(key(KeyCode::Esc));
+  std::thread::sleep(Duration::from_millis(600));
+  app.tick();
+  assert!(app.game().moves().is_empty());
+  assert_eq!(app.pending_move(), None);
+
+  app.set_warnings(None);
+  assert!(app.run_command("Qd2").is_ok());
+  assert_eq!(app.game().moves(), &[blunder]);
+
+  // Hints win over threats, threats over hanging pieces
+  let board = Board::default();
+  let theme = Theme::default();
+  let area = Rect::new(0, 0, 26, 9);
+  let mut buf = Buffer::empty(area);
+  let hanging = [coord!(0, 1), coord!(7, 6)];
+  let widget = || {
+    BoardWidget::new(&board)
+      .theme(theme)
+      .hanging(&hanging)
+      .threat(Some(Move::new(coord!(0, 1), coord!(2, 2))))
+      .hint(Some(Move::new(coord!(7, 6), coord!(5, 5))))
+  };
+  widget().render(area, &mut buf);
+  let background = |coord| {
+    let square = widget().square_area(area, coord);
+    buf.get(square.x, square.y).bg
+  };
+  assert_eq!(background(coord!(0, 1)), theme.threat);
+  assert_eq!(background(coord!(2, 2)), theme.threat);
+  assert_eq!(background(coord!(7, 6)), theme.hint);
+  assert_eq!(background(coord!(5, 5)), theme.hint);
+  assert_eq!(background(coord!(4, 4)), theme.light);
+}
+
+#[test]
+fn game_report() {
+  use std::sync::atomic::AtomicBool;
+  use std::time::{Duration, Instant};
+
+  use crate::app::App;
+  use crate::eval::Evaluator;
+  use crate::report::{self, Judgement};
+  use crate::search::Limits;
+
+  assert_eq!(Judgement::from_loss(49), None);
+  assert_eq!(Judgement::from_loss(50), Some(Judgement::Inaccuracy));
+  assert_eq!(Judgement::from_loss(150), Some(Judgement::Mistake));
+  assert_eq!(Judgement::from_loss(300), Some(Judgement::Blunder));
+  assert_eq!(Judgement::Blunder.symbol(), "??");
+  assert_eq!(Judgement::Mistake.nag(), 2);
+
+  let pgn = "[White \"A\"]\n[Black \"B\"]\n\n1. e4 e5 2. Bc4 Nc6 3. Qh5 Nf6 {oops} 4. Qxf7# 1-0\n";
+  let game = pgn::parse(pgn).unwrap().remove(0).game;
+  let limits = Limits {
+    depth: Some(3),
+    ..Limits::default()
+  };
+  let mut calls = Vec::new();
+  let report = report::analyse(
+    &game,
+    &Evaluator::default(),
+    &limits,
+    &AtomicBool::new(false),
+    |searched, total| calls.push((searched, total))
+  );
+  assert_eq!(calls.len(), 8);
+  assert_eq!(calls.last(), Some(&(8, 8)));
+  assert_eq!(report.moves.len(), 7);
+
+  // Nf6 lets white mate, Qxf7# ends the game
+  let blunder = &report.moves[5];
+  assert_eq!(blunder.san, "Nf6");
+  assert_eq!(blunder.color, Color::Black);
+  assert_eq!(blunder.judgement, Some(Judgement::Blunder));
+  assert!(blunder.best.is_some() && blunder.best_san.is_some());
+  assert!(blunder.accuracy < 50.0, "{}", blunder.accuracy);
+  let mate = &report.moves[6];
+  assert_eq!(mate.loss, 0);
+  assert_eq!(mate.eval, None);
+  assert!(mate.accuracy > 99.9);
+  assert_eq!(report.curve().len(), 8);
+  assert_eq!(report.curve()[7], 1000);
+
+  assert_eq!(report.count(Color::Black, Judgement::Blunder), 1);
+  assert!(report.accuracy(Color::White).unwrap() > report.accuracy(Color::Black).unwrap());
+  assert!(report
+    .summary(Color::Black)
+    .ends_with("0 mistakes, 1 blunder"));
+
+  let annotated = report.to_pgn(&game, &[("White", String::from("A"))]);
+  assert!(annotated.contains("[White \"A\"]"));
+  assert!(
+    annotated.contains("Nf6 $4") && annotated.contains("{oops #1 Blunder, "),
+    "{}",
+    annotated
+  );
+  assert!(annotated.contains("4. Qxf7# 1-0"));
+  // The annotations don't get in the way of reading it back
+  let read = pgn::parse(&annotated).unwrap().remove(0);
+  assert_eq!(read.game.moves(), game.moves());
+
+  // The app works it out in the background and marks the moves
+  let mut app = App::default();
+  assert!(app.run_command("report").is_err());
+  for san in ["e4", "e5", "Bc4", "Nc6", "Qh5", "Nf6", "Qxf7#"] {
+    app.run_command(san).unwrap();
+  }
+  assert!(app.run_command("report save x.pgn").is_err());
+  app.run_command("report").unwrap();
+  assert!(app.report_progress().is_some());
+
+  let started = Instant::now();
+  while app.report().is_none() && started.elapsed() < Duration::from_secs(30) {
+    app.tick();
+    std::thread::sleep(Duration::from_millis(10));
+  }
+  assert_eq!(app.report().map(|report| report.moves.len()), Some(7));
+  assert_eq!(app.report_progress(), None);
+  assert!(app
+    .message()
+    .is_some_and(|message| message.starts_with("Report of 7 moves done")));
+
+  let path = std::env::temp_dir().join(format!("chyes-report-{}.pgn", std::process::id()));
+  let saved = app
+    .run_command(&format!("report save {}", path.display()))
+    .unwrap();
+  assert!(saved.is_some_and(|saved| saved.starts_with("Saved")));
+  let text = std::fs::read_to_string(&path).unwrap();
+  std::fs::remove_file(&path).unwrap();
+  assert!(text.contains("[Annotator \"chyes\"]") && text.contains("$4"));
+
+  // The graph and the judged moves on screen
+  let mut terminal = tui::Terminal::new(tui::backend::TestBackend::new(80, 40)).unwrap();
+  terminal.draw(|f| app.draw(f)).unwrap();
+  let screen: String = terminal
+    .backend()
+    .buffer()
+    .content()
+    .iter()
+    .map(|cell| cell.symbol.as_str())
+    .collect();
+  assert!(screen.contains("Report") && screen.contains("Nf6??"));
+  assert!(screen.contains("4. Qxf7# game over"));
+  assert!(screen.contains("Black: ") && screen.contains("1 blunder"));
+
+  // A report of another game isn't kept
+  app.run_command("undo").unwrap();
+  app.tick();
+  assert!(app.report().is_none());
+}
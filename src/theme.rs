@@ -0,0 +1,439 @@
+use std::env;
+use std::fs;
+use std::path::Path;
+use std::str::FromStr;
+
+use tui::style::Color as TermColor;
+
+use crate::{Color, Piece, BLACK_PIECES, WHITE_PIECES};
+
+/// Characters the pieces are drawn with
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct GlyphSet {
+  // Sequence: King, Queen, Rook, Bishop, Knight, Pawn
+  white: [char; 6],
+  black: [char; 6]
+}
+
+impl GlyphSet {
+  /// FEN letters, for terminals and fonts without chess symbols
+  pub const ASCII: GlyphSet = GlyphSet {
+    white: ['K', 'Q', 'R', 'B', 'N', 'P'],
+    black: ['k', 'q', 'r', 'b', 'n', 'p']
+  };
+  /// Filled white pieces, the default since they read well on dark terminals
+  pub const FILLED: GlyphSet = GlyphSet {
+    white: WHITE_PIECES,
+    black: BLACK_PIECES
+  };
+  /// Outlined white pieces, as in printed diagrams
+  pub const OUTLINE: GlyphSet = GlyphSet {
+    white: BLACK_PIECES,
+    black: WHITE_PIECES
+  };
+
+  pub fn glyph(
+    &self,
+    piece: Piece
+  ) -> char {
+    match piece.color {
+      Color::White => self.white[piece.breed],
+      Color::Black => self.black[piece.breed]
+    }
+  }
+}
+
+impl Default for GlyphSet {
+  fn default() -> Self { GlyphSet::FILLED }
+}
+
+impl FromStr for GlyphSet {
+  type Err = String;
+
+  /// `filled`, `outline`, `ascii`, or twelve custom characters in the order
+  /// `KQRBNPkqrbnp`
+  fn from_str(text: &str) -> Result<Self, Self::Err> {
+    match text.trim() {
+      "filled" | "unicode" => return Ok(GlyphSet::FILLED),
+      "outline" => return Ok(GlyphSet::OUTLINE),
+      "ascii" | "letters" => return Ok(GlyphSet::ASCII),
+      _ => {}
+    }
+
+    let chars: Vec<char> = text.trim().chars().collect();
+    if chars.len() != 12 {
+      return Err(format!(
+        "Unknown piece set `{}`, expected filled, outline, ascii or 12 characters",
+        text
+      ))
+    }
+
+    let mut set = GlyphSet::ASCII;
+    set.white.copy_from_slice(&chars[..6]);
+    set.black.copy_from_slice(&chars[6..]);
+    Ok(set)
+  }
+}
+
+/// How many colors the terminal can show
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ColorDepth {
+  TrueColor,
+  Ansi256,
+  Ansi16
+}
+
+impl ColorDepth {
+  /// Guesses the depth from `COLORTERM` and `TERM`, the same way most terminal
+  /// programs do
+  pub fn detect() -> Self {
+    let colorterm = env::var("COLORTERM").unwrap_or_default();
+    let term = env::var("TERM").unwrap_or_default();
+
+    if colorterm == "truecolor" || colorterm == "24bit" {
+      ColorDepth::TrueColor
+    } else if term.contains("256color") {
+      ColorDepth::Ansi256
+    } else {
+      ColorDepth::Ansi16
+    }
+  }
+}
+
+impl FromStr for ColorDepth {
+  type Err = String;
+
+  fn from_str(text: &str) -> Result<Self, Self::Err> {
+    match text.trim() {
+      "truecolor" | "24bit" => Ok(ColorDepth::TrueColor),
+      "256" => Ok(ColorDepth::Ansi256),
+      "16" => Ok(ColorDepth::Ansi16),
+      _ => Err(format!(
+        "Unknown color depth `{}`, expected truecolor, 256 or 16",
+        text
+      ))
+    }
+  }
+}
+
+/// Colors of the board widget
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Theme {
+  pub light:       TermColor,
+  pub dark:        TermColor,
+  pub cursor:      TermColor,
+  pub selected:    TermColor,
+  // Squares the selected piece can move to, and the one under the pointer
+  pub target:      TermColor,
+  pub hover:       TermColor,
+  // King of the side to move when it's in check
+  pub check:       TermColor,
+  pub last_move:   TermColor,
+  // Training overlays: pieces that can be taken for free, the opponent's
+  // threat and the suggested move
+  pub hanging:     TermColor,
+  pub threat:      TermColor,
+  pub hint:        TermColor,
+  pub white_piece: TermColor,
+  pub black_piece: TermColor
+}
+
+impl Default for Theme {
+  fn default() -> Self {
+    Theme {
+      light:       TermColor::Rgb(161, 189, 203),
+      dark:        TermColor::Rgb(254, 255, 255),
+      cursor:      TermColor::Rgb(120, 160, 230),
+      selected:    TermColor::Rgb(246, 246, 105),
+      target:      TermColor::Rgb(130, 200, 130),
+      hover:       TermColor::Rgb(80, 170, 80),
+      check:       TermColor::Rgb(230, 90, 80),
+      last_move:   TermColor::Rgb(205, 210, 140),
+      hanging:     TermColor::Rgb(240, 160, 80),
+      threat:      TermColor::Rgb(215, 130, 200),
+      hint:        TermColor::Rgb(110, 200, 220),
+      white_piece: TermColor::Black,
+      black_piece: TermColor::Black
+    }
+  }
+}
+
+// Names of the keys in theme files, in the order of the fields
+const KEYS: [&str; 13] = [
+  "light",
+  "dark",
+  "cursor",
+  "selected",
+  "target",
+  "hover",
+  "check",
+  "last_move",
+  "hanging",
+  "threat",
+  "hint",
+  "white_piece",
+  "black_piece"
+];
+
+impl Theme {
+  pub const NAMES: [&'static str; 3] = ["default", "wood", "green"];
+
+  /// One of the built in themes
+  pub fn named(name: &str) -> Option<Theme> {
+    let theme = Theme::default();
+
+    match name {
+      "default" => Some(theme),
+      "wood" => Some(Theme {
+        light: TermColor::Rgb(240, 217, 181),
+        dark: TermColor::Rgb(181, 136, 99),
+        white_piece: TermColor::Rgb(255, 255, 255),
+        black_piece: TermColor::Rgb(0, 0, 0),
+        ..theme
+      }),
+      "green" => Some(Theme {
+        light: TermColor::Rgb(238, 238, 210),
+        dark: TermColor::Rgb(118, 150, 86),
+        selected: TermColor::Rgb(186, 202, 68),
+        target: TermColor::Rgb(150, 190, 110),
+        ..theme
+      }),
+      _ => None
+    }
+  }
+
+  /// A built in theme, or a theme file
+  pub fn load(name: &str) -> Result<Theme, String> {
+    if let Some(theme) = Theme::named(name) {
+      return Ok(theme)
+    }
+
+    let path = Path::new(name);
+    let text = fs::read_to_string(path).map_err(|err| {
+      format!(
+        "Couldn't read theme {}: {} (built in themes: {})",
+        name,
+        err,
+        Theme::NAMES.join(", ")
+      )
+    })?;
+    Theme::parse(&text)
+  }
+
+  /// Theme file with `key = value` lines, where the value is a color name,
+  /// a `#rrggbb` hex code or a 256 color palette index. A `base` key picks
+  /// the built in theme the missing colors come from
+  pub fn parse(text: &str) -> Result<Theme, String> {
+    let mut theme = Theme::default();
+    let mut colors = Vec::new();
+
+    for line in text.lines() {
+      let line = line.trim();
+      if line.is_empty() || line.starts_with('#') || line.starts_with('[') {
+        continue
+      }
+
+      let (key, value) = line
+        .split_once('=')
+        .ok_or_else(|| format!("Expected `key = value` in theme, got `{}`", line))?;
+      let key = key.trim();
+      let value = value_of(value);
+
+      if key == "base" {
+        theme = Theme::named(value).ok_or_else(|| format!("Unknown theme `{}`", value))?;
+      } else if KEYS.contains(&key) {
+        colors.push((key, parse_color(value)?));
+      } else {
+        return Err(format!("Unknown theme key `{}`", key))
+      }
+    }
+
+    for (key, color) in colors {
+      *theme.field(key) = color;
+    }
+
+    Ok(theme)
+  }
+
+  fn field(
+    &mut self,
+    key: &str
+  ) -> &mut TermColor {
+    match key {
+      "light" => &mut self.light,
+      "dark" => &mut self.dark,
+      "cursor" => &mut self.cursor,
+      "selected" => &mut self.selected,
+      "target" => &mut self.target,
+      "hover" => &mut self.hover,
+      "check" => &mut self.check,
+      "last_move" => &mut self.last_move,
+      "hanging" => &mut self.hanging,
+      "threat" => &mut self.threat,
+      "hint" => &mut self.hint,
+      "white_piece" => &mut self.white_piece,
+      _ => &mut self.black_piece
+    }
+  }
+
+  /// The same theme with its colors brought down to what the terminal shows
+  pub fn with_depth(
+    mut self,
+    depth: ColorDepth
+  ) -> Self {
+    for key in KEYS {
+      let color = self.field(key);
+      *color = downgrade(*color, depth);
+    }
+
+    self
+  }
+}
+
+// Value of a `key = value` line, quoted or not, without a trailing comment
+pub(crate) fn value_of(text: &str) -> &str {
+  let text = text.trim();
+
+  match text.strip_prefix('"') {
+    Some(quoted) => quoted.split('"').next().unwrap_or_default(),
+    None => text.split(" #").next().unwrap_or_default().trim()
+  }
+}
+
+fn parse_color(text: &str) -> Result<TermColor, String> {
+  if let Some(hex) = text.strip_prefix('#') {
+    let channel = |index: usize| {
+      hex
+        .get(index..index + 2)
+        .and_then(|channel| u8::from_str_radix(channel, 16).ok())
+    };
+
+    return match (hex.len(), channel(0), channel(2), channel(4)) {
+      (6, Some(r), Some(g), Some(b)) => Ok(TermColor::Rgb(r, g, b)),
+      _ => Err(format!("Invalid color `{}`", text))
+    }
+  }
+
+  if let Ok(index) = text.parse::<u8>() {
+    return Ok(TermColor::Indexed(index))
+  }
+
+  ANSI_16
+    .iter()
+    .find(|(_, name, _)| *name == text.to_lowercase().replace(['_', '-', ' '], ""))
+    .map(|(color, _, _)| *color)
+    .ok_or_else(|| format!("Invalid color `{}`", text))
+}
+
+// The 16 basic colors, with their usual xterm values
+const ANSI_16: [(TermColor, &str, (u8, u8, u8)); 16] = [
+  (TermColor::Black, "black", (0, 0, 0)),
+  (TermColor::Red, "red", (205, 0, 0)),
+  (TermColor::Green, "green", (0, 205, 0)),
+  (TermColor::Yellow, "yellow", (205, 205, 0)),
+  (TermColor::Blue, "blue", (0, 0, 238)),
+  (TermColor::Magenta, "magenta", (205, 0, 205)),
+  (TermColor::Cyan, "cyan", (0, 205, 205)),
+  (TermColor::Gray, "gray", (229, 229, 229)),
+  (TermColor::DarkGray, "darkgray", (127, 127, 127)),
+  (TermColor::LightRed, "lightred", (255, 0, 0)),
+  (TermColor::LightGreen, "lightgreen", (0, 255, 0)),
+  (TermColor::LightYellow, "lightyellow", (255, 255, 0)),
+  (TermColor::LightBlue, "lightblue", (92, 92, 255)),
+  (TermColor::LightMagenta, "lightmagenta", (255, 0, 255)),
+  (TermColor::LightCyan, "lightcyan", (0, 255, 255)),
+  (TermColor::White, "white", (255, 255, 255))
+];
+
+/// SGR parameters setting the color as the background or the foreground,
+/// e.g. `48;2;255;0;0`
+pub(crate) fn sgr(
+  color: TermColor,
+  background: bool
+) -> String {
+  let (base, bright, extended) = if background {
+    (40, 100, 48)
+  } else {
+    (30, 90, 38)
+  };
+
+  match color {
+    TermColor::Rgb(r, g, b) => format!("{};2;{};{};{}", extended, r, g, b),
+    TermColor::Indexed(index) => format!("{};5;{}", extended, index),
+    TermColor::Reset => (base + 9).to_string(),
+    color => {
+      let index = ANSI_16
+        .iter()
+        .position(|(basic, _, _)| *basic == color)
+        .unwrap_or_default();
+      if index < 8 {
+        (base + index).to_string()
+      } else {
+        (bright + index - 8).to_string()
+      }
+    }
+  }
+}
+
+// Levels of each channel in the 6x6x6 cube of the 256 color palette
+const CUBE: [u8; 6] = [0, 95, 135, 175, 215, 255];
+
+fn distance(
+  a: (u8, u8, u8),
+  b: (u8, u8, u8)
+) -> u32 {
+  let d = |x: u8, y: u8| (x as i32 - y as i32).pow(2) as u32;
+  d(a.0, b.0) + d(a.1, b.1) + d(a.2, b.2)
+}
+
+fn palette_rgb(index: u8) -> (u8, u8, u8) {
+  match index {
+    0..=15 => ANSI_16[index as usize].2,
+    16..=231 => {
+      let index = index - 16;
+      (
+        CUBE[index as usize / 36],
+        CUBE[index as usize / 6 % 6],
+        CUBE[index as usize % 6]
+      )
+    },
+    _ => {
+      let level = 8 + 10 * (index - 232);
+      (level, level, level)
+    }
+  }
+}
+
+fn downgrade(
+  color: TermColor,
+  depth: ColorDepth
+) -> TermColor {
+  let rgb = match color {
+    TermColor::Rgb(r, g, b) => (r, g, b),
+    TermColor::Indexed(index) => palette_rgb(index),
+    _ => return color
+  };
+
+  match (depth, color) {
+    (ColorDepth::TrueColor, _) | (ColorDepth::Ansi256, TermColor::Indexed(_)) => color,
+    (ColorDepth::Ansi256, _) => {
+      // Closest of the color cube and the gray ramp
+      let nearest = |value: u8| {
+        (0..6)
+          .min_by_key(|&level| (CUBE[level] as i32 - value as i32).abs())
+          .unwrap_or_default()
+      };
+      let cube = 16 + 36 * nearest(rgb.0) + 6 * nearest(rgb.1) + nearest(rgb.2);
+
+      let index = (232..=255)
+        .chain([cube as u8])
+        .min_by_key(|&index| distance(palette_rgb(index), rgb))
+        .unwrap_or(cube as u8);
+      TermColor::Indexed(index)
+    },
+    (ColorDepth::Ansi16, _) => ANSI_16
+      .iter()
+      .min_by_key(|(_, _, value)| distance(*value, rgb))
+      .map_or(color, |(color, _, _)| *color)
+  }
+}
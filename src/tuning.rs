@@ -0,0 +1,272 @@
+use std::path::Path;
+use std::{fs, io};
+
+use crate::eval::{features, EvalParams, Features, TERMS};
+use crate::Board;
+
+// Texel tuning: the static evaluation is mapped to an expected game result
+// with a sigmoid, and the weights are changed to minimise the mean squared
+// error against the real results of a set of quiet positions.
+
+struct Sample {
+  features: Features,
+  // 1.0 - white won, 0.5 - draw, 0.0 - black won
+  result:   f64
+}
+
+pub struct Dataset {
+  samples: Vec<Sample>
+}
+
+fn parse_result(result: &str) -> Option<f64> {
+  let result = result.trim_matches(|c: char| c.is_whitespace() || "[]\"';,|".contains(c));
+
+  match result {
+    "1-0" | "1.0" | "1" => Some(1.0),
+    "0-1" | "0.0" | "0" => Some(0.0),
+    "1/2-1/2" | "0.5" | "1/2" => Some(0.5),
+    _ => None
+  }
+}
+
+impl Dataset {
+  /// One position per line: a FEN followed by the game result, e.g.
+  /// `<fen> [0.5]`, `<fen>; 1-0` or `<fen> "1/2-1/2"`. EPD lines leave out
+  /// the clocks and put the result in an opcode, `<epd> c9 "1-0";`
+  pub fn parse(text: &str) -> Result<Self, String> {
+    let mut samples = Vec::new();
+
+    for (number, line) in text.lines().enumerate() {
+      let line = line.trim();
+
+      if line.is_empty() || line.starts_with('#') {
+        continue
+      }
+
+      let fields: Vec<&str> = line.split_whitespace().collect();
+      let clocks = fields.len() > 6
+        && fields[4..6]
+          .iter()
+          .all(|field| field.trim_end_matches(';').parse::<u32>().is_ok());
+      let (fen, rest) = if clocks {
+        (fields[..6].join(" "), &fields[6..])
+      } else if fields.len() > 4 {
+        (format!("{} 0 1", fields[..4].join(" ")), &fields[4..])
+      } else {
+        return Err(format!("Line {}: expected a FEN and a result", number + 1))
+      };
+
+      let fen = fen.trim_end_matches(';');
+      let mut board = Board::new();
+      board
+        .load_fen(fen)
+        .map_err(|err| format!("Line {}: {}", number + 1, err))?;
+
+      // The opcode naming the result, `c9`, is skipped
+      let rest = match rest {
+        [opcode, result @ ..]
+          if !result.is_empty() && opcode.starts_with(|c: char| c.is_ascii_alphabetic()) =>
+        {
+          result
+        },
+        _ => rest
+      };
+      let result = parse_result(&rest.join(" "))
+        .ok_or_else(|| format!("Line {}: couldn't parse the game result", number + 1))?;
+
+      samples.push(Sample {
+        features: features(&board),
+        result
+      });
+    }
+
+    Ok(Dataset {
+      samples
+    })
+  }
+
+  pub fn load(path: impl AsRef<Path>) -> io::Result<Self> {
+    Self::parse(&fs::read_to_string(path)?)
+      .map_err(|message| io::Error::new(io::ErrorKind::InvalidData, message))
+  }
+
+  pub fn len(&self) -> usize { self.samples.len() }
+
+  pub fn is_empty(&self) -> bool { self.samples.is_empty() }
+}
+
+/// Expected result for white of an evaluation in centipawns
+pub fn sigmoid(
+  score: f64,
+  k: f64
+) -> f64 {
+  1.0 / (1.0 + 10f64.powf(-k * score / 400.0))
+}
+
+pub enum Method {
+  // Tries to move every weight by one step at a time, keeping improvements
+  LocalSearch { step: i32 },
+  // Gradient descent on real valued weights, rounded at the end
+  GradientDescent { learning_rate: f64 }
+}
+
+pub struct Tuner<'a> {
+  dataset: &'a Dataset,
+  k:       f64
+}
+
+impl<'a> Tuner<'a> {
+  /// Fits the sigmoid scaling constant to the evaluation before tuning
+  pub fn new(
+    dataset: &'a Dataset,
+    params: &EvalParams
+  ) -> Self {
+    let mut tuner = Tuner {
+      dataset,
+      k: 1.0
+    };
+    tuner.k = tuner.optimize_k(params);
+    tuner
+  }
+
+  pub fn k(&self) -> f64 { self.k }
+
+  pub fn mean_squared_error(
+    &self,
+    params: &EvalParams
+  ) -> f64 {
+    self.error(|features| params.score(features) as f64, self.k)
+  }
+
+  fn error(
+    &self,
+    score: impl Fn(&Features) -> f64,
+    k: f64
+  ) -> f64 {
+    if self.dataset.is_empty() {
+      return 0.0
+    }
+
+    self
+      .dataset
+      .samples
+      .iter()
+      .map(|sample| (sample.result - sigmoid(score(&sample.features), k)).powi(2))
+      .sum::<f64>()
+      / self.dataset.len() as f64
+  }
+
+  // Scans K with decreasing step sizes around the best value so far
+  fn optimize_k(
+    &self,
+    params: &EvalParams
+  ) -> f64 {
+    let error = |k: f64| self.error(|features| params.score(features) as f64, k);
+    let mut best: f64 = 1.0;
+    let mut step: f64 = 1.0;
+
+    for _ in 0..6 {
+      let mut k = (best - 10.0 * step).max(step / 10.0);
+
+      while k <= best + 10.0 * step {
+        if error(k) < error(best) {
+          best = k;
+        }
+        k += step;
+      }
+
+      step /= 10.0;
+    }
+
+    best
+  }
+
+  /// Runs the method for at most `iterations` passes, calling `progress`
+  /// with the iteration and the error after each pass
+  pub fn tune(
+    &self,
+    params: &EvalParams,
+    method: Method,
+    iterations: usize,
+    mut progress: impl FnMut(usize, f64)
+  ) -> EvalParams {
+    match method {
+      Method::LocalSearch {
+        step
+      } => {
+        let mut best = params.clone();
+        let mut best_error = self.mean_squared_error(&best);
+
+        for iteration in 0..iterations {
+          let mut improved = false;
+
+          for i in 0..TERMS.len() {
+            for delta in [step, -step] {
+              let mut candidate = best.clone();
+              candidate.weights[i] += delta;
+
+              let error = self.mean_squared_error(&candidate);
+              if error < best_error {
+                best = candidate;
+                best_error = error;
+                improved = true;
+                break
+              }
+            }
+          }
+
+          progress(iteration + 1, best_error);
+
+          if !improved {
+            break
+          }
+        }
+
+        best
+      },
+      Method::GradientDescent {
+        learning_rate
+      } => {
+        let mut weights: Vec<f64> = params.weights.iter().map(|&w| w as f64).collect();
+        let score = |weights: &[f64], features: &Features| -> f64 {
+          weights
+            .iter()
+            .zip(features.iter())
+            .map(|(w, &f)| w * f as f64)
+            .sum()
+        };
+
+        for iteration in 0..iterations {
+          let mut gradient = vec![0.0; TERMS.len()];
+
+          for sample in self.dataset.samples.iter() {
+            let expected = sigmoid(score(&weights, &sample.features), self.k);
+            // d/dw of (result - sigmoid)^2
+            let factor =
+              -2.0 * (sample.result - expected) * expected * (1.0 - expected) * self.k * 10f64.ln()
+                / 400.0;
+
+            for (g, &f) in gradient.iter_mut().zip(sample.features.iter()) {
+              *g += factor * f as f64;
+            }
+          }
+
+          for (w, g) in weights.iter_mut().zip(gradient.iter()) {
+            *w -= learning_rate * g / self.dataset.len().max(1) as f64;
+          }
+
+          progress(
+            iteration + 1,
+            self.error(|features| score(&weights, features), self.k)
+          );
+        }
+
+        let mut tuned = params.clone();
+        for (weight, value) in tuned.weights.iter_mut().zip(weights.iter()) {
+          *weight = value.round() as i32;
+        }
+        tuned
+      }
+    }
+  }
+}
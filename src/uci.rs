@@ -0,0 +1,403 @@
+use std::io::{BufRead, BufReader, Write};
+use std::process::{Child, ChildStdin, Command, Stdio};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use crate::eval::Evaluator;
+use crate::game::Game;
+use crate::search::{self, Limits, Score};
+use crate::{Board, Color, Move};
+
+// Both sides of the universal chess interface: a client driving an engine
+// subprocess, and a server exposing our own search on stdin and stdout.
+
+/// Moves in UCI notation, played one after another from the position
+pub fn moves_to_uci(
+  board: &Board,
+  moves: &[Move]
+) -> Vec<String> {
+  let mut board = board.clone();
+
+  moves
+    .iter()
+    .map(|&mv| {
+      let text = board.to_uci(mv);
+      let _ = board.apply_move(mv);
+      text
+    })
+    .collect()
+}
+
+fn limits_to_go(
+  limits: &Limits,
+  board: &Board
+) -> String {
+  let mut go = String::from("go");
+
+  if let Some(depth) = limits.depth {
+    go.push_str(&format!(" depth {}", depth));
+  }
+  if let Some(nodes) = limits.nodes {
+    go.push_str(&format!(" nodes {}", nodes));
+  }
+  if let Some(movetime) = limits.movetime {
+    go.push_str(&format!(" movetime {}", movetime.as_millis()));
+  }
+  if let Some(time) = limits.time {
+    let side = if board.turn == Color::White { "w" } else { "b" };
+    go.push_str(&format!(
+      " {side}time {} {side}inc {}",
+      time.as_millis(),
+      limits.increment.as_millis()
+    ));
+  }
+  if let Some(moves_to_go) = limits.moves_to_go {
+    go.push_str(&format!(" movestogo {}", moves_to_go));
+  }
+
+  go
+}
+
+/// An engine running in a subprocess
+pub struct UciEngine {
+  name:  String,
+  child: Child,
+  stdin: ChildStdin,
+  lines: Receiver<String>
+}
+
+impl UciEngine {
+  /// Starts the engine and waits for it to finish the handshake
+  pub fn spawn(
+    command: &str,
+    args: &[String]
+  ) -> Result<Self, String> {
+    let mut child = Command::new(command)
+      .args(args)
+      .stdin(Stdio::piped())
+      .stdout(Stdio::piped())
+      .stderr(Stdio::null())
+      .spawn()
+      .map_err(|err| format!("Couldn't start {}: {}", command, err))?;
+
+    let stdin = child.stdin.take().expect("Stdin is piped");
+    let stdout = child.stdout.take().expect("Stdout is piped");
+
+    // Lines are read on a thread, so waiting for them can time out
+    let (sender, lines) = mpsc::channel();
+    thread::spawn(move || {
+      for line in BufReader::new(stdout).lines().map_while(Result::ok) {
+        if sender.send(line).is_err() {
+          break
+        }
+      }
+    });
+
+    let mut engine = UciEngine {
+      name: String::from(command),
+      child,
+      stdin,
+      lines
+    };
+
+    engine.send("uci")?;
+    loop {
+      let line = engine.read_line(Duration::from_secs(10))?;
+
+      if let Some(name) = line.strip_prefix("id name ") {
+        engine.name = name.trim().to_string();
+      } else if line.trim() == "uciok" {
+        break
+      }
+    }
+
+    Ok(engine)
+  }
+
+  pub fn name(&self) -> &str { &self.name }
+
+  fn send(
+    &mut self,
+    command: &str
+  ) -> Result<(), String> {
+    writeln!(self.stdin, "{}", command)
+      .and_then(|_| self.stdin.flush())
+      .map_err(|err| format!("{} stopped responding: {}", self.name, err))
+  }
+
+  fn read_line(
+    &mut self,
+    timeout: Duration
+  ) -> Result<String, String> {
+    self.lines.recv_timeout(timeout).map_err(|err| match err {
+      RecvTimeoutError::Timeout => format!("{} timed out", self.name),
+      RecvTimeoutError::Disconnected => format!("{} exited", self.name)
+    })
+  }
+
+  pub fn set_option(
+    &mut self,
+    name: &str,
+    value: &str
+  ) -> Result<(), String> {
+    self.send(&format!("setoption name {} value {}", name, value))
+  }
+
+  /// Waits until the engine has processed everything sent so far
+  pub fn wait_ready(&mut self) -> Result<(), String> {
+    self.send("isready")?;
+
+    while self.read_line(Duration::from_secs(10))?.trim() != "readyok" {}
+
+    Ok(())
+  }
+
+  pub fn new_game(&mut self) -> Result<(), String> {
+    self.send("ucinewgame")?;
+    self.wait_ready()
+  }
+
+  /// Searches the position reached by the moves from the start, returns the
+  /// best move with the last reported score
+  pub fn go(
+    &mut self,
+    start: &Board,
+    moves: &[Move],
+    limits: &Limits,
+    timeout: Duration
+  ) -> Result<(Move, Option<Score>), String> {
+    let mut board = start.clone();
+    for mv in moves {
+      board.apply_move(*mv)?;
+    }
+
+    let mut position = format!("position fen {}", start.get_fen());
+    if !moves.is_empty() {
+      position.push_str(" moves ");
+      position.push_str(&moves_to_uci(start, moves).join(" "));
+    }
+
+    self.send(&position)?;
+    self.send(&limits_to_go(limits, &board))?;
+
+    let deadline = Instant::now() + timeout;
+    let mut score = None;
+
+    loop {
+      let line = self.read_line(deadline.saturating_duration_since(Instant::now()))?;
+      let mut tokens = line.split_whitespace();
+
+      match tokens.next() {
+        Some("info") => {
+          while let Some(token) = tokens.next() {
+            if token == "score" {
+              let kind = tokens.next();
+              let value = tokens.next().and_then(|value| value.parse().ok());
+
+              score = match (kind, value) {
+                (Some("cp"), Some(value)) => Some(Score::Cp(value)),
+                (Some("mate"), Some(value)) => Some(Score::Mate(value)),
+                _ => score
+              };
+            }
+          }
+        },
+        Some("bestmove") => {
+          let text = tokens.next().unwrap_or("(none)");
+          let mv = board
+            .parse_uci(text)
+            .map_err(|err| format!("{} played {}: {}", self.name, text, err))?;
+
+          return Ok((mv, score))
+        },
+        _ => {}
+      }
+    }
+  }
+}
+
+impl Drop for UciEngine {
+  fn drop(&mut self) {
+    let _ = self.send("quit");
+
+    // Give the engine a moment to exit by itself
+    for _ in 0..20 {
+      if let Ok(Some(_)) = self.child.try_wait() {
+        return
+      }
+      thread::sleep(Duration::from_millis(10));
+    }
+
+    let _ = self.child.kill();
+    let _ = self.child.wait();
+  }
+}
+
+fn parse_position(tokens: &[&str]) -> Result<Game, String> {
+  let (mut game, rest) = match tokens {
+    ["startpos", rest @ ..] => (Game::default(), rest),
+    ["fen", rest @ ..] => {
+      let end = rest
+        .iter()
+        .position(|&token| token == "moves")
+        .unwrap_or(rest.len());
+      (Game::from_fen(&rest[..end].join(" "))?, &rest[end..])
+    },
+    _ => return Err(String::from("Expected startpos or fen"))
+  };
+
+  for text in rest.iter().skip(1) {
+    let mv = game.board().parse_uci(text)?;
+    game.play(mv)?;
+  }
+
+  Ok(game)
+}
+
+fn parse_go(
+  tokens: &[&str],
+  board: &Board
+) -> Limits {
+  let mut limits = Limits::default();
+  let white = board.turn == Color::White;
+  let millis = |value: Option<&&str>| {
+    Duration::from_millis(value.and_then(|value| value.parse().ok()).unwrap_or(0))
+  };
+
+  for (index, &token) in tokens.iter().enumerate() {
+    let value = tokens.get(index + 1);
+
+    match token {
+      "depth" => limits.depth = value.and_then(|value| value.parse().ok()),
+      "nodes" => limits.nodes = value.and_then(|value| value.parse().ok()),
+      "movetime" => limits.movetime = Some(millis(value)),
+      "wtime" if white => limits.time = Some(millis(value)),
+      "btime" if !white => limits.time = Some(millis(value)),
+      "winc" if white => limits.increment = millis(value),
+      "binc" if !white => limits.increment = millis(value),
+      "movestogo" => limits.moves_to_go = value.and_then(|value| value.parse().ok()),
+      _ => {}
+    }
+  }
+
+  limits
+}
+
+fn say(
+  output: &Mutex<impl Write>,
+  text: &str
+) {
+  let mut output = output.lock().expect("Output lock is never poisoned");
+  let _ = writeln!(output, "{}", text);
+  let _ = output.flush();
+}
+
+/// Runs the engine over UCI until `quit` or the end of the input
+pub fn serve(
+  evaluator: Evaluator,
+  input: impl BufRead,
+  output: impl Write + Send + 'static
+) {
+  let evaluator = Arc::new(evaluator);
+  let output = Arc::new(Mutex::new(output));
+  let stop = Arc::new(AtomicBool::new(false));
+  let mut game = Game::default();
+  let mut searching: Option<JoinHandle<()>> = None;
+  let mut multi_pv = 1;
+
+  let finish = |searching: &mut Option<JoinHandle<()>>| {
+    if let Some(handle) = searching.take() {
+      stop.store(true, Ordering::Relaxed);
+      let _ = handle.join();
+    }
+  };
+
+  for line in input.lines() {
+    let line = match line {
+      Ok(line) => line,
+      Err(_) => break
+    };
+    let tokens: Vec<&str> = line.split_whitespace().collect();
+
+    match tokens.first().copied() {
+      Some("uci") => {
+        say(&output, "id name chyes");
+        say(&output, "id author chyes developers");
+        say(
+          &output,
+          "option name MultiPV type spin default 1 min 1 max 20"
+        );
+        say(&output, "uciok");
+      },
+      Some("isready") => say(&output, "readyok"),
+      Some("setoption") => {
+        // setoption name MultiPV value 3
+        if let [_, _, name, _, value] = tokens.as_slice() {
+          if name.eq_ignore_ascii_case("MultiPV") {
+            multi_pv = value.parse().unwrap_or(1);
+          }
+        }
+      },
+      Some("ucinewgame") => {
+        finish(&mut searching);
+        game = Game::default();
+      },
+      Some("position") => {
+        finish(&mut searching);
+        match parse_position(&tokens[1..]) {
+          Ok(position) => game = position,
+          Err(err) => say(&output, &format!("info string {}", err))
+        }
+      },
+      Some("go") => {
+        finish(&mut searching);
+
+        let board = game.board().clone();
+        let history = game.history().to_vec();
+        let limits = Limits {
+          multi_pv,
+          ..parse_go(&tokens[1..], &board)
+        };
+        let evaluator = evaluator.clone();
+        let output = output.clone();
+        let stop = stop.clone();
+        stop.store(false, Ordering::Relaxed);
+
+        searching = Some(thread::spawn(move || {
+          let result = search::search(&board, &history, &evaluator, &limits, &stop, |info| {
+            let score = match info.score {
+              Score::Cp(cp) => format!("cp {}", cp),
+              Score::Mate(moves) => format!("mate {}", moves)
+            };
+            say(
+              &output,
+              &format!(
+                "info depth {} multipv {} score {} nodes {} nps {} time {} pv {}",
+                info.depth,
+                info.multipv,
+                score,
+                info.nodes,
+                info.nps(),
+                info.time.as_millis(),
+                moves_to_uci(&board, &info.pv).join(" ")
+              )
+            );
+          });
+
+          let best = result
+            .best_move
+            .map_or_else(|| String::from("0000"), |mv| board.to_uci(mv));
+          say(&output, &format!("bestmove {}", best));
+        }));
+      },
+      Some("stop") => finish(&mut searching),
+      Some("quit") => break,
+      _ => {}
+    }
+  }
+
+  finish(&mut searching);
+}
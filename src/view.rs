@@ -0,0 +1,168 @@
+use core::fmt;
+use std::str::FromStr;
+
+use tui::buffer::Buffer;
+use tui::layout::Rect;
+use tui::widgets::Widget;
+
+use crate::render::{TextBoard, TextStyle};
+use crate::theme::GlyphSet;
+use crate::widget::BoardWidget;
+use crate::{Board, Color};
+
+/// Which side of the board is drawn at the bottom
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum Orientation {
+  #[default]
+  White,
+  Black,
+  // The side to move at the start of the game, e.g. the one solving a puzzle
+  SideToMove,
+  // The side to move right now, flipping after every move
+  AutoFlip
+}
+
+impl Orientation {
+  /// The next orientation, in the order they are listed
+  pub fn next(self) -> Self {
+    match self {
+      Orientation::White => Orientation::Black,
+      Orientation::Black => Orientation::SideToMove,
+      Orientation::SideToMove => Orientation::AutoFlip,
+      Orientation::AutoFlip => Orientation::White
+    }
+  }
+}
+
+impl fmt::Display for Orientation {
+  fn fmt(
+    &self,
+    f: &mut fmt::Formatter<'_>
+  ) -> fmt::Result {
+    write!(
+      f,
+      "{}",
+      match self {
+        Orientation::White => "white",
+        Orientation::Black => "black",
+        Orientation::SideToMove => "side to move",
+        Orientation::AutoFlip => "auto flip"
+      }
+    )
+  }
+}
+
+impl FromStr for Orientation {
+  type Err = String;
+
+  fn from_str(text: &str) -> Result<Self, Self::Err> {
+    match text.trim().to_lowercase().as_str() {
+      "white" | "w" => Ok(Orientation::White),
+      "black" | "b" => Ok(Orientation::Black),
+      "side" | "side-to-move" | "stm" => Ok(Orientation::SideToMove),
+      "auto" | "auto-flip" | "flip" => Ok(Orientation::AutoFlip),
+      _ => Err(format!("Unknown orientation `{}`", text))
+    }
+  }
+}
+
+/// A board seen from one side, shared by the terminal widget and the text output
+#[derive(Clone, Copy)]
+pub struct BoardView<'a> {
+  board:       &'a Board,
+  orientation: Orientation,
+  // Side to move when the game started, for `Orientation::SideToMove`
+  first:       Color,
+  glyphs:      GlyphSet
+}
+
+impl<'a> BoardView<'a> {
+  pub fn new(board: &'a Board) -> Self {
+    BoardView {
+      board,
+      orientation: Orientation::default(),
+      first: board.turn,
+      glyphs: GlyphSet::default()
+    }
+  }
+
+  pub fn orientation(
+    mut self,
+    orientation: Orientation
+  ) -> Self {
+    self.orientation = orientation;
+    self
+  }
+
+  /// Side to move in the starting position of the game the board comes from
+  pub fn first_to_move(
+    mut self,
+    color: Color
+  ) -> Self {
+    self.first = color;
+    self
+  }
+
+  pub fn glyphs(
+    mut self,
+    glyphs: GlyphSet
+  ) -> Self {
+    self.glyphs = glyphs;
+    self
+  }
+
+  pub fn board(&self) -> &'a Board { self.board }
+
+  /// Side drawn at the bottom
+  pub fn bottom(&self) -> Color {
+    match self.orientation {
+      Orientation::White => Color::White,
+      Orientation::Black => Color::Black,
+      Orientation::SideToMove => self.first,
+      Orientation::AutoFlip => self.board.turn
+    }
+  }
+
+  pub fn is_flipped(&self) -> bool { self.bottom() == Color::Black }
+
+  /// Terminal widget drawing the board from the same side
+  pub fn widget(&self) -> BoardWidget<'a> {
+    BoardWidget::new(self.board)
+      .flipped(self.is_flipped())
+      .glyphs(self.glyphs)
+  }
+
+  /// Text rendering from the same side, in any of the text styles
+  pub fn text(&self) -> TextBoard<'a> {
+    TextBoard::new(self.board)
+      .flipped(self.is_flipped())
+      .glyphs(self.glyphs)
+  }
+
+  /// Prints the board to the standard output
+  pub fn draw(&self) {
+    for line in self.text().style(TextStyle::Compact).render().lines() {
+      let squares: Vec<String> = line.chars().map(String::from).collect();
+      println!("{} ", squares.join(" "));
+    }
+  }
+}
+
+impl fmt::Display for BoardView<'_> {
+  fn fmt(
+    &self,
+    f: &mut fmt::Formatter<'_>
+  ) -> fmt::Result {
+    write!(f, "{}", self.text().style(TextStyle::Compact))
+  }
+}
+
+impl Widget for BoardView<'_> {
+  fn render(
+    self,
+    area: Rect,
+    buf: &mut Buffer
+  ) {
+    self.widget().render(area, buf)
+  }
+}
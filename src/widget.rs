@@ -0,0 +1,448 @@
+use tui::buffer::Buffer;
+use tui::layout::Rect;
+use tui::style::Style;
+use tui::widgets::Widget;
+
+use crate::theme::{GlyphSet, Theme};
+use crate::{Board, Color, Coordinate, Move, Piece, Pieces};
+
+// Square sizes in cells, tried from the largest down to the first that fits
+const SQUARE_SIZES: [(u16, u16); 5] = [(7, 3), (5, 3), (5, 2), (3, 1), (1, 1)];
+
+// Width of the rank labels column, the file labels take one row
+const LABEL_WIDTH: u16 = 2;
+
+// Three rows of five cells: a crown per piece over the initial, upper case for
+// white and lower case for black
+fn piece_art(piece: Piece) -> [String; 3] {
+  let crown = match piece.breed {
+    Pieces::King => "  +  ",
+    Pieces::Queen => " \\^/ ",
+    Pieces::Rook => " n_n ",
+    Pieces::Bishop => "  o  ",
+    Pieces::Knight => "  >) ",
+    Pieces::Pawn => "  _  "
+  };
+
+  let letter = match piece.breed {
+    Pieces::King => 'K',
+    Pieces::Queen => 'Q',
+    Pieces::Rook => 'R',
+    Pieces::Bishop => 'B',
+    Pieces::Knight => 'N',
+    Pieces::Pawn => 'P'
+  };
+  let letter = match piece.color {
+    Color::White => letter,
+    Color::Black => letter.to_ascii_lowercase()
+  };
+
+  [
+    crown.to_string(),
+    format!(" ({}) ", letter),
+    String::from(" /_\\ ")
+  ]
+}
+
+/// Where the squares and labels go inside the widget area
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+struct Geometry {
+  // Top left corner of the a8 (or h1 when flipped) square
+  x:      u16,
+  y:      u16,
+  width:  u16,
+  height: u16,
+  labels: bool
+}
+
+/// Draws a board together with the interactive markers of the TUI
+pub struct BoardWidget<'a> {
+  board:     &'a Board,
+  cursor:    Option<Coordinate>,
+  selected:  Option<Coordinate>,
+  hover:     Option<Coordinate>,
+  targets:   &'a [Coordinate],
+  last_move: Option<Move>,
+  hanging:   &'a [Coordinate],
+  threat:    Option<Move>,
+  hint:      Option<Move>,
+  flipped:   bool,
+  labels:    bool,
+  art:       bool,
+  theme:     Theme,
+  glyphs:    GlyphSet
+}
+
+impl<'a> BoardWidget<'a> {
+  pub fn new(board: &'a Board) -> Self {
+    BoardWidget {
+      board,
+      cursor: None,
+      selected: None,
+      hover: None,
+      targets: &[],
+      last_move: None,
+      hanging: &[],
+      threat: None,
+      hint: None,
+      flipped: false,
+      labels: true,
+      art: false,
+      theme: Theme::default(),
+      glyphs: GlyphSet::default()
+    }
+  }
+
+  pub fn cursor(
+    mut self,
+    cursor: Option<Coordinate>
+  ) -> Self {
+    self.cursor = cursor;
+    self
+  }
+
+  pub fn selected(
+    mut self,
+    selected: Option<Coordinate>
+  ) -> Self {
+    self.selected = selected;
+    self
+  }
+
+  /// Square under the mouse pointer
+  pub fn hover(
+    mut self,
+    hover: Option<Coordinate>
+  ) -> Self {
+    self.hover = hover;
+    self
+  }
+
+  /// Draws the board from black's side
+  pub fn flipped(
+    mut self,
+    flipped: bool
+  ) -> Self {
+    self.flipped = flipped;
+    self
+  }
+
+  /// Draws rank and file labels when there is room for them
+  pub fn labels(
+    mut self,
+    labels: bool
+  ) -> Self {
+    self.labels = labels;
+    self
+  }
+
+  /// Draws pieces as ASCII art on squares that are large enough
+  pub fn ascii_art(
+    mut self,
+    art: bool
+  ) -> Self {
+    self.art = art;
+    self
+  }
+
+  /// Squares the selected piece can move to
+  pub fn targets(
+    mut self,
+    targets: &'a [Coordinate]
+  ) -> Self {
+    self.targets = targets;
+    self
+  }
+
+  pub fn last_move(
+    mut self,
+    last_move: Option<Move>
+  ) -> Self {
+    self.last_move = last_move;
+    self
+  }
+
+  /// Pieces that can be taken for free
+  pub fn hanging(
+    mut self,
+    hanging: &'a [Coordinate]
+  ) -> Self {
+    self.hanging = hanging;
+    self
+  }
+
+  /// Move the opponent would play if it were their turn
+  pub fn threat(
+    mut self,
+    threat: Option<Move>
+  ) -> Self {
+    self.threat = threat;
+    self
+  }
+
+  /// Move suggested to the side to move
+  pub fn hint(
+    mut self,
+    hint: Option<Move>
+  ) -> Self {
+    self.hint = hint;
+    self
+  }
+
+  pub fn theme(
+    mut self,
+    theme: Theme
+  ) -> Self {
+    self.theme = theme;
+    self
+  }
+
+  pub fn glyphs(
+    mut self,
+    glyphs: GlyphSet
+  ) -> Self {
+    self.glyphs = glyphs;
+    self
+  }
+
+  // Largest squares that fit the area, a board that doesn't fit at all is clipped
+  fn geometry(
+    &self,
+    area: Rect
+  ) -> Geometry {
+    let with_labels = SQUARE_SIZES
+      .iter()
+      .filter(|_| self.labels)
+      .map(|&size| (size, true));
+    let without_labels = SQUARE_SIZES.iter().map(|&size| (size, false));
+
+    let ((width, height), labels) = with_labels
+      .chain(without_labels)
+      .find(|&((width, height), labels)| {
+        let label_width = if labels { LABEL_WIDTH } else { 0 };
+        let label_height = if labels { 1 } else { 0 };
+
+        8 * width + label_width <= area.width && 8 * height + label_height <= area.height
+      })
+      .unwrap_or(((1, 1), false));
+
+    Geometry {
+      x: area.x + if labels { LABEL_WIDTH } else { 0 },
+      y: area.y,
+      width,
+      height,
+      labels
+    }
+  }
+
+  // Row and column of the square on the screen, counted from the top left
+  fn screen_position(
+    &self,
+    coord: Coordinate
+  ) -> (u16, u16) {
+    if self.flipped {
+      (7 - coord.row as u16, 7 - coord.col as u16)
+    } else {
+      (coord.row as u16, coord.col as u16)
+    }
+  }
+
+  /// Area taken by the square when the board is drawn in `area`
+  pub fn square_area(
+    &self,
+    area: Rect,
+    coord: Coordinate
+  ) -> Rect {
+    let geometry = self.geometry(area);
+    let (row, col) = self.screen_position(coord);
+
+    let square = Rect::new(
+      geometry.x + col * geometry.width,
+      geometry.y + row * geometry.height,
+      geometry.width,
+      geometry.height
+    );
+
+    // Squares outside of a clipped board are empty
+    if square.intersects(area) {
+      square.intersection(area)
+    } else {
+      Rect::new(square.x, square.y, 0, 0)
+    }
+  }
+
+  /// Square under the terminal cell, when the board is drawn in `area`
+  pub fn square_at(
+    &self,
+    area: Rect,
+    x: u16,
+    y: u16
+  ) -> Option<Coordinate> {
+    let geometry = self.geometry(area);
+
+    if x < geometry.x || y < geometry.y || x >= area.right() || y >= area.bottom() {
+      return None
+    }
+
+    let (row, col) = (
+      (y - geometry.y) / geometry.height,
+      (x - geometry.x) / geometry.width
+    );
+    if row > 7 || col > 7 {
+      return None
+    }
+
+    let (row, col) = if self.flipped {
+      (7 - row, 7 - col)
+    } else {
+      (row, col)
+    };
+    Some(coord!(row as u8, col as u8))
+  }
+}
+
+// Whether the move starts or ends on the square
+fn touches(
+  mv: Option<Move>,
+  coord: Coordinate
+) -> bool {
+  mv.is_some_and(|mv| mv.start == coord || mv.target == coord)
+}
+
+// Writes the text, cutting it off at the edge of the area
+fn put(
+  buf: &mut Buffer,
+  area: Rect,
+  x: u16,
+  y: u16,
+  text: &str,
+  style: Style
+) {
+  if x >= area.x && x < area.right() && y >= area.y && y < area.bottom() {
+    buf.set_stringn(x, y, text, (area.right() - x) as usize, style);
+  }
+}
+
+impl<'a> Widget for BoardWidget<'a> {
+  fn render(
+    self,
+    area: Rect,
+    buf: &mut Buffer
+  ) {
+    let geometry = self.geometry(area);
+
+    // King of the side to move, when it's attacked
+    let turn = self.board.turn;
+    let check = self
+      .board
+      .pieces
+      .iter()
+      .find(|(_, piece)| piece.breed == Pieces::King && piece.color == turn)
+      .map(|(coord, _)| *coord)
+      .filter(|_| self.board.is_in_check(turn));
+
+    for row in 0..8 {
+      for col in 0..8 {
+        let coord = coord!(row, col);
+        let square = self.square_area(area, coord);
+
+        if square.area() == 0 {
+          continue
+        }
+
+        // Determine what is background color for given coordinate
+        let theme = &self.theme;
+        let color = if Some(coord) == self.hover && self.targets.contains(&coord) {
+          theme.hover
+        } else if Some(coord) == self.cursor {
+          theme.cursor
+        } else if Some(coord) == self.selected {
+          theme.selected
+        } else if self.targets.contains(&coord) {
+          theme.target
+        } else if Some(coord) == check {
+          theme.check
+        } else if touches(self.hint, coord) {
+          theme.hint
+        } else if touches(self.threat, coord) {
+          theme.threat
+        } else if self.hanging.contains(&coord) {
+          theme.hanging
+        } else if touches(self.last_move, coord) {
+          theme.last_move
+        } else if (row + col) % 2 == 0 {
+          theme.light
+        } else {
+          theme.dark
+        };
+        let piece = self.board.get_piece(&coord);
+        let foreground = match piece {
+          Some(Piece {
+            color: Color::White,
+            ..
+          }) => theme.white_piece,
+          _ => theme.black_piece
+        };
+        let style = Style::default().fg(foreground).bg(color);
+
+        buf.set_style(square, style);
+
+        // Pieces and target dots go in the middle of the square
+        let middle_x = square.x + geometry.width / 2;
+        let middle_y = square.y + geometry.height / 2;
+
+        match piece {
+          Some(piece) if self.art && geometry.width >= 5 && geometry.height >= 3 => {
+            for (line, text) in piece_art(piece).iter().enumerate() {
+              put(
+                buf,
+                area,
+                middle_x - 2,
+                middle_y - 1 + line as u16,
+                text,
+                style
+              );
+            }
+          },
+          Some(piece) => put(
+            buf,
+            area,
+            middle_x,
+            middle_y,
+            &self.glyphs.glyph(piece).to_string(),
+            style
+          ),
+          None if self.targets.contains(&coord) => put(buf, area, middle_x, middle_y, "·", style),
+          None => {}
+        }
+      }
+    }
+
+    if geometry.labels {
+      for index in 0..8 {
+        let (rank, file) = if self.flipped {
+          (index + 1, b'h' - index as u8)
+        } else {
+          (8 - index, b'a' + index as u8)
+        };
+
+        put(
+          buf,
+          area,
+          area.x,
+          geometry.y + index * geometry.height + geometry.height / 2,
+          &rank.to_string(),
+          Style::default()
+        );
+        put(
+          buf,
+          area,
+          geometry.x + index * geometry.width + geometry.width / 2,
+          geometry.y + 8 * geometry.height,
+          &(file as char).to_string(),
+          Style::default()
+        );
+      }
+    }
+  }
+}